@@ -24,3 +24,20 @@ pub enum ConversionError {
     #[error("The contained type does not match its target.")]
     TypeMismatch,
 }
+
+#[derive(Debug, Error, PartialEq, Eq)]
+/// Error indicating an invalid [`Tree`](crate::render::tree::Tree) modification.
+pub enum TreeError {
+    /// The referenced node index does not exist in the tree.
+    #[error("Node index {0} does not exist.")]
+    InvalidIndex(usize),
+    /// A node cannot be moved beneath itself or one of its descendants.
+    #[error("Node {0} cannot be moved beneath itself or its descendant ({1}).")]
+    CyclicMove(usize, usize),
+    /// A root node cannot be moved.
+    #[error("Root node {0} cannot be moved.")]
+    RootMove(usize),
+    /// An empty tree cannot be grafted.
+    #[error("Cannot graft an empty tree.")]
+    EmptyGraft,
+}
@@ -7,6 +7,9 @@ extern crate self as redact_composer_core;
 /// Error types.
 pub mod error;
 
+/// Progress observation and cancellation of composition.
+pub mod observer;
+
 /// Types and traits used for and during composition rendering.
 pub mod render;
 
@@ -26,22 +29,34 @@ use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
 use std::iter::successors;
 use std::ops::{Range, RangeBounds};
+use std::sync::Arc;
 use twox_hash::XxHash64;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use crate::error::ConversionError;
-use crate::render::context::CompositionContext;
+use crate::error::RendererError::MissingContext;
+use crate::observer::{CancellationToken, CompositionObserver, CompositionProgress};
+use crate::render::context::{CompositionContext, CtxQuery};
+use crate::render::stats::{RenderAttempt, RenderStats};
+use crate::render::validation::ElementType;
 use crate::render::{tree::Tree, RenderEngine, RenderSegment};
-use crate::timing::{Timing, STANDARD_BEAT_LENGTH};
+use crate::timing::{Rounding, TempoMap, Timing, STANDARD_BEAT_LENGTH};
 
 /// Contains the derive macro of [`Element`]. Specifically kept separate in core, so
 /// exporting trait vs macro can be done separately
 pub mod derive {
+    pub use redact_composer_derive::renderer_core as renderer;
     pub use redact_composer_derive::ElementCore as Element;
 }
 
+// Not public API. Used by generated code from the derive crate.
+#[doc(hidden)]
+pub mod __private {
+    pub use inventory;
+}
+
 use std::any::Any;
 
 const LOG: &str = "redact_composer";
@@ -71,18 +86,38 @@ pub trait Element: Debug + AsAny + 'static {
     fn wrapped_element(&self) -> Option<&dyn Element> {
         None
     }
+
+    /// Rescales any tick values stored within this element (such as a beat length, as opposed to
+    /// its segment's timing) to a different resolution (ticks per beat). Called for each element
+    /// by [`Composition::rescale`].
+    ///
+    /// **Default:** no-op, for elements without tick values.
+    fn rescale_ticks(
+        &mut self,
+        _from_ticks_per_beat: i32,
+        _to_ticks_per_beat: i32,
+        _rounding: Rounding,
+    ) {
+    }
 }
 
 /// Convenience trait for converting to [`&dyn Any`].
 pub trait AsAny {
     /// Converts this to a [`&dyn Any`].
     fn as_any(&self) -> &dyn Any;
+
+    /// Returns the type name of this value, as given by [`std::any::type_name`].
+    fn type_name(&self) -> &'static str;
 }
 
 impl<T: Element> AsAny for T {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
 }
 
 /// A (type-erased) [`Element`] spanning a [`Timing`] interval.
@@ -304,11 +339,11 @@ pub mod elements {
     #[element(wrapped_element = self.wrapped_element())]
     pub struct Part(pub(super) Box<dyn Element>, pub(super) PartType);
 }
-use elements::Part;
+use elements::{Part, PlayNote};
 use log::{debug, info, log_enabled, trace, warn, Level};
 
 /// Indicates whether a part is an instrument, or percussion.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PartType {
     /// Instrument part.
@@ -340,21 +375,53 @@ impl Part {
 }
 
 /// Options used by a [`Composer`].
+///
+/// Built from the defaults by chaining the setter methods:
+/// ```
+/// # use redact_composer_core::ComposerOptions;
+/// let options = ComposerOptions::new().ticks_per_beat(960).collect_stats(true);
+/// ```
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
 pub struct ComposerOptions {
     /// The number of ticks per beat.
     pub ticks_per_beat: i32,
+    /// Whether to collect [`RenderStats`] during composition, exposed via [`Composition::stats`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub collect_stats: bool,
 }
 
 impl Default for ComposerOptions {
     fn default() -> Self {
         Self {
             ticks_per_beat: STANDARD_BEAT_LENGTH,
+            collect_stats: false,
         }
     }
 }
 
+impl ComposerOptions {
+    /// Creates the default options.
+    pub fn new() -> ComposerOptions {
+        ComposerOptions::default()
+    }
+
+    /// Sets the number of ticks per beat.
+    pub fn ticks_per_beat(mut self, ticks_per_beat: i32) -> ComposerOptions {
+        self.ticks_per_beat = ticks_per_beat;
+
+        self
+    }
+
+    /// Sets whether to collect [`RenderStats`] during composition.
+    pub fn collect_stats(mut self, collect_stats: bool) -> ComposerOptions {
+        self.collect_stats = collect_stats;
+
+        self
+    }
+}
+
 /// Provides methods to create compositions using a [`RenderEngine`] and its
 /// [`Renderer`](render::Renderer)s.
 #[derive(Debug, Default)]
@@ -363,6 +430,10 @@ pub struct Composer {
     pub engine: RenderEngine,
     /// The composer's options.
     pub options: ComposerOptions,
+    // Observers notified of composition progress, see `with_observer`
+    observers: Vec<Arc<dyn CompositionObserver>>,
+    // Token checked between renders, stopping composition early once cancelled
+    cancellation: Option<CancellationToken>,
 }
 
 impl From<RenderEngine> for Composer {
@@ -402,14 +473,180 @@ impl From<ComposerOptions> for CompositionOptions {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// A composition output, including the tree of rendered segments, produced from
 /// [`Composer::compose`].
+#[non_exhaustive]
 pub struct Composition {
     /// The options used during this composition.
     pub options: CompositionOptions,
     /// The tree of rendered [`Segment`]s produced during composition.
     pub tree: Tree<RenderSegment>,
+    /// Render statistics, if enabled via [`ComposerOptions::collect_stats`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub stats: Option<RenderStats>,
+}
+
+impl Composition {
+    /// Creates a [`Composition`] from an already rendered tree, such as one built by hand or
+    /// converted from another format.
+    pub fn new(options: CompositionOptions, tree: Tree<RenderSegment>) -> Composition {
+        Composition {
+            options,
+            tree,
+            stats: None,
+        }
+    }
+
+    /// Search the composition tree for segments of type `Element`. Returns a [`CtxQuery`],
+    /// allowing further specifications before running the search. Unless otherwise specified, the
+    /// search spans the entire composition.
+    /// ```
+    /// # use redact_composer_core::{Composer, IntoSegment};
+    /// # use redact_composer_core::derive::Element;
+    /// # use redact_composer_core::elements::PlayNote;
+    /// # use redact_composer_core::render::{AdhocRenderer, RenderEngine};
+    /// # use redact_composer_core::render::context::TimingRelation::Overlapping;
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Song;
+    /// # let engine = RenderEngine::new()
+    /// #     + AdhocRenderer::<Song>::new(|seg, _| {
+    /// #         Ok(vec![PlayNote { note: 60, velocity: 100 }.over(0..480), PlayNote { note: 62, velocity: 100 }.over(480..960)])
+    /// #     });
+    /// let composition = Composer::from(engine).compose(Song.over(0..960));
+    ///
+    /// let notes = composition
+    ///     .find::<PlayNote>()
+    ///     .with_timing(Overlapping, 0..480)
+    ///     .get_all()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(notes.len(), 1);
+    /// assert_eq!(notes[0].element.note, 60);
+    /// ```
+    ///
+    /// # Panics
+    /// If the composition tree is empty.
+    pub fn find<Element: crate::Element>(
+        &self,
+    ) -> CtxQuery<'_, Element, impl Fn(&Element) -> bool> {
+        CompositionContext::new(&self.options, &self.tree, &self.tree[0], None, true)
+            .find::<Element>()
+    }
+
+    /// Returns a [`TempoMap`] of this composition, for converting between ticks and seconds.
+    pub fn tempo_map(&self) -> TempoMap {
+        TempoMap::from_tree(&self.tree, self.options.ticks_per_beat)
+    }
+
+    /// Returns all [`PlayNote`]s of the composition as `(timing, note, part_idx)` tuples ordered by
+    /// start time. `part_idx` is the tree index of the note's nearest [`Part`] ancestor, if any.
+    pub fn notes(&self) -> Vec<(Timing, PlayNote, Option<usize>)> {
+        let mut notes = self
+            .tree
+            .dfs_pre()
+            .filter_map(|node| {
+                node.value.segment.element_as::<PlayNote>().map(|note| {
+                    let part_idx = self
+                        .tree
+                        .ancestors_of(node.idx)
+                        .find(|a| a.value.segment.element_as::<Part>().is_some())
+                        .map(|a| a.idx);
+
+                    (node.value.segment.timing, *note, part_idx)
+                })
+            })
+            .collect::<Vec<_>>();
+        notes.sort_by_key(|(timing, _, _)| timing.start);
+
+        notes
+    }
+
+    /// Rescales the timing of every segment in this composition to a new resolution (ticks per
+    /// beat), rounding according to the given [`Rounding`] policy. Returns a [`RescaleReport`],
+    /// which notably includes any segments collapsing to zero length as a result.
+    ///
+    /// Tick values stored within elements themselves are rescaled via
+    /// [`Element::rescale_ticks`].
+    /// ```
+    /// # use redact_composer_core::{Composer, IntoSegment};
+    /// # use redact_composer_core::derive::Element;
+    /// # use redact_composer_core::render::{AdhocRenderer, RenderEngine};
+    /// # use redact_composer_core::timing::{Rounding, Timing};
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Song;
+    /// let mut composition = Composer::from(RenderEngine::new()).compose(Song.over(0..960));
+    /// let report = composition.rescale(96, Rounding::Nearest);
+    ///
+    /// assert_eq!(composition.options.ticks_per_beat, 96);
+    /// assert_eq!(composition.tree[0].value.segment.timing, Timing::from(0..192));
+    /// assert!(report.collapsed.is_empty());
+    /// ```
+    ///
+    /// # Panics
+    /// If either this composition's current or the new ticks per beat is not positive.
+    pub fn rescale(&mut self, new_ticks_per_beat: i32, rounding: Rounding) -> RescaleReport {
+        let old_ticks_per_beat = self.options.ticks_per_beat;
+        assert!(
+            old_ticks_per_beat > 0 && new_ticks_per_beat > 0,
+            "Ticks per beat must be positive (rescaling {:?} -> {:?}).",
+            old_ticks_per_beat,
+            new_ticks_per_beat
+        );
+        let mut collapsed = vec![];
+
+        for idx in 0..self.tree.len() {
+            let segment = &mut self.tree[idx].value.segment;
+            segment
+                .element
+                .rescale_ticks(old_ticks_per_beat, new_ticks_per_beat, rounding);
+
+            let timing = &mut segment.timing;
+            let rescaled = timing.rescaled(old_ticks_per_beat, new_ticks_per_beat, rounding);
+
+            if rescaled.is_empty() && !timing.is_empty() {
+                collapsed.push(idx);
+            }
+            *timing = rescaled;
+        }
+        self.options.ticks_per_beat = new_ticks_per_beat;
+
+        if !collapsed.is_empty() {
+            warn!(target: LOG, "{:?} segments collapsed to zero length while rescaling.", collapsed.len());
+        }
+
+        RescaleReport {
+            from_ticks_per_beat: old_ticks_per_beat,
+            to_ticks_per_beat: new_ticks_per_beat,
+            collapsed,
+        }
+    }
+}
+
+/// Outcome of [`Composition::rescale`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RescaleReport {
+    /// The previous ticks per beat.
+    pub from_ticks_per_beat: i32,
+    /// The new ticks per beat.
+    pub to_ticks_per_beat: i32,
+    /// Tree indices of segments which collapsed to zero length (previously non-empty).
+    pub collapsed: Vec<usize>,
 }
 
 impl Composer {
+    /// Adds an observer to be notified of composition progress.
+    pub fn with_observer(mut self, observer: Arc<dyn CompositionObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Sets a [`CancellationToken`] which can be used to stop composition early.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
     /// Generates a [`Composition`] from a starting [Segment].
     pub fn compose(&self, seg: Segment) -> Composition {
         let mut hasher = XxHash64::with_seed(0);
@@ -419,8 +656,79 @@ impl Composer {
     /// Generates a [`Composition`] from a starting [Segment], using a seed to to
     /// create a reproducible output.
     pub fn compose_with_seed(&self, seg: Segment, seed: u64) -> Composition {
+        self.compose_with_seed_and_context(seg, seed, [])
+    }
+
+    /// Generates a [`Composition`] from a starting [Segment], with pre-existing [`Composition`]s
+    /// (such as an imported MIDI file) available as context during rendering.
+    ///
+    /// Each context [`Composition`]'s tree is inserted as a child of the starting segment before
+    /// rendering begins (rescaled to this composer's
+    /// [`ticks_per_beat`](ComposerOptions::ticks_per_beat) if necessary). All of its nodes are
+    /// marked as rendered, so they are never processed by the [`RenderEngine`] but can be found
+    /// via [`CompositionContext::find`]. They are also marked as
+    /// [`context`](RenderSegment::context), allowing consumers of the output to tell them apart
+    /// from rendered nodes.
+    /// ```
+    /// # use redact_composer_core::{Composer, Composition, IntoSegment};
+    /// # use redact_composer_core::derive::Element;
+    /// # use redact_composer_core::elements::PlayNote;
+    /// # use redact_composer_core::render::{AdhocRenderer, RenderEngine};
+    /// # use redact_composer_core::render::context::TimingRelation::During;
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Song;
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Reference;
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Harmony;
+    /// # let reference = Composer::from(RenderEngine::new() + AdhocRenderer::<Reference>::new(|r, _| {
+    /// #     Ok(vec![PlayNote { note: 60, velocity: 100 }.over(r)])
+    /// # })).compose(Reference.over(0..1920));
+    /// // `reference` could be a `Composition` imported from MIDI, for example
+    /// let engine = RenderEngine::new()
+    ///     + AdhocRenderer::<Song>::new(|song, _| Ok(vec![Harmony.over(song)]))
+    ///     + AdhocRenderer::<Harmony>::new(|harmony, ctx| {
+    ///         let reference_note = ctx
+    ///             .find::<PlayNote>()
+    ///             .with_timing(During, harmony)
+    ///             .within::<Reference>()
+    ///             .require()?;
+    ///         let note = reference_note.element.note + 4;
+    ///
+    ///         Ok(vec![PlayNote { note, velocity: 100 }.over(reference_note)])
+    ///     });
+    ///
+    /// let composition = Composer::from(engine).compose_with_context(Song.over(0..960), [reference]);
+    /// assert!(composition.tree.iter().all(|n| n.value.rendered));
+    /// ```
+    pub fn compose_with_context(
+        &self,
+        seg: Segment,
+        context: impl IntoIterator<Item = Composition>,
+    ) -> Composition {
+        let mut hasher = XxHash64::with_seed(0);
+        thread_rng().next_u64().hash(&mut hasher);
+        self.compose_with_seed_and_context(seg, hasher.finish(), context)
+    }
+
+    /// Generates a [`Composition`] from a starting [Segment] and context [`Composition`]s, using a
+    /// seed to create a reproducible output. See [`Composer::compose_with_context`].
+    pub fn compose_with_seed_and_context(
+        &self,
+        seg: Segment,
+        seed: u64,
+        context: impl IntoIterator<Item = Composition>,
+    ) -> Composition {
         info!(target: LOG, "Composing {:?} with seed {:?}.", seg, seed);
         debug!(target: LOG, "{:?}", self.options);
+        if log_enabled!(target: LOG, Level::Debug) {
+            let wrapped = successors(seg.element.wrapped_element(), |s| s.wrapped_element());
+            let report = self.engine.validate_root(
+                ElementType::from(&*seg.element),
+                wrapped.map(ElementType::from),
+            );
+            debug!(target: LOG, "{}", report);
+        }
         let start_time = std::time::Instant::now();
         let options: CompositionOptions = self.options.into();
         let mut render_tree = Tree::new();
@@ -431,22 +739,90 @@ impl Composer {
                 seed,
                 segment: seg,
                 error: None,
+                context: false,
             },
             None,
         );
         type_cache.insert(node_id, HashSet::default());
 
+        for mut context_composition in context {
+            if context_composition.options.ticks_per_beat <= 0 {
+                warn!(target: LOG, "Skipping context composition with invalid ticks per beat: {:?}.",
+                    context_composition.options.ticks_per_beat);
+                continue;
+            }
+            if context_composition.options.ticks_per_beat != options.ticks_per_beat {
+                context_composition.rescale(options.ticks_per_beat, Rounding::Nearest);
+            }
+            if context_composition.tree.is_empty() {
+                continue;
+            }
+
+            let offset = render_tree.len();
+            render_tree
+                .graft(context_composition.tree, 0)
+                .expect("Root node exists.");
+            debug!(target: LOG, "Inserted {:?} context nodes.", render_tree.len() - offset);
+
+            for idx in offset..render_tree.len() {
+                render_tree[idx].value.rendered = true;
+                render_tree[idx].value.context = true;
+                type_cache.insert(idx, HashSet::default());
+
+                let type_ids = successors(Some(&*render_tree[idx].value.segment.element), |s| {
+                    s.wrapped_element()
+                })
+                .map(|s| s.as_any().type_id())
+                .collect::<Vec<_>>();
+                for ancestor_idx in successors(render_tree[idx].parent, |p| render_tree[*p].parent)
+                {
+                    type_cache[ancestor_idx].extend(type_ids.iter().copied());
+                }
+            }
+        }
+
+        let mut render_attempts = vec![];
+        let mut progress = CompositionProgress {
+            renderable: usize::from(
+                self.engine
+                    .can_render(&*render_tree[0].value.segment.element),
+            ),
+            ..Default::default()
+        };
+        let is_cancelled = || {
+            self.cancellation
+                .as_ref()
+                .is_some_and(CancellationToken::is_cancelled)
+        };
+
         // Nodes are rendered in depth-first order, meaning any children of a node will be rendered
         // before its siblings (assuming their required context is available). Nodes which cannot be
         // rendered (due to missing context) are skipped until their context dependencies are met.
         //
+        // Nodes whose renderers declare their requirements are not attempted until those are met.
+        // Instead, pending nodes which may produce an unmet requirement (according to declared
+        // renderer outputs) are moved to be rendered next, ahead of any others.
+        //
         // `render_stack` keeps track the (reverse) sequence of node ids to render, enabling this
         // depth-first ordering without having to do any element shifting.
+        //
+        // Once no further progress can be made, remaining nodes are retried in a `stalled` state
+        // where soft context dependencies resolve (to `None`) instead of erroring. The first
+        // successful render during this state resumes normal rendering, since it may have satisfied
+        // other nodes' context.
         let mut render_stack = vec![0];
-        loop {
+        let mut stalled = false;
+        'passes: loop {
             let mut added_node_count = 0;
+            let mut rendered_node_count = 0;
 
             for render_stack_idx in (0_usize..render_stack.len()).rev() {
+                if is_cancelled() {
+                    info!(target: LOG, "Composition cancelled.");
+                    self.observers.iter().for_each(|o| o.on_cancelled(progress));
+                    break 'passes;
+                }
+
                 let node_idx = render_stack[render_stack_idx];
                 let is_top_of_render_stack = render_stack_idx + 1 == render_stack.len();
 
@@ -463,13 +839,65 @@ impl Composer {
                     &render_tree,
                     &render_tree[node_idx],
                     Some(&type_cache),
+                    stalled,
                 );
 
+                // Nodes with unmet declared requirements are deferred without invoking their
+                // renderer, same as if it had failed due to missing context.
+                if let Some(requirement) = self
+                    .engine
+                    .requirements(&*render_tree[node_idx].value.segment.element)
+                    .into_iter()
+                    .find(|r| !r.is_met(&composition_context))
+                {
+                    trace!(target: LOG, "Deferring (Node idx: {:?}) until its requirement for {:?} is met.",
+                        node_idx, requirement.type_name());
+                    // The remaining nodes of this pass are visited from the top of this range
+                    let (producers, others): (Vec<usize>, Vec<usize>) =
+                        render_stack[..render_stack_idx].iter().partition(|idx| {
+                            !render_tree[**idx].value.rendered
+                                && self.engine.may_produce(
+                                    &*render_tree[**idx].value.segment.element,
+                                    requirement.type_id(),
+                                )
+                        });
+                    if !producers.is_empty() {
+                        trace!(target: LOG, "Rendering producers (Node idx: {:?}) of {:?} next.",
+                            producers, requirement.type_name());
+                        render_stack
+                            .splice(..render_stack_idx, others.into_iter().chain(producers));
+                    }
+
+                    let err = MissingContext(requirement.type_name().to_string());
+                    self.observers
+                        .iter()
+                        .for_each(|o| o.on_render_error(&render_tree[node_idx], &err));
+                    render_tree[node_idx].value.error = Some(err);
+                    continue;
+                }
+
                 trace!(target: LOG, "Rendering: {:?}", &render_tree[node_idx]);
+                let render_start = std::time::Instant::now();
                 let result = self
                     .engine
                     .render(&render_tree[node_idx].value.segment, composition_context);
 
+                if let (true, Some(render_res)) = (self.options.collect_stats, &result) {
+                    render_attempts.push(RenderAttempt {
+                        node_idx,
+                        element_type: render_tree[node_idx]
+                            .value
+                            .segment
+                            .element
+                            .type_name()
+                            .to_string(),
+                        start: render_start.duration_since(start_time),
+                        duration: render_start.elapsed(),
+                        success: render_res.is_ok(),
+                        children: render_res.as_ref().map_or(0, |segments| segments.len()),
+                    });
+                }
+
                 if let Some(render_res) = result {
                     match render_res {
                         // Case: Unable to render -- most commonly missing required context
@@ -477,6 +905,9 @@ impl Composer {
                         crate::render::Result::Err(err) => {
                             trace!(target: LOG, "Rendering (Node idx: {:?}) was unsuccessful: {:?}",
                                 &render_tree[node_idx].idx, err);
+                            self.observers
+                                .iter()
+                                .for_each(|o| o.on_render_error(&render_tree[node_idx], &err));
                             render_tree[node_idx].value.error = Some(err);
                         }
                         // Case: Successfully rendered
@@ -508,10 +939,12 @@ impl Composer {
                                     },
                                     segment: s,
                                     error: None,
+                                    context: false,
                                 })
                                 .collect();
 
                             added_node_count += children.len();
+                            progress.renderable += children.iter().filter(|c| !c.rendered).count();
                             let mut added_node_ids = vec![];
 
                             for child in children {
@@ -535,6 +968,11 @@ impl Composer {
 
                             render_tree[node_idx].value.rendered = true;
                             render_tree[node_idx].value.error = None;
+                            rendered_node_count += 1;
+                            progress.rendered += 1;
+                            self.observers
+                                .iter()
+                                .for_each(|o| o.on_node_rendered(&render_tree[node_idx], progress));
 
                             // Nodes are only rendered once so it can be removed if at the top of the stack.
                             // If not at the top, it will be removed at a later iteration (preventing
@@ -549,7 +987,8 @@ impl Composer {
 
                             // Breaking here ensures depth-first rendering by starting the iteration over
                             // from the top of the render_stack (which is where the newly added nodes are).
-                            if added_node_count > 0 {
+                            // While stalled, any successful render resumes normal rendering.
+                            if added_node_count > 0 || stalled {
                                 break;
                             }
                         }
@@ -557,9 +996,30 @@ impl Composer {
                 }
             }
 
-            // If no nodes were added, no further progress can be made -- rendering complete.
-            if added_node_count == 0 {
-                break;
+            progress.passes += 1;
+            self.observers
+                .iter()
+                .for_each(|o| o.on_pass_complete(progress));
+
+            if stalled {
+                // If nothing rendered while stalled, no further progress can be made -- rendering complete.
+                if rendered_node_count == 0 {
+                    break;
+                }
+
+                stalled = false;
+            } else if added_node_count == 0 {
+                // If no nodes were added, no further progress can be made without falling back to
+                // soft dependency defaults. Retry any remaining nodes in a stalled state.
+                if render_stack
+                    .iter()
+                    .all(|node_idx| render_tree[*node_idx].value.rendered)
+                {
+                    break;
+                }
+
+                trace!(target: LOG, "No further progress possible. Retrying unrendered nodes as stalled.");
+                stalled = true;
             }
         }
 
@@ -576,6 +1036,10 @@ impl Composer {
         Composition {
             options: self.options.into(),
             tree: render_tree,
+            stats: self.options.collect_stats.then_some(RenderStats {
+                duration,
+                attempts: render_attempts,
+            }),
         }
     }
 }
@@ -0,0 +1,95 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::RendererError;
+use crate::render::tree::Node;
+use crate::render::RenderSegment;
+
+/// Receives progress callbacks during [`Composer::compose`](crate::Composer::compose).
+///
+/// All methods have no-op defaults, so only the callbacks of interest need be implemented. Since
+/// callbacks receive `&self`, any state should use interior mutability (such as atomics or a
+/// `Mutex`). Observers are `Send + Sync`, so the same observer can be shared between the thread
+/// composing and another thread reporting progress (e.g. a GUI).
+/// ```
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// # use redact_composer_core::observer::{CompositionObserver, CompositionProgress};
+/// # use redact_composer_core::render::{tree::Node, RenderSegment};
+/// #[derive(Debug, Default)]
+/// struct RenderCounter(AtomicUsize);
+///
+/// impl CompositionObserver for RenderCounter {
+///     fn on_node_rendered(&self, _node: &Node<RenderSegment>, _progress: CompositionProgress) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+/// ```
+pub trait CompositionObserver: Debug + Send + Sync {
+    /// Called after a node has successfully rendered. Its children are already inserted into the
+    /// tree.
+    fn on_node_rendered(&self, _node: &Node<RenderSegment>, _progress: CompositionProgress) {}
+
+    /// Called after a node's render attempt has failed. The node may be retried later (most
+    /// commonly due to [`MissingContext`](RendererError::MissingContext)).
+    fn on_render_error(&self, _node: &Node<RenderSegment>, _error: &RendererError) {}
+
+    /// Called after each pass over the unrendered nodes.
+    fn on_pass_complete(&self, _progress: CompositionProgress) {}
+
+    /// Called if composition stops early due to a cancelled [`CancellationToken`].
+    fn on_cancelled(&self, _progress: CompositionProgress) {}
+}
+
+/// A snapshot of composition progress, provided to [`CompositionObserver`] callbacks.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct CompositionProgress {
+    /// The number of completed render passes.
+    pub passes: usize,
+    /// The number of nodes rendered so far.
+    pub rendered: usize,
+    /// The number of renderable nodes known so far (rendered or not).
+    pub renderable: usize,
+}
+
+impl CompositionProgress {
+    /// An estimate of the completed fraction (`0.0..=1.0`) of the composition. Since rendering
+    /// produces new nodes, this is only an estimate and may decrease as new nodes are discovered.
+    pub fn fraction(&self) -> f32 {
+        if self.renderable == 0 {
+            1.0
+        } else {
+            self.rendered as f32 / self.renderable as f32
+        }
+    }
+}
+
+/// A shareable token enabling cooperative cancellation of composition. The token is checked
+/// between renders, returning the partially rendered composition once cancelled.
+/// ```
+/// # use redact_composer_core::observer::CancellationToken;
+/// let token = CancellationToken::new();
+/// let handle = token.clone();
+///
+/// handle.cancel();
+/// assert!(token.is_cancelled());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a new (uncancelled) token.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation, affecting all clones of this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if cancellation has been requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -15,9 +15,9 @@ use crate::render::{
     tree::{Node, Tree},
     Result,
 };
-use crate::timing::RangeOps;
-use crate::SegmentRef;
+use crate::timing::{RangeOps, TempoMap};
 use crate::{CompositionOptions, Element};
+use crate::{Segment, SegmentRef};
 
 use crate::error::RendererError::MissingContext;
 use crate::render::context::TimingRelation::*;
@@ -35,6 +35,7 @@ pub struct CompositionContext<'a> {
     pub(crate) tree: &'a Tree<RenderSegment>,
     pub(crate) start: &'a Node<RenderSegment>,
     pub(crate) type_cache: Option<&'a Vec<HashSet<TypeId>>>,
+    pub(crate) stalled: bool,
 }
 
 impl Copy for CompositionContext<'_> {}
@@ -51,20 +52,24 @@ impl<'a> CompositionContext<'a> {
         tree: &'a Tree<RenderSegment>,
         start: &'a Node<RenderSegment>,
         type_cache: Option<&'a Vec<HashSet<TypeId>>>,
+        stalled: bool,
     ) -> CompositionContext<'a> {
         CompositionContext {
             options,
             tree,
             start,
             type_cache,
+            stalled,
         }
     }
 
     /// Search the in-progress composition tree for nodes of type `Element`.
     /// Returns a [`CtxQuery`], allowing further specifications before running the search.
-    pub fn find<Element: crate::Element>(&self) -> CtxQuery<Element, impl Fn(&Element) -> bool> {
+    pub fn find<Element: crate::Element>(
+        &self,
+    ) -> CtxQuery<'a, Element, impl Fn(&Element) -> bool> {
         CtxQuery {
-            ctx: self,
+            ctx: *self,
             timing: None,
             scope: None,
             where_fn: |_| true,
@@ -72,6 +77,98 @@ impl<'a> CompositionContext<'a> {
         }
     }
 
+    /// Returns the currently rendering segment's parent as a [`SegmentRef`] if it is of type
+    /// `Element`, otherwise [`None`] (also [`None`] for the root).
+    pub fn parent<Element: crate::Element>(&self) -> Option<SegmentRef<'a, Element>> {
+        self.start
+            .parent
+            .and_then(|p_idx| (&self.tree[p_idx].value.segment).try_into().ok())
+    }
+
+    /// Returns the currently rendering segment's ancestors of type `Element`, ordered from
+    /// nearest (parent) to furthest (root).
+    pub fn ancestors<Element: crate::Element>(&self) -> Vec<SegmentRef<'a, Element>> {
+        let tree = self.tree;
+
+        successors(self.start.parent, |p_idx| tree[*p_idx].parent)
+            .filter_map(|idx| (&tree[idx].value.segment).try_into().ok())
+            .collect()
+    }
+
+    /// Returns the currently rendering segment's siblings of type `Element` (excluding itself), in
+    /// the order they were produced by their parent. Siblings are returned regardless of whether
+    /// they have been rendered yet.
+    pub fn siblings<Element: crate::Element>(&self) -> Vec<SegmentRef<'a, Element>> {
+        let tree = self.tree;
+
+        self.start
+            .parent
+            .map(|p_idx| {
+                tree[p_idx]
+                    .children
+                    .iter()
+                    .filter(|idx| **idx != self.start.idx)
+                    .filter_map(|idx| (&tree[*idx].value.segment).try_into().ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the segments from the root to the currently rendering segment (inclusive). Since
+    /// these are of varying [`Element`] types, each can be viewed as a typed [`SegmentRef`] via
+    /// [`TryFrom`] (or [`Segment::element_as`]).
+    /// ```
+    /// # use redact_composer_core::{Composer, IntoSegment, SegmentRef};
+    /// # use redact_composer_core::derive::Element;
+    /// # use redact_composer_core::render::{AdhocRenderer, RenderEngine};
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Root;
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Child;
+    /// let engine = RenderEngine::new()
+    ///     + AdhocRenderer::<Root>::new(|seg, _| Ok(vec![Child.over(seg)]))
+    ///     + AdhocRenderer::<Child>::new(|_, ctx| {
+    ///         let path = ctx.node_path();
+    ///         assert_eq!(path.len(), 2);
+    ///         assert!(SegmentRef::<Root>::try_from(path[0]).is_ok());
+    ///         assert!(path[1].element_as::<Child>().is_some());
+    ///         assert_eq!(ctx.depth(), 1);
+    ///         Ok(vec![])
+    ///     });
+    ///
+    /// Composer::from(engine).compose(Root.over(0..10));
+    /// ```
+    pub fn node_path(&self) -> Vec<&'a Segment> {
+        let tree = self.tree;
+        let mut path = successors(Some(self.start), |node| node.parent.map(|idx| &tree[idx]))
+            .map(|node| &node.value.segment)
+            .collect::<Vec<_>>();
+        path.reverse();
+
+        path
+    }
+
+    /// Returns the depth of the currently rendering segment within the composition tree (the root
+    /// being depth `0`).
+    pub fn depth(&self) -> usize {
+        successors(self.start.parent, |p_idx| self.tree[*p_idx].parent).count()
+    }
+
+    /// Returns `true` if the composer is unable to make further progress, and is re-invoking
+    /// renderers whose context dependencies remain unsatisfied. In this case, soft dependencies
+    /// (such as [`CtxQuery::wait_for`]) resolve to [`None`] rather than [`MissingContext`], giving
+    /// the renderer a chance to fall back to a default.
+    pub fn is_stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// Returns a [`TempoMap`] of the in-progress composition. Only includes tempos which have been
+    /// rendered so far -- use [`find`](Self::find) with [`Tempo`](crate::timing::elements::Tempo)
+    /// (or [`TempoRamp`](crate::timing::elements::TempoRamp)) to require them first if needed.
+    pub fn tempo_map(&self) -> TempoMap {
+        TempoMap::from_tree(self.tree, self.options.ticks_per_beat)
+    }
+
     /// Returns the composition beat length. A composition's tempo (BPM) is relative to this value.
     pub fn beat_length(&self) -> i32 {
         self.options.ticks_per_beat
@@ -101,8 +198,8 @@ impl<'a> CompositionContext<'a> {
         where_clause: impl Fn(&F) -> bool,
         relation: TimingConstraint,
         scope: SearchScope,
-    ) -> Option<Vec<SegmentRef<F>>> {
-        let mut matching_segments: Vec<SegmentRef<F>> = vec![];
+    ) -> Option<Vec<SegmentRef<'a, F>>> {
+        let mut matching_segments: Vec<SegmentRef<'a, F>> = vec![];
 
         let search_start = (match scope {
             SearchScope::WithinAncestor(t) => successors(Some(self.start), |node| {
@@ -190,10 +287,11 @@ impl<'a> CompositionContext<'a> {
     }
 }
 
-/// A context query builder. Initiate a query via [`CompositionContext::find`].
+/// A context query builder. Initiate a query via [`CompositionContext::find`] (during rendering)
+/// or [`Composition::find`](crate::Composition::find) (after rendering).
 #[derive(Debug)]
 pub struct CtxQuery<'a, S: Element, F: Fn(&S) -> bool> {
-    ctx: &'a CompositionContext<'a>,
+    ctx: CompositionContext<'a>,
     timing: Option<TimingConstraint>,
     scope: Option<SearchScope>,
     where_fn: F,
@@ -298,10 +396,144 @@ impl<'a, S: Element, F: Fn(&S) -> bool> CtxQuery<'a, S, F> {
         self.get_at_least(min_requested)
             .ok_or(MissingContext(type_name::<S>().to_string()))
     }
+
+    /// Runs the context query as a soft dependency, returning a single optional result. If none are
+    /// found, a [`MissingContext`] error is returned (and the render retried later) unless the
+    /// composer can make no further progress, in which case [`None`] is returned so the renderer
+    /// can fall back to a default.
+    ///
+    /// ```
+    /// # use redact_composer_core::{Composer, IntoSegment};
+    /// # use redact_composer_core::derive::Element;
+    /// # use redact_composer_core::elements::PlayNote;
+    /// # use redact_composer_core::render::{AdhocRenderer, RenderEngine};
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Melody;
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Velocity(u8);
+    /// let renderer = AdhocRenderer::<Melody>::new(|segment, context| {
+    ///     // Use a `Velocity` if one is (or will eventually be) available, otherwise default to 100
+    ///     let velocity = context
+    ///         .find::<Velocity>()
+    ///         .wait_for()?
+    ///         .map(|v| v.element.0)
+    ///         .unwrap_or(100);
+    ///
+    ///     Ok(vec![PlayNote { note: 60, velocity }.over(segment)])
+    /// });
+    /// # let composition = Composer::from(RenderEngine::new() + renderer).compose(Melody.over(0..1));
+    /// # assert!(composition.tree.root().unwrap().value.rendered);
+    /// ```
+    pub fn wait_for(self) -> Result<Option<SegmentRef<'a, S>>> {
+        let stalled = self.ctx.stalled;
+
+        match self.get() {
+            None if !stalled => Err(MissingContext(type_name::<S>().to_string())),
+            result => Ok(result),
+        }
+    }
+
+    /// Runs the context query as a soft dependency, returning all results. If none are found, a
+    /// [`MissingContext`] error is returned (and the render retried later) unless the composer can
+    /// make no further progress, in which case [`None`] is returned.
+    pub fn wait_for_all(self) -> Result<Option<Vec<SegmentRef<'a, S>>>> {
+        self.wait_for_at_least(1)
+    }
+
+    /// Runs the context query as a soft dependency. If at least `min_requested` results are found
+    /// they are returned. Otherwise, a [`MissingContext`] error is returned (and the render retried
+    /// later) unless the composer can make no further progress, in which case [`None`] is returned.
+    pub fn wait_for_at_least(self, min_requested: usize) -> Result<Option<Vec<SegmentRef<'a, S>>>> {
+        let stalled = self.ctx.stalled;
+
+        match self.get_at_least(min_requested) {
+            None if !stalled => Err(MissingContext(type_name::<S>().to_string())),
+            result => Ok(result),
+        }
+    }
+}
+
+/// A context dependency declared ahead of time by a [`Renderer`](crate::render::Renderer) (via
+/// [`Renderer::requirements`](crate::render::Renderer::requirements)), describing an [`Element`]
+/// type which must be present with a given [`TimingRelation`] to the segment being rendered.
+///
+/// The composer defers rendering a segment until its (non-soft) requirements are met, rather than
+/// invoking its renderer only for it to fail with [`MissingContext`]. Declared requirements are
+/// only used for scheduling -- renderers still query their context as usual.
+/// ```
+/// # use redact_composer_core::derive::Element;
+/// # use redact_composer_core::render::context::{ContextRequirement, TimingRelation::During};
+/// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+/// # struct Key;
+/// let requirement = ContextRequirement::new::<Key>(During);
+///
+/// assert_eq!(requirement.relation(), During);
+/// assert!(!requirement.is_soft());
+/// assert!(requirement.soft().is_soft());
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct ContextRequirement {
+    type_id: TypeId,
+    type_name: &'static str,
+    relation: TimingRelation,
+    soft: bool,
+    is_met: fn(&CompositionContext, TimingRelation) -> bool,
+}
+
+impl ContextRequirement {
+    /// Creates a requirement for an `Element` with the given [`TimingRelation`] to the segment
+    /// being rendered.
+    pub fn new<Element: crate::Element>(relation: TimingRelation) -> ContextRequirement {
+        ContextRequirement {
+            type_id: TypeId::of::<Element>(),
+            type_name: type_name::<Element>(),
+            relation,
+            soft: false,
+            is_met: |ctx, relation| {
+                ctx.find::<Element>()
+                    .with_timing(relation, ctx.start.value.segment.timing)
+                    .get()
+                    .is_some()
+            },
+        }
+    }
+
+    /// Makes this a soft requirement (matching [`CtxQuery::wait_for`]), which no longer defers
+    /// rendering once the composer is unable to make further progress.
+    pub fn soft(mut self) -> ContextRequirement {
+        self.soft = true;
+
+        self
+    }
+
+    /// The [`TypeId`] of the required [`Element`].
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// The type name of the required [`Element`].
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// The required [`TimingRelation`] to the segment being rendered.
+    pub fn relation(&self) -> TimingRelation {
+        self.relation
+    }
+
+    /// Returns `true` if this is a soft requirement.
+    pub fn is_soft(&self) -> bool {
+        self.soft
+    }
+
+    /// Determines if this requirement is met for the segment of the given context.
+    pub fn is_met(&self, context: &CompositionContext) -> bool {
+        (self.soft && context.stalled) || (self.is_met)(context, self.relation)
+    }
 }
 
 /// Describes a timing relationship to reference time range.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum TimingRelation {
     /// Describes a relationship for a target whose time range fully includes the reference time range.
     During,
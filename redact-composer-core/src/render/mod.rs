@@ -1,15 +1,26 @@
 /// Context structs involved during composition rendering.
 pub mod context;
 
+/// Render statistics collected during composition.
+pub mod stats;
+
 /// Basic n-ary tree implementation.
 pub mod tree;
 
+/// Static validation of a [`RenderEngine`] based on declared renderer outputs and requirements.
+pub mod validation;
+
 use crate::error::RendererError;
 
 use std::fmt::Formatter;
 use std::iter::successors;
 use std::ops::Deref;
-use std::{any::TypeId, collections::HashMap, fmt::Debug, ops::Add};
+use std::{
+    any::TypeId,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Debug,
+    ops::Add,
+};
 use Vec;
 
 use crate::{Element, Segment, SegmentRef};
@@ -17,7 +28,8 @@ use crate::{Element, Segment, SegmentRef};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::render::context::CompositionContext;
+use crate::render::context::{CompositionContext, ContextRequirement};
+use crate::render::validation::ElementType;
 
 /// [`Result`](std::result::Result) with a default error type of [`RendererError`].
 pub type Result<T, E = RendererError> = std::result::Result<T, E>;
@@ -28,7 +40,8 @@ pub type Result<T, E = RendererError> = std::result::Result<T, E>;
 /// [`SegmentRef<Self::Element>`](crate::SegmentRef<Self::Element>) with
 /// [`CompositionContext`] and may return [`Vec<Segment`>] on success, or
 /// [`RendererError::MissingContext`] in the case that its render dependencies are not satisfied
-/// (which will be retried later).
+/// (which will be retried later). Context which is optional, but preferred if it will eventually be
+/// available, can be requested via [`CtxQuery::wait_for`](crate::render::context::CtxQuery::wait_for).
 pub trait Renderer {
     /// The particular [`Element`] this [`Renderer`] renders.
     type Element: Element;
@@ -40,6 +53,30 @@ pub trait Renderer {
         segment: SegmentRef<Self::Element>,
         context: CompositionContext,
     ) -> Result<Vec<Segment>>;
+
+    /// Context dependencies this [`Renderer`] declares ahead of time. Segments are not rendered
+    /// until these are met, allowing the composer to render them in dependency order rather than
+    /// retrying failed renders. Renderers are not required to declare their dependencies.
+    ///
+    /// **Default:** no requirements.
+    fn requirements(&self) -> Vec<ContextRequirement> {
+        vec![]
+    }
+
+    /// [`Element`] types this [`Renderer`] may produce. Used for validation (see
+    /// [`RenderEngine::validate`]), and so that segments producing another's declared
+    /// [`requirements`](Renderer::requirements) are rendered first. Renderers are not required to
+    /// declare their outputs, in which case this is [`None`] -- as opposed to `Some(vec![])`, which
+    /// declares that the renderer produces no further elements.
+    ///
+    /// Elements produced wrapped in another (such as `X` of [`Part::instrument(X)`](crate::elements::Part::instrument))
+    /// are declared by their wrapped type, since that is what will be rendered. The wrapper type
+    /// may also be declared, if it is required elsewhere.
+    ///
+    /// **Default:** [`None`] (no declared outputs).
+    fn outputs(&self) -> Option<Vec<ElementType>> {
+        None
+    }
 }
 
 /// Wraps a [`Segment`] with additional render-related information.
@@ -56,6 +93,14 @@ pub struct RenderSegment {
     /// Stores the latest encountered [`RendererError`] for debugging.
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub error: Option<RendererError>,
+    /// `true` if this segment was inserted from a context [`Composition`](crate::Composition)
+    /// (see [`Composer::compose_with_context`](crate::Composer::compose_with_context)), rather
+    /// than rendered during composition.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    pub context: bool,
 }
 
 /// Implements a [`Renderer`] via a wrapped closure.
@@ -83,6 +128,8 @@ pub struct AdhocRenderer<T: Element> {
     /// [`Renderer::render`](crate::render::Renderer::render).
     #[allow(clippy::type_complexity)]
     func: Box<dyn Fn(SegmentRef<T>, CompositionContext) -> Result<Vec<Segment>>>,
+    requirements: Vec<ContextRequirement>,
+    outputs: Option<Vec<ElementType>>,
 }
 
 impl<T: Element> AdhocRenderer<T> {
@@ -92,8 +139,36 @@ impl<T: Element> AdhocRenderer<T> {
     ) -> AdhocRenderer<T> {
         AdhocRenderer {
             func: Box::new(func),
+            requirements: vec![],
+            outputs: None,
         }
     }
+
+    /// Declares a context dependency of this renderer. See [`Renderer::requirements`].
+    pub fn requiring(mut self, requirement: ContextRequirement) -> AdhocRenderer<T> {
+        self.requirements.push(requirement);
+
+        self
+    }
+
+    /// Declares an [`Element`] type produced by this renderer (or wrapped by an element it
+    /// produces). See [`Renderer::outputs`].
+    pub fn producing<Output: Element>(mut self) -> AdhocRenderer<T> {
+        self.outputs
+            .get_or_insert_with(Vec::new)
+            .push(ElementType::of::<Output>());
+
+        self
+    }
+
+    /// Declares that this renderer produces no [`Element`]s (other than any declared via
+    /// [`producing`](Self::producing)). Unlike leaving its outputs undeclared, validation can then
+    /// rule out this renderer producing anything else. See [`Renderer::outputs`].
+    pub fn producing_nothing(mut self) -> AdhocRenderer<T> {
+        self.outputs.get_or_insert_with(Vec::new);
+
+        self
+    }
 }
 
 impl<T: Element> Renderer for AdhocRenderer<T> {
@@ -107,6 +182,14 @@ impl<T: Element> Renderer for AdhocRenderer<T> {
     ) -> Result<Vec<Segment>> {
         (self.func)(segment, context)
     }
+
+    fn requirements(&self) -> Vec<ContextRequirement> {
+        self.requirements.clone()
+    }
+
+    fn outputs(&self) -> Option<Vec<ElementType>> {
+        self.outputs.clone()
+    }
 }
 
 /// A group of [`Renderer`]s for a single [`Renderer::Element`]. This group is itself a
@@ -160,31 +243,67 @@ impl<T: Element> Renderer for RendererGroup<T> {
 
         Ok(result_children)
     }
+
+    fn requirements(&self) -> Vec<ContextRequirement> {
+        self.renderers
+            .iter()
+            .flat_map(|renderer| renderer.requirements())
+            .collect()
+    }
+
+    /// Combined outputs of this group's [`Renderer`]s, or [`None`] if any of them does not
+    /// declare its outputs.
+    fn outputs(&self) -> Option<Vec<ElementType>> {
+        self.renderers
+            .iter()
+            .map(|renderer| renderer.outputs())
+            .try_fold(vec![], |mut outputs, declared| {
+                outputs.append(&mut declared?);
+                Some(outputs)
+            })
+    }
 }
 
 trait ErasedRenderer {
     fn render(&self, segment: &Segment, context: CompositionContext) -> Result<Vec<Segment>>;
+    fn requirements(&self) -> Vec<ContextRequirement>;
+    fn outputs(&self) -> Option<Vec<ElementType>>;
 }
 
 impl<T: Renderer> ErasedRenderer for T {
     fn render(&self, segment: &Segment, context: CompositionContext) -> Result<Vec<Segment>> {
         self.render(segment.try_into()?, context)
     }
+
+    fn requirements(&self) -> Vec<ContextRequirement> {
+        Renderer::requirements(self)
+    }
+
+    fn outputs(&self) -> Option<Vec<ElementType>> {
+        Renderer::outputs(self)
+    }
 }
 
 /// A mapping of [`Element`] to [`Renderer`]s used to delegate rendering of generic
 /// [`Segment`]s via their [`Element`]. Only one [`Renderer`] per type is
 /// allowed in the current implementation.
-#[allow(missing_debug_implementations)] // TODO
 #[derive(Default)]
 pub struct RenderEngine {
     renderers: HashMap<TypeId, Box<dyn ErasedRenderer>>,
+    element_types: HashMap<TypeId, ElementType>,
 }
 
 impl Debug for RenderEngine {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        // TODO
-        write!(f, "RenderEngine {{ /* TODO */ }}")
+        let renderers = self
+            .element_types()
+            .into_iter()
+            .map(|element| element.short_name())
+            .collect::<Vec<_>>();
+
+        f.debug_struct("RenderEngine")
+            .field("renderers", &renderers)
+            .finish()
     }
 }
 
@@ -193,6 +312,7 @@ impl RenderEngine {
     pub fn new() -> RenderEngine {
         RenderEngine {
             renderers: HashMap::new(),
+            element_types: HashMap::new(),
         }
     }
 
@@ -201,6 +321,32 @@ impl RenderEngine {
     pub fn add_renderer<R: Renderer + 'static>(&mut self, renderer: R) {
         self.renderers
             .insert(TypeId::of::<R::Element>(), Box::new(renderer));
+        self.element_types
+            .insert(TypeId::of::<R::Element>(), ElementType::of::<R::Element>());
+    }
+
+    /// The [`Element`] types this [`RenderEngine`] has [`Renderer`]s for, ordered by type name.
+    pub fn element_types(&self) -> Vec<ElementType> {
+        let mut element_types = self.element_types.values().copied().collect::<Vec<_>>();
+        element_types.sort_by_key(|element| element.type_name());
+
+        element_types
+    }
+
+    /// Creates a [`RenderEngine`] containing all [`Renderer`]s registered via the
+    /// [`renderer`](crate::derive::renderer) attribute macro, across all linked crates.
+    ///
+    /// Registration order is unspecified, so registering more than one [`Renderer`] for the same
+    /// [`Element`] leaves it unspecified which is used.
+    pub fn registered() -> RenderEngine {
+        inventory::iter::<RendererRegistration>.into_iter().fold(
+            RenderEngine::new(),
+            |mut engine, registration| {
+                (registration.register)(&mut engine);
+
+                engine
+            },
+        )
     }
 
     /// Returns the [`Renderer`] corresponding to the given [`&dyn Element`], if one exists.
@@ -224,6 +370,38 @@ impl RenderEngine {
         self.renderers.contains_key(&element.as_any().type_id())
     }
 
+    /// Returns the declared [`ContextRequirement`]s of the [`Renderer`]s for a given `&dyn`
+    /// [`Element`], including those of any types it wraps.
+    pub fn requirements(&self, element: &dyn Element) -> Vec<ContextRequirement> {
+        successors(Some(element), |&s| s.wrapped_element())
+            .filter_map(|s| self.renderer_for(s))
+            .flat_map(|renderer| renderer.requirements())
+            .collect()
+    }
+
+    /// Determines if rendering a given `&dyn` [`Element`] may eventually produce an [`Element`] of
+    /// type `output`, according to the declared [`Renderer::outputs`] of its [`Renderer`] (and
+    /// those of its outputs, and so on). This includes the [`Renderer`]s of any types it wraps.
+    pub(crate) fn may_produce(&self, element: &dyn Element, output: TypeId) -> bool {
+        let mut visited = successors(Some(element), |&s| s.wrapped_element())
+            .map(|s| s.as_any().type_id())
+            .collect::<HashSet<_>>();
+        let mut queue = visited.iter().copied().collect::<VecDeque<_>>();
+
+        while let Some(type_id) = queue.pop_front() {
+            let outputs = self.renderers.get(&type_id).and_then(|r| r.outputs());
+            for declared in outputs.unwrap_or_default() {
+                if declared.type_id() == output {
+                    return true;
+                } else if visited.insert(declared.type_id()) {
+                    queue.push_back(declared.type_id());
+                }
+            }
+        }
+
+        false
+    }
+
     /// Renders a [`Element`] over a given time range with supplied context, delegating to
     /// [`Renderer`]s mapped to its type and wrapped types if any. If no mapped [`Renderer`]
     /// for the type or wrapped types exists, [`None`] is returned.
@@ -258,6 +436,28 @@ impl RenderEngine {
     }
 }
 
+/// A [`Renderer`] registered for automatic collection via [`RenderEngine::registered`]. Typically
+/// submitted by the [`renderer`](crate::derive::renderer) attribute macro rather than directly.
+#[derive(Debug)]
+pub struct RendererRegistration {
+    register: fn(&mut RenderEngine),
+}
+
+impl RendererRegistration {
+    /// Creates a registration for the given [`Renderer`] type, constructed via [`Default`].
+    pub const fn of<R: Renderer + Default + 'static>() -> RendererRegistration {
+        RendererRegistration {
+            register: add_default_renderer::<R>,
+        }
+    }
+}
+
+fn add_default_renderer<R: Renderer + Default + 'static>(engine: &mut RenderEngine) {
+    engine.add_renderer(R::default());
+}
+
+inventory::collect!(RendererRegistration);
+
 impl<R, S> Add<R> for RenderEngine
 where
     R: Renderer<Element = S> + 'static,
@@ -277,6 +477,7 @@ impl Add<RenderEngine> for RenderEngine {
 
     fn add(mut self, rhs: RenderEngine) -> Self::Output {
         self.renderers.extend(rhs.renderers);
+        self.element_types.extend(rhs.element_types);
 
         self
     }
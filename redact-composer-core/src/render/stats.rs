@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Render statistics collected during composition, if enabled via
+/// [`ComposerOptions::collect_stats`](crate::ComposerOptions::collect_stats).
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RenderStats {
+    /// Total time spent composing.
+    pub duration: Duration,
+    /// Every render attempt, in the order they occurred.
+    pub attempts: Vec<RenderAttempt>,
+}
+
+/// A single call to a node's renderer(s) during composition.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RenderAttempt {
+    /// The tree index of the node being rendered.
+    pub node_idx: usize,
+    /// The type name of the node's element.
+    pub element_type: String,
+    /// When the attempt started, relative to the start of composition.
+    pub start: Duration,
+    /// How long the attempt took.
+    pub duration: Duration,
+    /// `true` if the render succeeded, `false` if it returned an error (and may be retried).
+    pub success: bool,
+    /// The number of children produced (always `0` for unsuccessful attempts).
+    pub children: usize,
+}
+
+/// Aggregated render statistics for a single element type. See [`RenderStats::by_element_type`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ElementRenderStats {
+    /// The number of distinct nodes of this type which were attempted.
+    pub nodes: usize,
+    /// The number of nodes of this type which rendered successfully.
+    pub rendered: usize,
+    /// The total number of render attempts.
+    pub attempts: usize,
+    /// The number of attempts beyond the first for each node (typically due to
+    /// [`MissingContext`](crate::error::RendererError::MissingContext)).
+    pub retries: usize,
+    /// The total number of children produced.
+    pub children: usize,
+    /// The total time spent across all attempts.
+    pub total_duration: Duration,
+    /// The longest single attempt.
+    pub max_duration: Duration,
+}
+
+impl RenderStats {
+    /// Aggregates the render attempts by element type, ordered by type name.
+    pub fn by_element_type(&self) -> BTreeMap<String, ElementRenderStats> {
+        let mut by_type: BTreeMap<String, (ElementRenderStats, BTreeMap<usize, bool>)> =
+            BTreeMap::new();
+
+        for attempt in &self.attempts {
+            let (stats, nodes) = by_type.entry(attempt.element_type.clone()).or_default();
+
+            stats.attempts += 1;
+            stats.children += attempt.children;
+            stats.total_duration += attempt.duration;
+            stats.max_duration = stats.max_duration.max(attempt.duration);
+            *nodes.entry(attempt.node_idx).or_default() |= attempt.success;
+        }
+
+        by_type
+            .into_iter()
+            .map(|(element_type, (mut stats, nodes))| {
+                stats.nodes = nodes.len();
+                stats.rendered = nodes.values().filter(|s| **s).count();
+                stats.retries = stats.attempts - stats.nodes;
+
+                (element_type, stats)
+            })
+            .collect()
+    }
+
+    /// The total number of render retries.
+    pub fn retries(&self) -> usize {
+        self.by_element_type().values().map(|s| s.retries).sum()
+    }
+
+    /// Exports the render attempts in
+    /// [Trace Event Format](https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU)
+    /// JSON, viewable in trace viewers such as `chrome://tracing` or Perfetto.
+    pub fn to_chrome_trace(&self) -> String {
+        let mut json = String::from("{\"traceEvents\":[");
+
+        for (i, attempt) in self.attempts.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+
+            write!(
+                json,
+                "{{\"name\":\"{}\",\"cat\":\"render\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":0,\"tid\":0,\
+                \"args\":{{\"node_idx\":{},\"success\":{},\"children\":{}}}}}",
+                escape_json(&attempt.element_type),
+                attempt.start.as_micros(),
+                attempt.duration.as_micros(),
+                attempt.node_idx,
+                attempt.success,
+                attempt.children
+            )
+            .unwrap();
+        }
+
+        json.push_str("],\"displayTimeUnit\":\"ms\"}");
+
+        json
+    }
+}
+
+fn escape_json(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| match c {
+            '"' => vec!['\\', '"'],
+            '\\' => vec!['\\', '\\'],
+            c if c.is_control() => format!("\\u{:04x}", c as u32).chars().collect(),
+            c => vec![c],
+        })
+        .collect()
+}
@@ -1,3 +1,5 @@
+use crate::error::TreeError;
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::ops::Index;
 
@@ -49,7 +51,9 @@ impl<T> Tree<T> {
         self.nodes.get(idx)
     }
 
-    /// Iterates a subtree starting from a given node. Order is not guaranteed.
+    /// Iterates a subtree starting from a given node. Order is not guaranteed -- use
+    /// [`dfs_pre`](Self::dfs_pre), [`dfs_post`](Self::dfs_post) or [`bfs`](Self::bfs) if a specific
+    /// order is required.
     pub fn node_iter<'a>(&'a self, start: &'a Node<T>) -> NodeIter<T> {
         NodeIter {
             tree: self,
@@ -82,6 +86,87 @@ impl<T> Tree<T> {
         }
     }
 
+    /// Iterates the tree's nodes in depth-first pre-order (each node before its children), starting
+    /// from the root. Children are visited in insertion order.
+    pub fn dfs_pre(&self) -> DepthFirstIter<'_, T> {
+        DepthFirstIter {
+            tree: self,
+            stack: self.root().map(|r| vec![r.idx]).unwrap_or_default(),
+        }
+    }
+
+    /// Iterates the tree's nodes in depth-first post-order (each node after its children), starting
+    /// from the root. Children are visited in insertion order.
+    pub fn dfs_post(&self) -> PostOrderIter<'_, T> {
+        PostOrderIter {
+            tree: self,
+            stack: self
+                .root()
+                .map(|r| vec![(r.idx, false)])
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Iterates the tree's nodes in breadth-first order (level by level), starting from the root.
+    /// Nodes of the same level are visited in insertion order of their parents, then themselves.
+    pub fn bfs(&self) -> BreadthFirstIter<'_, T> {
+        BreadthFirstIter {
+            tree: self,
+            queue: self
+                .root()
+                .map(|r| VecDeque::from([r.idx]))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Iterates the tree's leaf nodes (nodes without children), in depth-first order.
+    pub fn leaves(&self) -> LeafIter<'_, T> {
+        LeafIter(self.dfs_pre())
+    }
+
+    /// Iterates the ancestors of the node at `idx`, starting from its parent and ending with the
+    /// root.
+    pub fn ancestors_of(&self, idx: usize) -> AncestorIter<'_, T> {
+        AncestorIter {
+            tree: self,
+            next: self.nodes[idx].parent,
+        }
+    }
+
+    /// Iterates the descendants of the node at `idx` (excluding itself) in depth-first pre-order.
+    pub fn descendants_of(&self, idx: usize) -> DepthFirstIter<'_, T> {
+        DepthFirstIter {
+            tree: self,
+            stack: self.nodes[idx].children.iter().rev().copied().collect(),
+        }
+    }
+
+    /// Walks the tree depth-first from its root, calling the [`Visitor`]'s methods on each node.
+    pub fn walk<V: Visitor<T>>(&self, visitor: &mut V) {
+        if let Some(root) = self.root() {
+            self.walk_from(root.idx, visitor)
+        }
+    }
+
+    /// Walks the subtree of the node at `idx` depth-first, calling the [`Visitor`]'s methods on
+    /// each node. Depth is reported relative to this starting node.
+    pub fn walk_from<V: Visitor<T>>(&self, idx: usize, visitor: &mut V) {
+        let mut stack = vec![(idx, 0, false)];
+
+        while let Some((idx, depth, entered)) = stack.pop() {
+            let node = &self.nodes[idx];
+
+            if entered {
+                visitor.exit(node, depth);
+            } else if visitor.enter(node, depth) {
+                stack.push((idx, depth, true));
+                stack.extend(node.children.iter().rev().map(|c| (*c, depth + 1, false)));
+            } else {
+                visitor.exit(node, depth);
+            }
+        }
+    }
+
     /// Inserts a new value in this tree as a child of the `parent_idx` node.
     pub fn insert(&mut self, item: T, parent_idx: Option<usize>) -> usize {
         let new_idx = self.nodes.len();
@@ -100,6 +185,151 @@ impl<T> Tree<T> {
     }
 }
 
+impl<T> Tree<T> {
+    /// Replaces the value of the node at `idx`, returning the previous value.
+    pub fn replace_value(&mut self, idx: usize, value: T) -> Result<T, TreeError> {
+        self.check_idx(idx)?;
+
+        Ok(std::mem::replace(&mut self.nodes[idx].value, value))
+    }
+
+    /// Removes the node at `idx` along with all its descendants, returning them as a new [`Tree`]
+    /// (rooted at index `0`, with nodes in depth-first pre-order).
+    ///
+    /// Remaining node indices are compacted (preserving their relative order), so any indices held
+    /// from before the removal should be considered invalid.
+    /// ```
+    /// # use redact_composer_core::render::tree::Tree;
+    /// let mut tree = Tree::new();
+    /// let root = tree.insert("root", None);
+    /// let a = tree.insert("a", Some(root));
+    /// tree.insert("a.1", Some(a));
+    /// let b = tree.insert("b", Some(root));
+    ///
+    /// let removed = tree.remove_subtree(a).unwrap();
+    /// assert_eq!(removed.dfs_pre().map(|n| n.value).collect::<Vec<_>>(), ["a", "a.1"]);
+    /// assert_eq!(tree.dfs_pre().map(|n| n.value).collect::<Vec<_>>(), ["root", "b"]);
+    /// assert_eq!(tree[0].children, [1]);
+    /// ```
+    pub fn remove_subtree(&mut self, idx: usize) -> Result<Tree<T>, TreeError> {
+        self.check_idx(idx)?;
+
+        let removed_idxs = [idx]
+            .into_iter()
+            .chain(self.descendants_of(idx).map(|n| n.idx))
+            .collect::<Vec<_>>();
+
+        if let Some(parent_idx) = self.nodes[idx].parent {
+            self.nodes[parent_idx].children.retain(|c| *c != idx);
+        }
+
+        let mut slots = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+
+        // Removed nodes are re-indexed by their depth-first order
+        let mut removed_remap = vec![usize::MAX; slots.len()];
+        for (new_idx, old_idx) in removed_idxs.iter().enumerate() {
+            removed_remap[*old_idx] = new_idx;
+        }
+        let removed_nodes = removed_idxs
+            .iter()
+            .filter_map(|old_idx| slots[*old_idx].take())
+            .map(|node| Self::remapped(node, &removed_remap, |old_idx| old_idx == idx))
+            .collect();
+
+        // Remaining nodes retain their relative order, shifting down to fill any gaps
+        let mut remap = vec![usize::MAX; slots.len()];
+        for (new_idx, old_idx) in slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, n)| n.as_ref().map(|_| i))
+            .enumerate()
+        {
+            remap[old_idx] = new_idx;
+        }
+        self.nodes = slots
+            .into_iter()
+            .flatten()
+            .map(|node| Self::remapped(node, &remap, |_| false))
+            .collect();
+
+        Ok(Tree {
+            nodes: removed_nodes,
+        })
+    }
+
+    /// Moves the node at `idx` (along with its descendants) to become the last child of
+    /// `new_parent_idx`. Node indices are unaffected.
+    pub fn reparent(&mut self, idx: usize, new_parent_idx: usize) -> Result<(), TreeError> {
+        self.check_idx(idx)?;
+        self.check_idx(new_parent_idx)?;
+
+        let old_parent_idx = self.nodes[idx].parent.ok_or(TreeError::RootMove(idx))?;
+        if new_parent_idx == idx || self.ancestors_of(new_parent_idx).any(|n| n.idx == idx) {
+            return Err(TreeError::CyclicMove(idx, new_parent_idx));
+        }
+
+        self.nodes[old_parent_idx].children.retain(|c| *c != idx);
+        self.nodes[new_parent_idx].children.push(idx);
+        self.nodes[idx].parent = Some(new_parent_idx);
+
+        Ok(())
+    }
+
+    /// Inserts all nodes of `other` into this tree, attaching its root as the last child of the node
+    /// at `at`. Returns the new index of the grafted root.
+    ///
+    /// Grafted nodes are appended (in their existing order), so existing node indices are
+    /// unaffected. Combined with [`remove_subtree`](Self::remove_subtree) this can be used to move
+    /// or copy subtrees between trees.
+    pub fn graft(&mut self, other: Tree<T>, at: usize) -> Result<usize, TreeError> {
+        self.check_idx(at)?;
+        if other.is_empty() {
+            return Err(TreeError::EmptyGraft);
+        }
+
+        let offset = self.nodes.len();
+        let remap = (offset..offset + other.len()).collect::<Vec<_>>();
+        for node in other.nodes {
+            let was_root = node.parent.is_none();
+            let mut node = Self::remapped(node, &remap, |_| false);
+
+            if was_root {
+                node.parent = Some(at);
+                self.nodes[at].children.push(node.idx);
+            }
+
+            self.nodes.push(node);
+        }
+
+        Ok(offset)
+    }
+
+    fn check_idx(&self, idx: usize) -> Result<(), TreeError> {
+        if idx < self.nodes.len() {
+            Ok(())
+        } else {
+            Err(TreeError::InvalidIndex(idx))
+        }
+    }
+
+    // Updates a node's indices according to `remap` (indexed by old node index). Nodes for which
+    // `detach` returns `true` lose their parent.
+    fn remapped(mut node: Node<T>, remap: &[usize], detach: impl Fn(usize) -> bool) -> Node<T> {
+        node.parent = if detach(node.idx) {
+            None
+        } else {
+            node.parent.map(|p| remap[p])
+        };
+        node.idx = remap[node.idx];
+        node.children.iter_mut().for_each(|c| *c = remap[*c]);
+
+        node
+    }
+}
+
 impl<T> Default for Tree<T> {
     fn default() -> Self {
         Self::new()
@@ -154,6 +384,133 @@ impl<'a, T> Iterator for NodeIter<'a, T> {
     }
 }
 
+/// Depth-first pre-order node iterator. See [`Tree::dfs_pre`].
+#[derive(Debug)]
+pub struct DepthFirstIter<'a, T> {
+    tree: &'a Tree<T>,
+    stack: Vec<usize>,
+}
+
+impl<'a, T> Iterator for DepthFirstIter<'a, T> {
+    type Item = &'a Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = &self.tree.nodes[self.stack.pop()?];
+        self.stack.extend(node.children.iter().rev());
+
+        Some(node)
+    }
+}
+
+/// Depth-first post-order node iterator. See [`Tree::dfs_post`].
+#[derive(Debug)]
+pub struct PostOrderIter<'a, T> {
+    tree: &'a Tree<T>,
+    // (node idx, whether its children have already been pushed)
+    stack: Vec<(usize, bool)>,
+}
+
+impl<'a, T> Iterator for PostOrderIter<'a, T> {
+    type Item = &'a Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((idx, expanded)) = self.stack.pop() {
+            let node = &self.tree.nodes[idx];
+
+            if expanded || node.children.is_empty() {
+                return Some(node);
+            }
+
+            self.stack.push((idx, true));
+            self.stack
+                .extend(node.children.iter().rev().map(|c| (*c, false)));
+        }
+
+        None
+    }
+}
+
+/// Breadth-first node iterator. See [`Tree::bfs`].
+#[derive(Debug)]
+pub struct BreadthFirstIter<'a, T> {
+    tree: &'a Tree<T>,
+    queue: VecDeque<usize>,
+}
+
+impl<'a, T> Iterator for BreadthFirstIter<'a, T> {
+    type Item = &'a Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = &self.tree.nodes[self.queue.pop_front()?];
+        self.queue.extend(node.children.iter());
+
+        Some(node)
+    }
+}
+
+/// Leaf node iterator. See [`Tree::leaves`].
+#[derive(Debug)]
+pub struct LeafIter<'a, T>(DepthFirstIter<'a, T>);
+
+impl<'a, T> Iterator for LeafIter<'a, T> {
+    type Item = &'a Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find(|n| n.children.is_empty())
+    }
+}
+
+/// Ancestor node iterator. See [`Tree::ancestors_of`].
+#[derive(Debug)]
+pub struct AncestorIter<'a, T> {
+    tree: &'a Tree<T>,
+    next: Option<usize>,
+}
+
+impl<'a, T> Iterator for AncestorIter<'a, T> {
+    type Item = &'a Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = &self.tree.nodes[self.next?];
+        self.next = node.parent;
+
+        Some(node)
+    }
+}
+
+/// Visits the nodes of a [`Tree`] during a depth-first walk (via [`Tree::walk`] or
+/// [`Tree::walk_from`]).
+///
+/// ```
+/// # use redact_composer_core::render::tree::{Node, Tree, Visitor};
+/// struct Outline(Vec<String>);
+///
+/// impl Visitor<&str> for Outline {
+///     fn enter(&mut self, node: &Node<&str>, depth: usize) -> bool {
+///         self.0.push(format!("{}{}", "  ".repeat(depth), node.value));
+///         true
+///     }
+/// }
+///
+/// let mut tree = Tree::new();
+/// let root = tree.insert("root", None);
+/// let child = tree.insert("child", Some(root));
+/// tree.insert("grandchild", Some(child));
+/// tree.insert("other child", Some(root));
+///
+/// let mut outline = Outline(vec![]);
+/// tree.walk(&mut outline);
+/// assert_eq!(outline.0, ["root", "  child", "    grandchild", "  other child"]);
+/// ```
+pub trait Visitor<T> {
+    /// Called when a node is reached, before any of its children. Returning `false` skips visiting
+    /// this node's children.
+    fn enter(&mut self, node: &Node<T>, depth: usize) -> bool;
+
+    /// Called when leaving a node, after all of its (visited) children.
+    fn exit(&mut self, _node: &Node<T>, _depth: usize) {}
+}
+
 impl<Idx: std::slice::SliceIndex<[Node<T>]>, T> Index<Idx> for Tree<T> {
     type Output = Idx::Output;
 
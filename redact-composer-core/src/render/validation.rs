@@ -0,0 +1,278 @@
+use std::any::{type_name, TypeId};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+
+use crate::render::context::ContextRequirement;
+use crate::render::RenderEngine;
+use crate::Element;
+
+/// Identifies an [`Element`] type, such as those declared by
+/// [`Renderer::outputs`](crate::render::Renderer::outputs).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ElementType {
+    type_id: TypeId,
+    type_name: &'static str,
+}
+
+impl ElementType {
+    /// The [`ElementType`] of `Element`.
+    pub fn of<Element: crate::Element>() -> ElementType {
+        ElementType {
+            type_id: TypeId::of::<Element>(),
+            type_name: type_name::<Element>(),
+        }
+    }
+
+    /// The [`TypeId`] of this [`Element`] type.
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// The type name of this [`Element`] type.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    // Type name without module paths (including those of generic parameters).
+    pub(super) fn short_name(&self) -> String {
+        let mut short_name = String::new();
+        let mut segment = String::new();
+
+        for c in self.type_name.chars() {
+            if c.is_alphanumeric() || c == '_' || c == ':' {
+                segment.push(c);
+            } else {
+                short_name.push_str(segment.rsplit("::").next().unwrap_or_default());
+                short_name.push(c);
+                segment.clear();
+            }
+        }
+        short_name.push_str(segment.rsplit("::").next().unwrap_or_default());
+
+        short_name
+    }
+}
+
+impl From<&dyn Element> for ElementType {
+    fn from(element: &dyn Element) -> Self {
+        ElementType {
+            type_id: element.as_any().type_id(),
+            type_name: element.type_name(),
+        }
+    }
+}
+
+impl Display for ElementType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.short_name())
+    }
+}
+
+/// Result of [`RenderEngine::validate`], describing how a [`RenderEngine`] would render a
+/// composition from a given root [`Element`], based on the outputs and requirements declared by its
+/// [`Renderer`](crate::render::Renderer)s.
+///
+/// Since declaring outputs/requirements is optional, this is only as complete as the declarations
+/// are. Reachable renderers without declared outputs (i.e. [`Renderer::outputs`] is [`None`]) are
+/// listed in [`undeclared_outputs`](Self::undeclared_outputs), as anything they produce is unknown.
+///
+/// [`Renderer::outputs`]: crate::render::Renderer::outputs
+#[derive(Debug, Clone)]
+pub struct ValidationReport {
+    /// The root [`Element`] type validated against.
+    pub root: ElementType,
+    /// Each [`Element`] type reachable from the root (including itself) via declared outputs, in
+    /// breadth-first order.
+    pub reachable: Vec<ReachableElement>,
+    /// Declared (non-soft) requirements of reachable renderers, for which no reachable renderer
+    /// declares a matching output.
+    pub unproduced_requirements: Vec<UnproducedRequirement>,
+    /// Element types with a renderer which are never reachable from the root. Since a renderer
+    /// without declared outputs could produce anything, this is only determined if every reachable
+    /// renderer declares its outputs (i.e. [`undeclared_outputs`](Self::undeclared_outputs) is
+    /// empty), and is otherwise left empty.
+    pub unreachable_renderers: Vec<ElementType>,
+    /// Element types with a reachable renderer which does not declare its outputs. (Renderers
+    /// declaring they produce nothing, such as via
+    /// [`AdhocRenderer::producing_nothing`](crate::render::AdhocRenderer::producing_nothing), are
+    /// not included.)
+    pub undeclared_outputs: Vec<ElementType>,
+}
+
+/// An [`Element`] type reachable from a [`ValidationReport`]'s root.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ReachableElement {
+    /// The reachable [`Element`] type.
+    pub element: ElementType,
+    /// Whether the [`RenderEngine`] has a renderer for this type.
+    pub has_renderer: bool,
+}
+
+/// A declared [`ContextRequirement`] which no reachable renderer declares as an output.
+#[derive(Debug, Copy, Clone)]
+pub struct UnproducedRequirement {
+    /// The [`Element`] type whose renderer declares the requirement.
+    pub renderer: ElementType,
+    /// The unproduced requirement.
+    pub requirement: ContextRequirement,
+}
+
+impl ValidationReport {
+    /// Returns `true` if every declared (non-soft) requirement of reachable renderers has a
+    /// producer.
+    pub fn is_valid(&self) -> bool {
+        self.unproduced_requirements.is_empty()
+    }
+
+    /// Element types reachable from the root which have no renderer. (Typically leaf types, such
+    /// as [`PlayNote`](crate::elements::PlayNote)).
+    pub fn unrendered(&self) -> impl Iterator<Item = ElementType> + '_ {
+        self.reachable
+            .iter()
+            .filter(|r| !r.has_renderer)
+            .map(|r| r.element)
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Validation of {}:", self.root)?;
+        for reachable in &self.reachable {
+            let status = if reachable.has_renderer {
+                "rendered"
+            } else {
+                "no renderer"
+            };
+            writeln!(f, "  {} ({})", reachable.element, status)?;
+        }
+        for unproduced in &self.unproduced_requirements {
+            writeln!(
+                f,
+                "  Unproduced requirement: {} requires {} ({:?})",
+                unproduced.renderer,
+                ElementType {
+                    type_id: unproduced.requirement.type_id(),
+                    type_name: unproduced.requirement.type_name(),
+                },
+                unproduced.requirement.relation()
+            )?;
+        }
+        for unreachable in &self.unreachable_renderers {
+            writeln!(f, "  Unreachable renderer: {}", unreachable)?;
+        }
+        for undeclared in &self.undeclared_outputs {
+            writeln!(f, "  Undeclared outputs: {}", undeclared)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RenderEngine {
+    /// Validates this [`RenderEngine`] for compositions starting from a `Root` element, based on
+    /// the outputs and requirements declared by its [`Renderer`](crate::render::Renderer)s. See
+    /// [`ValidationReport`].
+    /// ```
+    /// # use redact_composer_core::derive::Element;
+    /// # use redact_composer_core::elements::PlayNote;
+    /// # use redact_composer_core::render::{AdhocRenderer, RenderEngine};
+    /// # use redact_composer_core::render::context::{ContextRequirement, TimingRelation::During};
+    /// # use redact_composer_core::IntoSegment;
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Song;
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Melody;
+    /// # #[derive(Element, Debug, serde::Serialize, serde::Deserialize)]
+    /// # struct Key;
+    /// let engine = RenderEngine::new()
+    ///     + AdhocRenderer::<Song>::new(|song, _| Ok(vec![Melody.over(song)]))
+    ///         .producing::<Melody>()
+    ///     + AdhocRenderer::<Melody>::new(|_, _| Ok(vec![]))
+    ///         .producing::<PlayNote>()
+    ///         .requiring(ContextRequirement::new::<Key>(During));
+    ///
+    /// let report = engine.validate::<Song>();
+    /// assert!(!report.is_valid());
+    /// assert_eq!(report.unproduced_requirements[0].requirement.type_name(), std::any::type_name::<Key>());
+    /// ```
+    pub fn validate<Root: Element>(&self) -> ValidationReport {
+        self.validate_root(ElementType::of::<Root>(), [])
+    }
+
+    // Validates from a root element type, and any types it wraps (such as a `Part`'s element),
+    // which are rendered the same as the root itself.
+    pub(crate) fn validate_root(
+        &self,
+        root: ElementType,
+        wrapped: impl IntoIterator<Item = ElementType>,
+    ) -> ValidationReport {
+        let renderers = self.element_types();
+        let mut reachable = vec![];
+        let mut queue = VecDeque::from_iter([root].into_iter().chain(wrapped));
+        let mut visited = queue.iter().map(|e| e.type_id).collect::<HashSet<_>>();
+        let roots = visited.clone();
+        let mut outputs = HashMap::new();
+
+        while let Some(element) = queue.pop_front() {
+            let renderer = self.renderers.get(&element.type_id);
+            reachable.push(ReachableElement {
+                element,
+                has_renderer: renderer.is_some(),
+            });
+
+            for output in renderer.and_then(|r| r.outputs()).unwrap_or_default() {
+                outputs.insert(output.type_id, output);
+                if visited.insert(output.type_id) {
+                    queue.push_back(output);
+                }
+            }
+        }
+
+        let reachable_renderers = reachable
+            .iter()
+            .filter(|r| r.has_renderer)
+            .map(|r| r.element)
+            .collect::<Vec<_>>();
+
+        let unproduced_requirements = reachable_renderers
+            .iter()
+            .flat_map(|element| {
+                self.renderers[&element.type_id]
+                    .requirements()
+                    .into_iter()
+                    .filter(|req| {
+                        !req.is_soft()
+                            && !roots.contains(&req.type_id())
+                            && !outputs.contains_key(&req.type_id())
+                    })
+                    .map(|requirement| UnproducedRequirement {
+                        renderer: *element,
+                        requirement,
+                    })
+            })
+            .collect();
+
+        let undeclared_outputs = reachable_renderers
+            .iter()
+            .filter(|element| self.renderers[&element.type_id].outputs().is_none())
+            .copied()
+            .collect::<Vec<_>>();
+
+        let unreachable_renderers = if undeclared_outputs.is_empty() {
+            renderers
+                .into_iter()
+                .filter(|element| !visited.contains(&element.type_id))
+                .collect()
+        } else {
+            vec![]
+        };
+
+        ValidationReport {
+            root,
+            reachable,
+            unproduced_requirements,
+            unreachable_renderers,
+            undeclared_outputs,
+        }
+    }
+}
@@ -2,8 +2,11 @@ use serde::{Deserialize, Serialize};
 
 use crate::derive::Element;
 use crate::error::RendererError::MissingContext;
+use crate::error::TreeError;
 use crate::render::context::TimingRelation::Overlapping;
+use crate::render::tree::{Node, Tree, Visitor};
 use crate::render::{AdhocRenderer, RenderEngine};
+use crate::timing::{Tempo, TempoMap, TempoRamp, Timing, TimingSet};
 use crate::IntoSegment;
 use crate::{Composer, Composition, Segment};
 
@@ -181,3 +184,1330 @@ fn depth_first_render_order() {
     assert!(comp.tree[8].value.segment.element_as::<RONode4>().is_some());
     assert!(comp.tree[9].value.segment.element_as::<RONode7>().is_some());
 }
+
+#[test]
+fn context_ancestry_and_siblings() {
+    use crate::SegmentRef;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct AnRoot;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct AnSection(u8);
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct AnLeaf(u8);
+
+    let engine = RenderEngine::new()
+        + AdhocRenderer::<AnRoot>::new(|seg, ctx| {
+            assert!(ctx.parent::<AnRoot>().is_none());
+            assert!(ctx.ancestors::<AnRoot>().is_empty());
+            assert!(ctx.siblings::<AnRoot>().is_empty());
+            assert_eq!(ctx.node_path().len(), 1);
+            assert!(ctx.node_path()[0].element_as::<AnRoot>().is_some());
+            assert_eq!(ctx.depth(), 0);
+
+            Ok(vec![AnSection(0).over(seg), AnSection(1).over(seg)])
+        })
+        + AdhocRenderer::<AnSection>::new(|seg, ctx| {
+            assert!(ctx.parent::<AnRoot>().is_some());
+            assert!(ctx.parent::<AnSection>().is_none());
+            assert_eq!(ctx.siblings::<AnSection>().len(), 1);
+            assert_ne!(ctx.siblings::<AnSection>()[0].element.0, seg.element.0);
+
+            Ok(vec![AnLeaf(seg.element.0).over(seg)])
+        })
+        + AdhocRenderer::<AnLeaf>::new(|seg, ctx| {
+            assert_eq!(
+                ctx.parent::<AnSection>().map(|p| p.element.0),
+                Some(seg.element.0)
+            );
+            assert_eq!(ctx.ancestors::<AnSection>().len(), 1);
+            assert_eq!(ctx.ancestors::<AnRoot>().len(), 1);
+            assert!(ctx.siblings::<AnLeaf>().is_empty());
+            assert_eq!(ctx.depth(), 2);
+            let path = ctx.node_path();
+            assert_eq!(path.len(), 3);
+            assert!(path[0].element_as::<AnRoot>().is_some());
+            assert_eq!(
+                SegmentRef::<AnSection>::try_from(path[1])
+                    .ok()
+                    .map(|s| s.element.0),
+                Some(seg.element.0)
+            );
+            assert_eq!(
+                path[2].element_as::<AnLeaf>().map(|l| l.0),
+                Some(seg.element.0)
+            );
+
+            Ok(vec![])
+        });
+
+    let comp = Composer::from(engine).compose_with_seed(Segment::new(AnRoot, 0..10), 0);
+
+    assert!(comp.tree.iter().all(|n| n.value.rendered));
+}
+
+#[test]
+fn soft_context_dependencies() {
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct SoftRoot;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct SoftWaiter;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct SoftFallback;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct SoftProducer;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct SoftProduced;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct SoftNeverProduced;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct SoftResult(bool);
+
+    let engine = RenderEngine::new()
+        + AdhocRenderer::<SoftRoot>::new(|seg, _| {
+            Ok(vec![
+                SoftWaiter.over(seg),
+                SoftFallback.over(seg),
+                SoftProducer.over(seg),
+            ])
+        })
+        + AdhocRenderer::<SoftWaiter>::new(|seg, ctx| {
+            let found = ctx.find::<SoftProduced>().wait_for()?;
+            Ok(vec![SoftResult(found.is_some()).over(seg)])
+        })
+        + AdhocRenderer::<SoftFallback>::new(|seg, ctx| {
+            let found = ctx.find::<SoftNeverProduced>().wait_for_all()?;
+            assert!(ctx.is_stalled());
+            Ok(vec![SoftResult(found.is_some()).over(seg)])
+        })
+        + AdhocRenderer::<SoftProducer>::new(|seg, _| Ok(vec![SoftProduced.over(seg)]));
+
+    let comp = Composer::from(engine).compose_with_seed(Segment::new(SoftRoot, 0..10), 0);
+
+    assert!(comp.tree.iter().all(|n| n.value.rendered));
+
+    let result_of = |idx: usize| {
+        comp.tree[comp.tree[idx].children[0]]
+            .value
+            .segment
+            .element_as::<SoftResult>()
+            .map(|r| r.0)
+    };
+    // The waiter eventually finds its context once produced
+    assert_eq!(result_of(1), Some(true));
+    // The other falls back, once no further progress can be made
+    assert_eq!(result_of(2), Some(false));
+}
+
+#[test]
+fn composition_queries() {
+    use crate::elements::{Part, PlayNote};
+    use crate::render::context::TimingRelation::Within;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct QSong;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct QMelody;
+
+    let engine = RenderEngine::new()
+        + AdhocRenderer::<QSong>::new(|seg, _| {
+            Ok(vec![
+                Part::instrument(QMelody).over(seg),
+                PlayNote {
+                    note: 40,
+                    velocity: 1,
+                }
+                .over(5..10),
+            ])
+        })
+        + AdhocRenderer::<QMelody>::new(|_, _| {
+            Ok(vec![
+                PlayNote {
+                    note: 62,
+                    velocity: 1,
+                }
+                .over(5..10),
+                PlayNote {
+                    note: 60,
+                    velocity: 1,
+                }
+                .over(0..5),
+            ])
+        });
+
+    let comp = Composer::from(engine).compose_with_seed(Segment::new(QSong, 0..10), 0);
+
+    assert_eq!(comp.find::<PlayNote>().get_all().map(|n| n.len()), Some(3));
+    assert_eq!(
+        comp.find::<PlayNote>()
+            .with_timing(Within, 0..5)
+            .get_all()
+            .map(|n| n.len()),
+        Some(1)
+    );
+    assert_eq!(
+        comp.find::<PlayNote>()
+            .within::<Part>()
+            .matching(|n| n.note > 60)
+            .get_all()
+            .map(|n| n.iter().map(|n| n.element.note).collect::<Vec<_>>()),
+        Some(vec![62])
+    );
+    assert!(comp.find::<QSong>().wait_for().unwrap().is_some());
+
+    let notes = comp
+        .notes()
+        .into_iter()
+        .map(|(timing, note, part_idx)| (timing, note.note, part_idx))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        vec![
+            (Timing::from(0..5), 60, Some(1)),
+            (Timing::from(5..10), 62, Some(1)),
+            (Timing::from(5..10), 40, None),
+        ]
+    );
+}
+
+#[test]
+fn render_stats() {
+    use crate::ComposerOptions;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct StatsRoot;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct StatsWaiter;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct StatsProducer;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct StatsProduced;
+
+    let engine = || {
+        RenderEngine::new()
+            + AdhocRenderer::<StatsRoot>::new(|seg, _| {
+                Ok(vec![StatsWaiter.over(seg), StatsProducer.over(seg)])
+            })
+            + AdhocRenderer::<StatsWaiter>::new(|seg, ctx| {
+                ctx.find::<StatsProduced>().require()?;
+                Ok(vec![StatsProduced.over(seg), StatsProduced.over(seg)])
+            })
+            + AdhocRenderer::<StatsProducer>::new(|seg, _| Ok(vec![StatsProduced.over(seg)]))
+    };
+
+    let without_stats =
+        Composer::from(engine()).compose_with_seed(Segment::new(StatsRoot, 0..10), 0);
+    assert!(without_stats.stats.is_none());
+
+    let composer = Composer {
+        options: ComposerOptions::new().collect_stats(true),
+        ..Composer::from(engine())
+    };
+    let stats = composer
+        .compose_with_seed(Segment::new(StatsRoot, 0..10), 0)
+        .stats
+        .unwrap();
+
+    let by_type = stats.by_element_type();
+    let waiter = &by_type[std::any::type_name::<StatsWaiter>()];
+    assert_eq!((waiter.nodes, waiter.rendered), (1, 1));
+    assert_eq!((waiter.attempts, waiter.retries), (2, 1));
+    assert_eq!(waiter.children, 2);
+
+    let producer = &by_type[std::any::type_name::<StatsProducer>()];
+    assert_eq!(
+        (producer.nodes, producer.attempts, producer.children),
+        (1, 1, 1)
+    );
+    assert_eq!(stats.retries(), 1);
+
+    let trace = stats.to_chrome_trace();
+    assert!(trace.starts_with("{\"traceEvents\":[{\"name\":\""));
+    assert_eq!(trace.matches("\"ph\":\"X\"").count(), stats.attempts.len());
+}
+
+#[test]
+fn observers_and_cancellation() {
+    use crate::error::RendererError;
+    use crate::observer::{CancellationToken, CompositionObserver, CompositionProgress};
+    use crate::render::RenderSegment;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ObsRoot;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ObsWaiter;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ObsProducer;
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ObsProduced;
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+        last_progress: Mutex<CompositionProgress>,
+        cancel_after: Option<(usize, CancellationToken)>,
+    }
+
+    impl CompositionObserver for Recorder {
+        fn on_node_rendered(&self, node: &Node<RenderSegment>, progress: CompositionProgress) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("rendered {}", node.idx));
+            *self.last_progress.lock().unwrap() = progress;
+
+            if let Some((count, token)) = &self.cancel_after {
+                if progress.rendered >= *count {
+                    token.cancel();
+                }
+            }
+        }
+
+        fn on_render_error(&self, node: &Node<RenderSegment>, _error: &RendererError) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("error {}", node.idx));
+        }
+
+        fn on_pass_complete(&self, progress: CompositionProgress) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("pass {}", progress.passes));
+        }
+
+        fn on_cancelled(&self, _progress: CompositionProgress) {
+            self.events.lock().unwrap().push("cancelled".to_string());
+        }
+    }
+
+    let engine = || {
+        RenderEngine::new()
+            + AdhocRenderer::<ObsRoot>::new(|seg, _| {
+                Ok(vec![ObsWaiter.over(seg), ObsProducer.over(seg)])
+            })
+            + AdhocRenderer::<ObsWaiter>::new(|seg, ctx| {
+                ctx.find::<ObsProduced>().require()?;
+                Ok(vec![ObsProduced.over(seg)])
+            })
+            + AdhocRenderer::<ObsProducer>::new(|seg, _| Ok(vec![ObsProduced.over(seg)]))
+    };
+
+    let recorder = Arc::new(Recorder::default());
+    let comp = Composer::from(engine())
+        .with_observer(recorder.clone())
+        .compose_with_seed(Segment::new(ObsRoot, 0..10), 0);
+
+    assert!(comp.tree.iter().all(|n| n.value.rendered));
+    assert_eq!(
+        *recorder.events.lock().unwrap(),
+        vec![
+            "rendered 0",
+            "pass 1",
+            "error 1",
+            "rendered 2",
+            "pass 2",
+            "rendered 1",
+            "pass 3",
+            "pass 4"
+        ]
+    );
+    let progress = *recorder.last_progress.lock().unwrap();
+    assert_eq!((progress.rendered, progress.renderable), (3, 3));
+    assert_eq!(progress.fraction(), 1.0);
+
+    let token = CancellationToken::new();
+    let recorder = Arc::new(Recorder {
+        cancel_after: Some((1, token.clone())),
+        ..Default::default()
+    });
+    let comp = Composer::from(engine())
+        .with_observer(recorder.clone())
+        .with_cancellation(token)
+        .compose_with_seed(Segment::new(ObsRoot, 0..10), 0);
+
+    assert_eq!(comp.tree.len(), 3);
+    assert!(!comp.tree[1].value.rendered);
+    assert_eq!(
+        *recorder.events.lock().unwrap(),
+        vec!["rendered 0", "pass 1", "cancelled"]
+    );
+    assert_eq!(recorder.last_progress.lock().unwrap().fraction(), 1.0 / 3.0);
+
+    // Observers can be shared with the thread composing
+    let recorder = Arc::new(Recorder::default());
+    let observer: Arc<dyn CompositionObserver> = recorder.clone();
+    std::thread::spawn(move || {
+        Composer::from(engine())
+            .with_observer(observer)
+            .compose_with_seed(Segment::new(ObsRoot, 0..10), 0);
+    })
+    .join()
+    .unwrap();
+    assert_eq!(recorder.last_progress.lock().unwrap().fraction(), 1.0);
+}
+
+#[test]
+fn rescale() {
+    use crate::elements::PlayNote;
+    use crate::timing::Rounding;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct RescaleRoot;
+
+    let composition = || {
+        let engine = RenderEngine::new()
+            + AdhocRenderer::<RescaleRoot>::new(|_, _| {
+                Ok(vec![
+                    PlayNote {
+                        note: 60,
+                        velocity: 1,
+                    }
+                    .over(0..240),
+                    PlayNote {
+                        note: 62,
+                        velocity: 1,
+                    }
+                    .over(240..250),
+                ])
+            });
+
+        Composer::from(engine).compose_with_seed(Segment::new(RescaleRoot, 0..960), 0)
+    };
+    let timings = |c: &Composition| {
+        c.tree
+            .iter()
+            .map(|n| n.value.segment.timing)
+            .collect::<Vec<_>>()
+    };
+
+    let mut upscaled = composition();
+    let report = upscaled.rescale(960, Rounding::Nearest);
+    assert_eq!(
+        timings(&upscaled),
+        [
+            Timing::from(0..1920),
+            Timing::from(0..480),
+            Timing::from(480..500)
+        ]
+    );
+    assert_eq!(
+        (report.from_ticks_per_beat, report.to_ticks_per_beat),
+        (480, 960)
+    );
+    assert!(report.collapsed.is_empty());
+
+    let mut downscaled = composition();
+    let report = downscaled.rescale(4, Rounding::Nearest);
+    assert_eq!(downscaled.options.ticks_per_beat, 4);
+    assert_eq!(timings(&downscaled)[2], Timing::from(2..2));
+    assert_eq!(report.collapsed, vec![2]);
+
+    let mut downscaled = composition();
+    assert!(downscaled.rescale(4, Rounding::Ceil).collapsed.is_empty());
+    assert_eq!(timings(&downscaled)[2], Timing::from(2..3));
+}
+
+#[test]
+fn rescale_element_ticks() {
+    use crate::timing::Rounding;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    #[element(ticks(beat, swing))]
+    struct Pulse {
+        beat: i32,
+        swing: i32,
+        count: i32,
+    }
+
+    let mut composition = Composer::from(RenderEngine::new()).compose_with_seed(
+        Segment::new(
+            Pulse {
+                beat: 480,
+                swing: 40,
+                count: 4,
+            },
+            0..960,
+        ),
+        0,
+    );
+    composition.rescale(96, Rounding::Nearest);
+
+    let pulse = composition.tree[0]
+        .value
+        .segment
+        .element_as::<Pulse>()
+        .unwrap();
+    assert_eq!((pulse.beat, pulse.swing, pulse.count), (96, 8, 4));
+}
+
+#[test]
+#[should_panic]
+fn rescale_non_positive() {
+    use crate::timing::Rounding;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct RescaleRoot;
+
+    Composer::from(RenderEngine::new())
+        .compose_with_seed(Segment::new(RescaleRoot, 0..960), 0)
+        .rescale(0, Rounding::Nearest);
+}
+
+#[test]
+fn derive_options() {
+    use crate::elements::PlayNote;
+    use crate::Element;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    #[element(instance = "Layer<PlayNote>", instance = "Layer<Marker>")]
+    struct Layer<T: Element> {
+        inner: T,
+    }
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    #[element(wrapped_element = inner)]
+    struct WrapsConcrete {
+        inner: Marker,
+    }
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    #[element(wrapped_element = 0)]
+    struct WrapsOptionalBox(Option<Box<dyn Element>>);
+
+    #[derive(Element, Serialize, Deserialize, Debug, PartialEq)]
+    enum Marker {
+        Start,
+        Named(String),
+    }
+
+    let layer = Segment::new(
+        Layer {
+            inner: Marker::Named("a".to_string()),
+        },
+        0..1,
+    );
+    let serialized = serde_json::to_string(&layer).unwrap();
+    assert_eq!(
+        serialized,
+        "{\"element\":{\"Layer<Marker>\":{\"inner\":{\"Named\":\"a\"}}},\"start\":0,\"end\":1}"
+    );
+    let deserialized: Segment = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(
+        deserialized.element_as::<Layer<Marker>>().map(|l| &l.inner),
+        Some(&Marker::Named("a".to_string()))
+    );
+    assert!(Layer {
+        inner: PlayNote {
+            note: 0,
+            velocity: 0
+        }
+    }
+    .wrapped_element()
+    .is_none());
+
+    let wraps_concrete = WrapsConcrete {
+        inner: Marker::Start,
+    };
+    assert!(wraps_concrete
+        .wrapped_element()
+        .is_some_and(|e| e.as_any().is::<Marker>()));
+
+    assert!(WrapsOptionalBox(None).wrapped_element().is_none());
+    assert!(WrapsOptionalBox(Some(Box::new(Marker::Start)))
+        .wrapped_element()
+        .is_some_and(|e| e.as_any().is::<Marker>()));
+}
+
+#[test]
+fn renderer_attribute() {
+    use crate::derive::renderer;
+    use crate::render::context::CompositionContext;
+    use crate::render::Result;
+    use crate::SegmentRef;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct AttrRoot;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct AttrLeaf(i32);
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct AttrUnregistered;
+
+    #[renderer]
+    fn attr_root(root: SegmentRef<AttrRoot>, _ctx: CompositionContext) -> Result<Vec<Segment>> {
+        Ok(vec![
+            AttrLeaf(root.timing.len()).over(root.timing),
+            AttrUnregistered.over(root.timing),
+        ])
+    }
+
+    #[renderer(name = "UnregisteredRenderer", register = false)]
+    fn attr_unregistered(
+        _segment: SegmentRef<'_, AttrUnregistered>,
+        _ctx: CompositionContext,
+    ) -> Result<Vec<Segment>> {
+        Ok(vec![])
+    }
+
+    let registered = RenderEngine::registered();
+    assert!(registered.can_render(&AttrRoot));
+    assert!(!registered.can_render(&AttrUnregistered));
+    assert!((RenderEngine::new() + UnregisteredRenderer).can_render(&AttrUnregistered));
+
+    let comp = Composer::from(RenderEngine::registered() + AttrRootRenderer)
+        .compose_with_seed(Segment::new(AttrRoot, 0..10), 0);
+    let leaves = comp
+        .tree
+        .iter()
+        .filter_map(|n| n.value.segment.element_as::<AttrLeaf>())
+        .map(|l| l.0)
+        .collect::<Vec<_>>();
+
+    assert_eq!(leaves, [10]);
+}
+
+#[test]
+fn declared_requirements() {
+    use crate::derive::renderer;
+    use crate::render::context::{CompositionContext, ContextRequirement, TimingRelation::During};
+    use crate::render::Result;
+    use crate::{ComposerOptions, SegmentRef};
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ReqRoot;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ReqConsumer;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ReqProducer;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ReqProduced;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ReqFiller;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ReqNeverProduced;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ReqOptional;
+
+    #[renderer(register = false, requires(ReqProduced = During))]
+    fn req_consumer(
+        consumer: SegmentRef<ReqConsumer>,
+        ctx: CompositionContext,
+    ) -> Result<Vec<Segment>> {
+        ctx.find::<ReqProduced>().require()?;
+
+        Ok(vec![ReqOptional.over(consumer)])
+    }
+
+    #[renderer(register = false, waits_for(ReqNeverProduced = During))]
+    fn req_optional(
+        _optional: SegmentRef<ReqOptional>,
+        _ctx: CompositionContext,
+    ) -> Result<Vec<Segment>> {
+        Ok(vec![])
+    }
+
+    let engine = RenderEngine::new()
+        + AdhocRenderer::<ReqRoot>::new(|root, _| {
+            // The consumer is produced first, but should not be attempted until after the producer,
+            // which should be rendered before the filler
+            Ok(vec![
+                ReqConsumer.over(root),
+                ReqFiller.over(root),
+                ReqProducer.over(root),
+            ])
+        })
+        + AdhocRenderer::<ReqProducer>::new(|producer, _| Ok(vec![ReqProduced.over(producer)]))
+            .requiring(ContextRequirement::new::<ReqRoot>(During))
+            .producing::<ReqProduced>()
+        + AdhocRenderer::<ReqFiller>::new(|_, _| Ok(vec![]))
+        + ReqConsumerRenderer
+        + ReqOptionalRenderer;
+
+    assert_eq!(engine.requirements(&ReqConsumer).len(), 1);
+    assert!(engine.requirements(&ReqOptional)[0].is_soft());
+    assert!(engine.requirements(&ReqRoot).is_empty());
+
+    let composer = Composer {
+        options: ComposerOptions::new().collect_stats(true),
+        ..Composer::from(engine)
+    };
+    let comp = composer.compose_with_seed(Segment::new(ReqRoot, 0..10), 0);
+    let stats = comp.stats.as_ref().unwrap();
+
+    assert!(comp.tree.iter().all(|n| n.value.rendered));
+    // Every renderer is invoked only once its declared requirements are met, with the producer
+    // of the consumer's requirement rendered first
+    assert_eq!(
+        stats
+            .attempts
+            .iter()
+            .map(|a| a.element_type.rsplit("::").next().unwrap())
+            .collect::<Vec<_>>(),
+        [
+            "ReqRoot",
+            "ReqProducer",
+            "ReqConsumer",
+            "ReqFiller",
+            "ReqOptional"
+        ]
+    );
+    assert!(stats.attempts.iter().all(|a| a.success));
+    assert_eq!(stats.retries(), 0);
+}
+
+#[test]
+fn validation() {
+    use crate::derive::renderer;
+    use crate::elements::{Part, PlayNote};
+    use crate::render::context::{CompositionContext, ContextRequirement, TimingRelation::During};
+    use crate::render::validation::ElementType;
+    use crate::render::Result;
+    use crate::SegmentRef;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ValPartMelody;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ValRoot;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ValSection;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ValKey;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ValChords;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct ValOrphan;
+
+    #[renderer(register = false, outputs(ValSection, ValKey))]
+    fn val_root(root: SegmentRef<ValRoot>, _ctx: CompositionContext) -> Result<Vec<Segment>> {
+        Ok(vec![ValSection.over(root), ValKey.over(root)])
+    }
+
+    #[renderer(register = false, requires(ValKey = During), outputs(ValChords))]
+    fn val_section(
+        section: SegmentRef<ValSection>,
+        _ctx: CompositionContext,
+    ) -> Result<Vec<Segment>> {
+        Ok(vec![ValChords.over(section)])
+    }
+
+    let engine = RenderEngine::new()
+        + ValRootRenderer
+        + ValSectionRenderer
+        + AdhocRenderer::<ValChords>::new(|_, _| Ok(vec![]))
+            .producing::<PlayNote>()
+            .requiring(ContextRequirement::new::<ValOrphan>(During))
+        + AdhocRenderer::<ValOrphan>::new(|_, _| Ok(vec![]));
+
+    assert_eq!(
+        format!("{:?}", engine),
+        "RenderEngine { renderers: [\"ValChords\", \"ValOrphan\", \"ValRoot\", \"ValSection\"] }"
+    );
+
+    let report = engine.validate::<ValRoot>();
+    assert_eq!(
+        report
+            .reachable
+            .iter()
+            .map(|r| (r.element.to_string(), r.has_renderer))
+            .collect::<Vec<_>>(),
+        [
+            ("ValRoot".to_string(), true),
+            ("ValSection".to_string(), true),
+            ("ValKey".to_string(), false),
+            ("ValChords".to_string(), true),
+            ("PlayNote".to_string(), false),
+        ]
+    );
+    assert_eq!(
+        report.unrendered().collect::<Vec<_>>(),
+        [ElementType::of::<ValKey>(), ElementType::of::<PlayNote>()]
+    );
+    assert!(!report.is_valid());
+    assert_eq!(report.unproduced_requirements.len(), 1);
+    assert_eq!(
+        report.unproduced_requirements[0].renderer,
+        ElementType::of::<ValChords>()
+    );
+    assert_eq!(
+        report.unreachable_renderers,
+        [ElementType::of::<ValOrphan>()]
+    );
+    assert!(report.undeclared_outputs.is_empty());
+
+    let from_section = engine.validate::<ValSection>();
+    assert_eq!(
+        from_section.unreachable_renderers,
+        [ElementType::of::<ValOrphan>(), ElementType::of::<ValRoot>()]
+    );
+    // `ValKey` has no producer when starting from `ValSection`
+    assert_eq!(from_section.unproduced_requirements.len(), 2);
+
+    // Renderers of elements only produced within a `Part` are reachable via their wrapped type
+    let engine = RenderEngine::new()
+        + AdhocRenderer::<ValRoot>::new(|root, _| {
+            Ok(vec![Part::instrument(ValPartMelody).over(root)])
+        })
+        .producing::<Part>()
+        .producing::<ValPartMelody>()
+        + AdhocRenderer::<ValPartMelody>::new(|_, _| Ok(vec![]));
+    let report = engine.validate::<ValRoot>();
+    assert!(report.unreachable_renderers.is_empty());
+    assert_eq!(
+        report.unrendered().collect::<Vec<_>>(),
+        [ElementType::of::<Part>()]
+    );
+
+    // As are those of a wrapped root
+    let report = engine.validate_root(
+        ElementType::of::<Part>(),
+        [ElementType::of::<ValPartMelody>()],
+    );
+    assert_eq!(report.reachable.len(), 2);
+    // `ValRoot` may still be produced by the `ValPartMelody` renderer, which declares no outputs
+    assert_eq!(
+        report.undeclared_outputs,
+        [ElementType::of::<ValPartMelody>()]
+    );
+    assert!(report.unreachable_renderers.is_empty());
+
+    // Once declared as producing nothing, `ValRoot`'s renderer is known to be unreachable
+    #[renderer(register = false, outputs())]
+    fn val_part_melody(
+        _melody: SegmentRef<ValPartMelody>,
+        _ctx: CompositionContext,
+    ) -> Result<Vec<Segment>> {
+        Ok(vec![])
+    }
+
+    for engine in [
+        RenderEngine::new()
+            + AdhocRenderer::<ValRoot>::new(|_, _| Ok(vec![])).producing::<Part>()
+            + AdhocRenderer::<ValPartMelody>::new(|_, _| Ok(vec![])).producing_nothing(),
+        RenderEngine::new()
+            + AdhocRenderer::<ValRoot>::new(|_, _| Ok(vec![])).producing::<Part>()
+            + ValPartMelodyRenderer,
+    ] {
+        let report = engine.validate_root(
+            ElementType::of::<Part>(),
+            [ElementType::of::<ValPartMelody>()],
+        );
+        assert!(report.undeclared_outputs.is_empty());
+        assert_eq!(report.unreachable_renderers, [ElementType::of::<ValRoot>()]);
+    }
+}
+
+#[test]
+fn compose_with_context() {
+    use crate::elements::PlayNote;
+    use crate::render::context::TimingRelation::During;
+    use crate::{CompositionOptions, RenderSegment};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct CtxSong;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct CtxReference;
+
+    #[derive(Element, Serialize, Deserialize, Debug)]
+    struct CtxHarmony;
+
+    // A reference composition at a different beat length (960 vs the default 480)
+    let mut reference_tree = Tree::new();
+    let mut insert = |segment: Segment, parent: Option<usize>| {
+        reference_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: false,
+                error: None,
+                context: false,
+            },
+            parent,
+        )
+    };
+    let root = insert(Segment::new(CtxReference, 0..3840), None);
+    insert(
+        Segment::new(
+            PlayNote {
+                note: 60,
+                velocity: 100,
+            },
+            0..1920,
+        ),
+        Some(root),
+    );
+    insert(
+        Segment::new(
+            PlayNote {
+                note: 67,
+                velocity: 100,
+            },
+            1920..3840,
+        ),
+        Some(root),
+    );
+    let reference = Composition::new(
+        CompositionOptions {
+            ticks_per_beat: 960,
+        },
+        reference_tree,
+    );
+
+    let reference_renders = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&reference_renders);
+    let engine = RenderEngine::new()
+        + AdhocRenderer::<CtxSong>::new(|song, _| {
+            Ok(song
+                .timing
+                .divide_into(960)
+                .into_iter()
+                .map(|t| CtxHarmony.over(t))
+                .collect())
+        })
+        + AdhocRenderer::<CtxHarmony>::new(|harmony, ctx| {
+            let reference_note = ctx
+                .find::<PlayNote>()
+                .with_timing(During, harmony)
+                .within::<CtxReference>()
+                .require()?;
+
+            Ok(vec![PlayNote {
+                note: reference_note.element.note + 4,
+                velocity: 100,
+            }
+            .over(harmony)])
+        })
+        + AdhocRenderer::<CtxReference>::new(move |_, _| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(vec![])
+        });
+
+    let comp = Composer::from(engine).compose_with_seed_and_context(
+        Segment::new(CtxSong, 0..1920),
+        0,
+        [reference],
+    );
+
+    // Pre-rendered context nodes are never rendered
+    assert_eq!(reference_renders.load(Ordering::Relaxed), 0);
+    assert!(comp.tree.iter().all(|n| n.value.rendered));
+    assert_eq!(
+        comp.tree[1].value.segment.timing,
+        crate::timing::Timing::from(0..1920)
+    );
+    assert_eq!(comp.tree[1].parent, Some(0));
+
+    let harmony_notes = comp
+        .tree
+        .iter()
+        .filter(|n| {
+            n.parent.is_some_and(|p| {
+                comp.tree[p]
+                    .value
+                    .segment
+                    .element_as::<CtxHarmony>()
+                    .is_some()
+            })
+        })
+        .filter_map(|n| n.value.segment.element_as::<PlayNote>())
+        .map(|n| n.note)
+        .collect::<Vec<_>>();
+    assert_eq!(harmony_notes, [64, 71]);
+}
+
+//        0
+//      / | \
+//     1  2  3
+//    / \     \
+//   4   5     6
+//             |
+//             7
+fn test_tree() -> Tree<usize> {
+    let mut tree = Tree::new();
+    for (value, parent) in [
+        (0, None),
+        (1, Some(0)),
+        (2, Some(0)),
+        (3, Some(0)),
+        (4, Some(1)),
+        (5, Some(1)),
+        (6, Some(3)),
+        (7, Some(6)),
+    ] {
+        tree.insert(value, parent);
+    }
+
+    tree
+}
+
+fn values<'a>(nodes: impl Iterator<Item = &'a Node<usize>>) -> Vec<usize> {
+    nodes.map(|n| n.value).collect()
+}
+
+#[test]
+fn tree_traversal_orders() {
+    let tree = test_tree();
+
+    assert_eq!(values(tree.dfs_pre()), [0, 1, 4, 5, 2, 3, 6, 7]);
+    assert_eq!(values(tree.dfs_post()), [4, 5, 1, 2, 7, 6, 3, 0]);
+    assert_eq!(values(tree.bfs()), [0, 1, 2, 3, 4, 5, 6, 7]);
+    assert_eq!(values(tree.leaves()), [4, 5, 2, 7]);
+    assert_eq!(values(tree.ancestors_of(7)), [6, 3, 0]);
+    assert!(values(tree.ancestors_of(0)).is_empty());
+    assert_eq!(values(tree.descendants_of(3)), [6, 7]);
+    assert!(values(tree.descendants_of(4)).is_empty());
+}
+
+#[test]
+fn empty_tree_traversal() {
+    let tree: Tree<usize> = Tree::new();
+
+    assert_eq!(tree.dfs_pre().count(), 0);
+    assert_eq!(tree.dfs_post().count(), 0);
+    assert_eq!(tree.bfs().count(), 0);
+    assert_eq!(tree.leaves().count(), 0);
+}
+
+#[test]
+fn remove_subtree() {
+    let mut tree = test_tree();
+    let removed = tree.remove_subtree(1).unwrap();
+
+    assert_eq!(values(removed.dfs_pre()), [1, 4, 5]);
+    assert_eq!(removed[0].parent, None);
+    assert_eq!(removed[0].children, [1, 2]);
+    assert_consistent(&removed);
+
+    assert_eq!(values(tree.dfs_pre()), [0, 2, 3, 6, 7]);
+    assert_eq!(tree.len(), 5);
+    assert_eq!(tree[0].children, [1, 2]);
+    assert_eq!(tree[4].value, 7);
+    assert_eq!(tree[4].parent, Some(3));
+    assert_consistent(&tree);
+
+    let removed = tree.remove_subtree(0).unwrap();
+    assert!(tree.is_empty());
+    assert_eq!(values(removed.dfs_pre()), [0, 2, 3, 6, 7]);
+    assert_consistent(&removed);
+
+    assert_eq!(
+        tree.remove_subtree(0).err(),
+        Some(TreeError::InvalidIndex(0))
+    );
+}
+
+#[test]
+fn replace_value() {
+    let mut tree = test_tree();
+
+    assert_eq!(tree.replace_value(6, 60), Ok(6));
+    assert_eq!(values(tree.dfs_pre()), [0, 1, 4, 5, 2, 3, 60, 7]);
+    assert_eq!(tree.replace_value(8, 0), Err(TreeError::InvalidIndex(8)));
+}
+
+#[test]
+fn reparent() {
+    let mut tree = test_tree();
+
+    assert_eq!(tree.reparent(3, 4), Ok(()));
+    assert_eq!(values(tree.dfs_pre()), [0, 1, 4, 3, 6, 7, 5, 2]);
+    assert_eq!(values(tree.ancestors_of(7)), [6, 3, 4, 1, 0]);
+    assert_consistent(&tree);
+
+    assert_eq!(tree.reparent(1, 7), Err(TreeError::CyclicMove(1, 7)));
+    assert_eq!(tree.reparent(1, 1), Err(TreeError::CyclicMove(1, 1)));
+    assert_eq!(tree.reparent(0, 2), Err(TreeError::RootMove(0)));
+}
+
+#[test]
+fn tree_graft() {
+    let mut tree = test_tree();
+    let removed = tree.remove_subtree(3).unwrap();
+
+    assert_eq!(tree.graft(removed, 4), Ok(5));
+    assert_eq!(values(tree.dfs_pre()), [0, 1, 4, 5, 3, 6, 7, 2]);
+    assert_consistent(&tree);
+
+    assert_eq!(tree.graft(Tree::new(), 0), Err(TreeError::EmptyGraft));
+    assert_eq!(
+        tree.graft(test_tree(), 100),
+        Err(TreeError::InvalidIndex(100))
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn tree_serde_after_mutation() {
+    #[derive(serde::Serialize, serde::Deserialize, Debug)]
+    struct Val {
+        v: usize,
+    }
+
+    let mut tree = Tree::new();
+    for (v, parent) in [(0, None), (1, Some(0)), (2, Some(0)), (3, Some(1))] {
+        tree.insert(Val { v }, parent);
+    }
+
+    tree.reparent(3, 2).unwrap();
+    tree.remove_subtree(1).unwrap();
+    let mut other = Tree::new();
+    other.insert(Val { v: 4 }, None);
+    tree.graft(other, 0).unwrap();
+
+    let serialized = serde_json::to_string(&tree).unwrap();
+    assert_eq!(
+        serialized,
+        r#"{"v":0,"children":[{"v":2,"children":[{"v":3}]},{"v":4}]}"#
+    );
+
+    let deserialized: Tree<Val> = serde_json::from_str(&serialized).unwrap();
+    assert_consistent(&deserialized);
+    assert_eq!(serde_json::to_string(&deserialized).unwrap(), serialized);
+}
+
+// Checks that each node's idx/parent/children fields agree with each other
+fn assert_consistent<T>(tree: &Tree<T>) {
+    for idx in 0..tree.len() {
+        let node = &tree[idx];
+        assert_eq!(node.idx, idx);
+        for child in &node.children {
+            assert_eq!(tree[*child].parent, Some(idx));
+        }
+        if let Some(parent) = node.parent {
+            assert!(tree[parent].children.contains(&idx));
+        }
+    }
+}
+
+#[test]
+fn visitor_enter_exit() {
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl Visitor<usize> for Recorder {
+        fn enter(&mut self, node: &Node<usize>, depth: usize) -> bool {
+            self.0.push(format!("+{}@{}", node.value, depth));
+            // Don't descend into node 3
+            node.value != 3
+        }
+
+        fn exit(&mut self, node: &Node<usize>, _: usize) {
+            self.0.push(format!("-{}", node.value));
+        }
+    }
+
+    let tree = test_tree();
+    let mut recorder = Recorder::default();
+    tree.walk(&mut recorder);
+
+    assert_eq!(
+        recorder.0,
+        ["+0@0", "+1@1", "+4@2", "-4", "+5@2", "-5", "-1", "+2@1", "-2", "+3@1", "-3", "-0"]
+    );
+
+    let mut recorder = Recorder::default();
+    tree.walk_from(6, &mut recorder);
+    assert_eq!(recorder.0, ["+6@0", "+7@1", "-7", "-6"]);
+}
+
+fn timing_set(timings: &[(i32, i32)]) -> TimingSet {
+    timings.iter().map(|(s, e)| *s..*e).collect()
+}
+
+fn timing_pairs(set: &TimingSet) -> Vec<(i32, i32)> {
+    set.iter().map(|t| (t.start, t.end)).collect()
+}
+
+#[test]
+fn timing_set_normalization() {
+    let mut timings = timing_set(&[(5, 8), (0, 2), (1, 3), (3, 4), (6, 7), (9, 9)]);
+    assert_eq!(timing_pairs(&timings), [(0, 3), (3, 4), (5, 8)]);
+
+    timings.insert(Timing::from(2..6));
+    assert_eq!(timing_pairs(&timings), [(0, 8)]);
+    assert_eq!(timings.total_len(), 8);
+    assert_eq!(timings.span(), Some(Timing::from(0..8)));
+}
+
+#[test]
+fn timing_set_algebra() {
+    let a = timing_set(&[(0, 4), (6, 10), (12, 14)]);
+    let b = timing_set(&[(2, 7), (9, 13)]);
+
+    assert_eq!(timing_pairs(&(&a | &b)), [(0, 14)]);
+    assert_eq!(
+        timing_pairs(&(&a & &b)),
+        [(2, 4), (6, 7), (9, 10), (12, 13)]
+    );
+    assert_eq!(timing_pairs(&(&a - &b)), [(0, 2), (7, 9), (13, 14)]);
+    assert_eq!(timing_pairs(&(&b - &a)), [(4, 6), (10, 12)]);
+    assert_eq!(
+        timing_pairs(&a.gaps(-2..16)),
+        [(-2, 0), (4, 6), (10, 12), (14, 16)]
+    );
+    assert_eq!(timing_pairs(&a.clip(3..13)), [(3, 4), (6, 10), (12, 13)]);
+
+    assert!((&a - &a).is_empty());
+    assert_eq!(&a & &TimingSet::new(), TimingSet::new());
+    assert!(a.contains(13) && !a.contains(14));
+}
+
+#[test]
+fn timing_set_from_segment_refs() {
+    use crate::elements::PlayNote;
+    use crate::SegmentRef;
+
+    let segments = [
+        Segment::new(
+            PlayNote {
+                note: 60,
+                velocity: 1,
+            },
+            0..4,
+        ),
+        Segment::new(
+            PlayNote {
+                note: 64,
+                velocity: 1,
+            },
+            2..6,
+        ),
+    ];
+    let notes = segments
+        .iter()
+        .filter_map(|s| SegmentRef::<PlayNote>::try_from(s).ok())
+        .collect::<Vec<_>>();
+
+    assert_eq!(timing_pairs(&TimingSet::from_iter(notes)), [(0, 6)]);
+}
+
+#[test]
+fn tempo_map_constant_tempos() {
+    let tempo_map = TempoMap::new(480, Tempo::from_bpm(120))
+        .with_tempo(960..1920, Tempo::from_bpm(60))
+        .with_tempo(1440..1920, Tempo::from_bpm(240));
+
+    assert_eq!(tempo_map.sections().len(), 2);
+    assert_eq!(tempo_map.seconds_at(960), 1.0);
+    assert_eq!(tempo_map.seconds_at(1440), 2.0);
+    assert_eq!(tempo_map.seconds_at(1920), 2.25);
+    // The last tempo persists
+    assert_eq!(tempo_map.seconds_at(2400), 2.5);
+    // The default tempo applies before any sections
+    assert_eq!(tempo_map.seconds_at(-480), -0.5);
+
+    for tick in [-960, -1, 0, 1, 480, 1000, 1500, 5000] {
+        assert_eq!(tempo_map.tick_at(tempo_map.seconds_at(tick)), tick);
+    }
+}
+
+#[test]
+fn tempo_map_ramps() {
+    let tempo_map = TempoMap::new(480, Tempo::from_bpm(60))
+        .with_ramp(0..4800, TempoRamp::from_bpm(60, 120))
+        .with_tempo(2400..3360, Tempo::from_bpm(100));
+
+    let sections = tempo_map.sections();
+    assert_eq!(
+        sections.iter().map(|s| s.timing).collect::<Vec<_>>(),
+        vec![
+            Timing::from(0..2400),
+            Timing::from(2400..3360),
+            Timing::from(3360..4800)
+        ]
+    );
+    assert_eq!((sections[0].start_bpm, sections[0].end_bpm), (60.0, 90.0));
+    assert_eq!((sections[2].start_bpm, sections[2].end_bpm), (102.0, 120.0));
+
+    // An accelerando takes less time than its starting tempo would
+    assert!(tempo_map.seconds_at(2400) < 5.0);
+    assert!(tempo_map.seconds_at(2400) > 2400.0 / 480.0 * 60.0 / 90.0);
+    assert_eq!(tempo_map.bpm_at(4800), 120.0);
+
+    for tick in [0, 100, 2399, 2400, 3000, 3360, 4000, 4800, 6000] {
+        assert_eq!(tempo_map.tick_at(tempo_map.seconds_at(tick)), tick);
+    }
+}
+
+#[test]
+fn tempo_map_non_finite_times() {
+    let tempo_map = TempoMap::new(480, Tempo::from_bpm(60)).with_tempo(0..480, Tempo::from_bpm(90));
+
+    assert_eq!(tempo_map.tick_at(f64::NAN), 0);
+    assert_eq!(tempo_map.tick_at(f64::INFINITY), i32::MAX);
+    assert_eq!(tempo_map.tick_at(f64::NEG_INFINITY), i32::MIN);
+}
+
+#[test]
+fn tempo_map_edges() {
+    let tempo_map = TempoMap::new(480, Tempo::from_bpm(60))
+        .with_tempo(0..480, Tempo::from_bpm(90))
+        .with_tempo(480..960, Tempo::from_bpm(0))
+        .with_ramp(960..1440, TempoRamp::from_bpm(0, 30));
+
+    // The last section's tempo persists through the end of the tick range
+    assert_eq!(tempo_map.bpm_at(i32::MAX), 30.0);
+    assert_eq!(tempo_map.bpm_at(i32::MIN), 60.0);
+
+    // Tempos of 0 BPM are treated as 1 BPM
+    assert_eq!(tempo_map.bpm_at(480), 1.0);
+    assert_eq!(tempo_map.bpm_at(960), 1.0);
+    assert!(tempo_map.seconds_at(i32::MAX).is_finite());
+    assert_eq!(tempo_map.seconds_between(480, 960), 60.0);
+}
+
+#[test]
+fn tempo_map_from_tree() {
+    use crate::render::RenderSegment;
+
+    let mut tree = Tree::new();
+    for (timing, tempo, parent) in [
+        (0..1920, 120, None),
+        (480..960, 60, Some(0)),
+        (720..960, 240, Some(1)),
+    ] {
+        tree.insert(
+            RenderSegment {
+                segment: Segment::new(Tempo::from_bpm(tempo), timing),
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        );
+    }
+    let tempo_map = TempoMap::from_tree(&tree, 480);
+
+    assert_eq!(
+        tempo_map
+            .sections()
+            .iter()
+            .map(|s| (s.timing, s.start_bpm))
+            .collect::<Vec<_>>(),
+        [
+            (Timing::from(0..480), 120.0),
+            (Timing::from(480..720), 60.0),
+            (Timing::from(720..960), 240.0),
+            (Timing::from(960..1920), 120.0)
+        ]
+    );
+    assert_eq!(tempo_map.seconds_at(960), 0.5 + 0.5 + 0.125);
+}
@@ -11,9 +11,15 @@ pub const STANDARD_BEAT_LENGTH: i32 = 480;
 /// Higher precision beat length if greater divisibility is required.
 pub const HIGH_PRECISION_BEAT_LENGTH: i32 = 960;
 
+mod set;
+pub use set::TimingSet;
+
+mod tempo_map;
+pub use tempo_map::{TempoMap, TempoSection};
+
 /// Types implementing [`Element`].
 pub mod elements {
-    pub use super::Tempo;
+    pub use super::{Tempo, TempoRamp};
 }
 
 /// The speed of a (or part of a) composition in beats per minute.
@@ -40,6 +46,35 @@ impl Tempo {
     }
 }
 
+/// A gradual tempo change (accelerando/ritardando) over the duration of its segment. The tempo
+/// changes linearly (in beats per minute) from `start` at the segment's start to `end` at its end.
+#[derive(Element, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TempoRamp {
+    pub(super) start_bpm: u32,
+    pub(super) end_bpm: u32,
+}
+
+impl TempoRamp {
+    /// Creates a [`TempoRamp`] changing from `start` to `end` beats per minute.
+    pub fn from_bpm(start: u32, end: u32) -> TempoRamp {
+        TempoRamp {
+            start_bpm: start,
+            end_bpm: end,
+        }
+    }
+
+    /// Returns the starting tempo.
+    pub fn start(&self) -> Tempo {
+        Tempo::from_bpm(self.start_bpm)
+    }
+
+    /// Returns the ending tempo.
+    pub fn end(&self) -> Tempo {
+        Tempo::from_bpm(self.end_bpm)
+    }
+}
+
 /// A start-inclusive, end-exclusive [`i32`] range (like [`Range<i32>`]) that is copyable,
 /// and implements several utility methods.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -98,6 +133,31 @@ impl From<&Timing> for Range<i32> {
 }
 
 impl Timing {
+    /// Converts this timing from one resolution (ticks per beat) to another, using the given
+    /// [`Rounding`] policy for both start and end.
+    /// ```
+    /// # use redact_composer_core::timing::{Rounding, Timing};
+    /// let timing = Timing::from(480..600);
+    /// assert_eq!(timing.rescaled(480, 960, Rounding::Nearest), Timing::from(960..1200));
+    /// assert_eq!(timing.rescaled(480, 4, Rounding::Floor), Timing::from(4..5));
+    /// assert_eq!(timing.rescaled(480, 4, Rounding::Ceil), Timing::from(4..5));
+    /// assert_eq!(Timing::from(0..50).rescaled(480, 4, Rounding::Nearest), Timing::from(0..0));
+    /// ```
+    ///
+    /// # Panics
+    /// If either resolution is not positive.
+    pub fn rescaled(
+        &self,
+        from_ticks_per_beat: i32,
+        to_ticks_per_beat: i32,
+        rounding: Rounding,
+    ) -> Timing {
+        Timing {
+            start: rounding.rescale(self.start, from_ticks_per_beat, to_ticks_per_beat),
+            end: rounding.rescale(self.end, from_ticks_per_beat, to_ticks_per_beat),
+        }
+    }
+
     /// Returns the length of this timing (`self.end` - `self.start`).
     pub fn len(&self) -> i32 {
         self.end - self.start
@@ -283,6 +343,58 @@ impl Timing {
     }
 }
 
+/// Rounding policy used when converting ticks between resolutions. See [`Timing::rescaled`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Rounding {
+    /// Rounds to the nearest tick (halfway values round away from zero).
+    #[default]
+    Nearest,
+    /// Rounds down (towards negative infinity).
+    Floor,
+    /// Rounds up (towards positive infinity).
+    Ceil,
+}
+
+impl Rounding {
+    /// Converts a tick value from one resolution (ticks per beat) to another, rounding according
+    /// to this policy. Results outside the `i32` range saturate to [`i32::MIN`]/[`i32::MAX`].
+    /// ```
+    /// # use redact_composer_core::timing::Rounding;
+    /// assert_eq!(Rounding::Nearest.rescale(960, 960, 480), 480);
+    /// assert_eq!(Rounding::Floor.rescale(5, 480, 96), 1);
+    /// assert_eq!(Rounding::Nearest.rescale(i32::MAX, 1, 2), i32::MAX);
+    /// ```
+    ///
+    /// # Panics
+    /// If either resolution is not positive.
+    pub fn rescale(&self, tick: i32, from: i32, to: i32) -> i32 {
+        assert!(
+            from > 0 && to > 0,
+            "Ticks per beat must be positive (rescaling {:?} -> {:?}).",
+            from,
+            to
+        );
+        let (numerator, denominator) = (tick as i64 * to as i64, from as i64);
+        let floor = numerator.div_euclid(denominator);
+        let remainder = numerator.rem_euclid(denominator);
+
+        (match self {
+            Rounding::Floor => floor,
+            Rounding::Ceil => floor + i64::from(remainder != 0),
+            Rounding::Nearest => {
+                let round_up = if numerator < 0 {
+                    remainder * 2 > denominator
+                } else {
+                    remainder * 2 >= denominator
+                };
+                floor + i64::from(round_up)
+            }
+        })
+        .clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+    }
+}
+
 /// Defines a start/end offsets for use when shifting a [`Timing`]'s boundaries.
 pub trait EndpointOffsets {
     /// Offset to be applied to the [`Timing`]'s start bound.
@@ -0,0 +1,259 @@
+use std::ops::{BitAnd, BitOr, Sub};
+
+use super::Timing;
+
+/// A set of ticks, represented as an ordered sequence of non-overlapping [`Timing`]s.
+///
+/// Overlapping timings are merged as they are added, while *adjacent* timings (i.e. one's end ==
+/// another's start) are kept distinct -- preserving segment boundaries -- unless explicitly merged
+/// via [`merge_adjacent`](Self::merge_adjacent).
+///
+/// Can be collected from any [`Timing`] convertible type, including
+/// [`SegmentRef`](crate::SegmentRef)s returned from [`CtxQuery`](crate::render::context::CtxQuery).
+/// ```
+/// # use redact_composer_core::timing::{Timing, TimingSet};
+/// let phrase = TimingSet::from(0..16);
+/// let fills = [4..6, 12..16].into_iter().collect::<TimingSet>();
+///
+/// assert_eq!(
+///     phrase.difference(&fills).timings(),
+///     [Timing::from(0..4), Timing::from(6..12)]
+/// );
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TimingSet {
+    timings: Vec<Timing>,
+}
+
+impl TimingSet {
+    /// Creates an empty [`TimingSet`].
+    pub fn new() -> TimingSet {
+        TimingSet::default()
+    }
+
+    /// Adds a timing to this set, merging any overlaps. Empty timings are ignored.
+    pub fn insert(&mut self, timing: impl Into<Timing>) {
+        let timing = timing.into();
+        if timing.is_empty() {
+            return;
+        }
+
+        let idx = self.timings.partition_point(|t| t.start <= timing.start);
+        self.timings.insert(idx, timing);
+        self.timings = Self::merged(self.timings.drain(..), |prev, next| prev.end > next.start);
+    }
+
+    /// The timings of this set, in order.
+    pub fn timings(&self) -> &[Timing] {
+        &self.timings
+    }
+
+    /// Iterates over the timings of this set, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &Timing> {
+        self.timings.iter()
+    }
+
+    /// Returns `true` if this set contains no ticks.
+    pub fn is_empty(&self) -> bool {
+        self.timings.is_empty()
+    }
+
+    /// The number of (distinct) timings in this set.
+    pub fn len(&self) -> usize {
+        self.timings.len()
+    }
+
+    /// The total number of ticks in this set.
+    pub fn total_len(&self) -> i32 {
+        self.timings.iter().map(Timing::len).sum()
+    }
+
+    /// Returns `true` if the given tick is in this set.
+    pub fn contains(&self, tick: i32) -> bool {
+        self.timings.iter().any(|t| t.contains(&tick))
+    }
+
+    /// The timing spanning this entire set (from its first start until its last end), if not
+    /// empty.
+    pub fn span(&self) -> Option<Timing> {
+        match (self.timings.first(), self.timings.last()) {
+            (Some(first), Some(last)) => Some(Timing::from(first.start..last.end)),
+            _ => None,
+        }
+    }
+
+    /// Returns the ticks in either this set or `other`.
+    /// ```
+    /// # use redact_composer_core::timing::{Timing, TimingSet};
+    /// let union = TimingSet::from(0..4).union(&TimingSet::from(2..6));
+    /// assert_eq!(union.timings(), [Timing::from(0..6)]);
+    /// ```
+    pub fn union(&self, other: &TimingSet) -> TimingSet {
+        self.iter().chain(other.iter()).collect()
+    }
+
+    /// Returns the ticks in both this set and `other`.
+    /// ```
+    /// # use redact_composer_core::timing::{Timing, TimingSet};
+    /// let intersection = TimingSet::from(0..4).intersection(&TimingSet::from(2..6));
+    /// assert_eq!(intersection.timings(), [Timing::from(2..4)]);
+    /// ```
+    pub fn intersection(&self, other: &TimingSet) -> TimingSet {
+        let mut timings = vec![];
+        let (mut a, mut b) = (0, 0);
+
+        while a < self.timings.len() && b < other.timings.len() {
+            let (ta, tb) = (self.timings[a], other.timings[b]);
+            let overlap = Timing::from(ta.start.max(tb.start)..ta.end.min(tb.end));
+            if !overlap.is_empty() {
+                timings.push(overlap);
+            }
+
+            if ta.end < tb.end {
+                a += 1;
+            } else {
+                b += 1;
+            }
+        }
+
+        TimingSet { timings }
+    }
+
+    /// Returns the ticks in this set which are not in `other`.
+    /// ```
+    /// # use redact_composer_core::timing::{Timing, TimingSet};
+    /// let difference = TimingSet::from(0..6).difference(&TimingSet::from(2..4));
+    /// assert_eq!(difference.timings(), [Timing::from(0..2), Timing::from(4..6)]);
+    /// ```
+    pub fn difference(&self, other: &TimingSet) -> TimingSet {
+        let mut timings = vec![];
+
+        for timing in &self.timings {
+            let mut cursor = timing.start;
+            for removed in other
+                .timings
+                .iter()
+                .filter(|r| r.end > timing.start && r.start < timing.end)
+            {
+                if removed.start > cursor {
+                    timings.push(Timing::from(cursor..removed.start));
+                }
+                cursor = cursor.max(removed.end);
+            }
+
+            if cursor < timing.end {
+                timings.push(Timing::from(cursor..timing.end));
+            }
+        }
+
+        TimingSet { timings }
+    }
+
+    /// Returns the ticks within `bounds` which are not in this set.
+    /// ```
+    /// # use redact_composer_core::timing::{Timing, TimingSet};
+    /// let notes = [1..3, 3..5, 7..8].into_iter().collect::<TimingSet>();
+    /// assert_eq!(notes.gaps(0..10).timings(), [Timing::from(0..1), Timing::from(5..7), Timing::from(8..10)]);
+    /// ```
+    pub fn gaps(&self, bounds: impl Into<Timing>) -> TimingSet {
+        TimingSet::from(bounds).difference(self)
+    }
+
+    /// Returns this set restricted to the ticks within `bounds`.
+    pub fn clip(&self, bounds: impl Into<Timing>) -> TimingSet {
+        self.intersection(&TimingSet::from(bounds))
+    }
+
+    /// Returns this set with adjacent timings (one's end == the next's start) merged.
+    /// ```
+    /// # use redact_composer_core::timing::{Timing, TimingSet};
+    /// let set = [0..2, 2..4, 5..6].into_iter().collect::<TimingSet>();
+    /// assert_eq!(set.len(), 3);
+    /// assert_eq!(set.merge_adjacent().timings(), [Timing::from(0..4), Timing::from(5..6)]);
+    /// ```
+    pub fn merge_adjacent(&self) -> TimingSet {
+        TimingSet {
+            timings: Self::merged(self.timings.iter().copied(), |prev, next| {
+                prev.end >= next.start
+            }),
+        }
+    }
+
+    // Merges consecutive (start-ordered) timings satisfying `should_merge`.
+    fn merged(
+        timings: impl IntoIterator<Item = Timing>,
+        should_merge: impl Fn(&Timing, &Timing) -> bool,
+    ) -> Vec<Timing> {
+        timings.into_iter().fold(vec![], |mut merged, next| {
+            match merged.last_mut() {
+                Some(prev) if should_merge(prev, &next) => prev.end = prev.end.max(next.end),
+                _ => merged.push(next),
+            }
+
+            merged
+        })
+    }
+}
+
+impl<T: Into<Timing>> FromIterator<T> for TimingSet {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut timings = iter
+            .into_iter()
+            .map(Into::into)
+            .filter(|t: &Timing| !t.is_empty())
+            .collect::<Vec<_>>();
+        timings.sort_by_key(|t| (t.start, t.end));
+
+        TimingSet {
+            timings: Self::merged(timings, |prev, next| prev.end > next.start),
+        }
+    }
+}
+
+impl<T: Into<Timing>> From<T> for TimingSet {
+    fn from(value: T) -> Self {
+        [value].into_iter().collect()
+    }
+}
+
+impl<'a> IntoIterator for &'a TimingSet {
+    type Item = &'a Timing;
+    type IntoIter = std::slice::Iter<'a, Timing>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.timings.iter()
+    }
+}
+
+impl IntoIterator for TimingSet {
+    type Item = Timing;
+    type IntoIter = std::vec::IntoIter<Timing>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.timings.into_iter()
+    }
+}
+
+impl BitOr for &TimingSet {
+    type Output = TimingSet;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(rhs)
+    }
+}
+
+impl BitAnd for &TimingSet {
+    type Output = TimingSet;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.intersection(rhs)
+    }
+}
+
+impl Sub for &TimingSet {
+    type Output = TimingSet;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.difference(rhs)
+    }
+}
@@ -0,0 +1,328 @@
+use super::{Tempo, TempoRamp, Timing};
+use crate::render::tree::Tree;
+use crate::render::RenderSegment;
+use crate::SegmentRef;
+
+/// Converts between tick positions and wall-clock time (in seconds) according to the [`Tempo`]
+/// and [`TempoRamp`] segments of a composition.
+///
+/// Time `0.0` corresponds to tick `0`. Outside of any tempo section, the tempo of the previous
+/// section persists (or the default tempo, before the first section). Tempos below 1 BPM (which
+/// would never reach the next beat) are treated as 1 BPM.
+/// ```
+/// # use redact_composer_core::timing::{elements::{Tempo, TempoRamp}, TempoMap};
+/// let tempo_map = TempoMap::new(480, Tempo::from_bpm(60))
+///     .with_tempo(480..960, Tempo::from_bpm(120))
+///     .with_ramp(960..1920, TempoRamp::from_bpm(120, 60));
+///
+/// assert_eq!(tempo_map.seconds_at(480), 1.0);
+/// assert_eq!(tempo_map.seconds_at(960), 1.5);
+/// assert_eq!(tempo_map.tick_at(1.5), 960);
+/// assert_eq!(tempo_map.bpm_at(1440), 90.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TempoMap {
+    ticks_per_beat: i32,
+    default_bpm: f64,
+    sections: Vec<TempoSection>,
+    // Cached from `sections`, see `build_pieces`
+    pieces: Vec<Piece>,
+}
+
+/// A section of a [`TempoMap`] over which the tempo is either constant, or changes linearly.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TempoSection {
+    /// The timing of this section.
+    pub timing: Timing,
+    /// The tempo (in beats per minute) at the start of this section.
+    pub start_bpm: f64,
+    /// The tempo (in beats per minute) at the end of this section.
+    pub end_bpm: f64,
+}
+
+impl TempoSection {
+    /// Returns `true` if the tempo changes during this section.
+    pub fn is_ramp(&self) -> bool {
+        self.start_bpm != self.end_bpm
+    }
+
+    /// Returns the tempo (in beats per minute) at the given tick, which is clamped to this
+    /// section's timing.
+    pub fn bpm_at(&self, tick: i32) -> f64 {
+        let tick = tick.clamp(self.timing.start, self.timing.end);
+        self.start_bpm + self.slope() * (tick - self.timing.start) as f64
+    }
+
+    fn constant(timing: Timing, tempo: Tempo) -> TempoSection {
+        TempoSection {
+            timing,
+            start_bpm: bpm(tempo.bpm()),
+            end_bpm: bpm(tempo.bpm()),
+        }
+    }
+
+    fn ramp(timing: Timing, ramp: TempoRamp) -> TempoSection {
+        TempoSection {
+            timing,
+            start_bpm: bpm(ramp.start_bpm),
+            end_bpm: bpm(ramp.end_bpm),
+        }
+    }
+
+    fn slope(&self) -> f64 {
+        if self.is_ramp() && !self.timing.is_empty() {
+            (self.end_bpm - self.start_bpm) / self.timing.len() as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+impl TempoMap {
+    /// Creates a tempo map using `default` wherever no other tempo is specified.
+    pub fn new(ticks_per_beat: i32, default: Tempo) -> TempoMap {
+        let mut tempo_map = TempoMap {
+            ticks_per_beat,
+            default_bpm: bpm(default.bpm()),
+            sections: vec![],
+            pieces: vec![],
+        };
+        tempo_map.pieces = tempo_map.build_pieces();
+
+        tempo_map
+    }
+
+    /// Creates a tempo map from the [`Tempo`] and [`TempoRamp`] segments of a composition tree.
+    /// These are applied in tree order, meaning those added later during rendering override those
+    /// they overlap. The root segment's timing defaults to 120 BPM (the MIDI default).
+    pub fn from_tree(tree: &Tree<RenderSegment>, ticks_per_beat: i32) -> TempoMap {
+        Self::from_tree_with_default(tree, ticks_per_beat, Tempo::from_bpm(120))
+    }
+
+    /// Same as [`TempoMap::from_tree`], but with the given `default` tempo for the root segment's
+    /// timing (and anywhere else not covered by [`Tempo`]/[`TempoRamp`] segments).
+    pub fn from_tree_with_default(
+        tree: &Tree<RenderSegment>,
+        ticks_per_beat: i32,
+        default: Tempo,
+    ) -> TempoMap {
+        let mut tempo_map = TempoMap::new(ticks_per_beat, default);
+        let Some(root) = tree.root() else {
+            return tempo_map;
+        };
+
+        // Pieces are only built once all sections are inserted
+        tempo_map.insert_section(TempoSection::constant(root.value.segment.timing, default));
+        for node in tree.iter() {
+            let segment = &node.value.segment;
+            if let Ok(tempo) = SegmentRef::<Tempo>::try_from(segment) {
+                tempo_map.insert_section(TempoSection::constant(*tempo.timing, *tempo.element));
+            } else if let Ok(ramp) = SegmentRef::<TempoRamp>::try_from(segment) {
+                tempo_map.insert_section(TempoSection::ramp(*ramp.timing, *ramp.element));
+            }
+        }
+        tempo_map.pieces = tempo_map.build_pieces();
+
+        tempo_map
+    }
+
+    /// Sets a constant tempo over the given timing, overriding any overlapped sections.
+    pub fn with_tempo(self, timing: impl Into<Timing>, tempo: Tempo) -> TempoMap {
+        self.with_section(TempoSection::constant(timing.into(), tempo))
+    }
+
+    /// Sets a gradual tempo change over the given timing, overriding any overlapped sections.
+    pub fn with_ramp(self, timing: impl Into<Timing>, ramp: TempoRamp) -> TempoMap {
+        self.with_section(TempoSection::ramp(timing.into(), ramp))
+    }
+
+    fn with_section(mut self, section: TempoSection) -> TempoMap {
+        self.insert_section(section);
+        self.pieces = self.build_pieces();
+
+        self
+    }
+
+    // Inserts a section without rebuilding `pieces`.
+    fn insert_section(&mut self, section: TempoSection) {
+        if section.timing.is_empty() {
+            return;
+        }
+
+        // Sections are ordered and non-overlapping, so any overlapped by `section` are contiguous
+        let timing = section.timing;
+        let first = self
+            .sections
+            .partition_point(|s| s.timing.end <= timing.start);
+        let last = self
+            .sections
+            .partition_point(|s| s.timing.start < timing.end);
+
+        // Keep any non-overlapped portions of the overlapped sections
+        let mut replacement = vec![];
+        if let Some(existing) = self.sections[first..last].first() {
+            if existing.timing.start < timing.start {
+                replacement.push(TempoSection {
+                    timing: Timing::from(existing.timing.start..timing.start),
+                    start_bpm: existing.start_bpm,
+                    end_bpm: existing.bpm_at(timing.start),
+                });
+            }
+        }
+        replacement.push(section);
+        if let Some(existing) = self.sections[first..last].last() {
+            if existing.timing.end > timing.end {
+                replacement.push(TempoSection {
+                    timing: Timing::from(timing.end..existing.timing.end),
+                    start_bpm: existing.bpm_at(timing.end),
+                    end_bpm: existing.end_bpm,
+                });
+            }
+        }
+
+        self.sections.splice(first..last, replacement);
+    }
+
+    /// The number of ticks per beat used for conversions.
+    pub fn ticks_per_beat(&self) -> i32 {
+        self.ticks_per_beat
+    }
+
+    /// The tempo sections of this map, ordered by start.
+    pub fn sections(&self) -> &[TempoSection] {
+        &self.sections
+    }
+
+    /// Returns the tempo (in beats per minute) at the given tick.
+    pub fn bpm_at(&self, tick: i32) -> f64 {
+        let idx = self.pieces.partition_point(|p| p.end <= tick);
+
+        self.pieces[idx.min(self.pieces.len() - 1)].bpm_at(tick)
+    }
+
+    /// Returns the time (in seconds) of the given tick. Negative ticks result in negative time.
+    pub fn seconds_at(&self, tick: i32) -> f64 {
+        let idx = self.pieces.partition_point(|p| p.end <= tick);
+        let p = &self.pieces[idx.min(self.pieces.len() - 1)];
+
+        self.seconds_from_anchor(p, tick)
+    }
+
+    /// Returns the time (in seconds) elapsed between two ticks.
+    pub fn seconds_between(&self, from: i32, to: i32) -> f64 {
+        self.seconds_at(to) - self.seconds_at(from)
+    }
+
+    /// Returns the tick (rounded to the nearest) at the given time (in seconds). Infinite times
+    /// saturate to [`i32::MAX`]/[`i32::MIN`], and `NaN` results in tick `0`.
+    pub fn tick_at(&self, seconds: f64) -> i32 {
+        if seconds.is_nan() {
+            return 0;
+        } else if seconds.is_infinite() {
+            return if seconds > 0.0 { i32::MAX } else { i32::MIN };
+        }
+
+        let idx = self.pieces.partition_point(|p| p.start_seconds <= seconds);
+        let p = &self.pieces[idx.saturating_sub(1)];
+        let ticks = self.ticks(p.bpm_at(p.anchor), p.slope, seconds - p.anchor_seconds);
+
+        (p.anchor as f64 + ticks).round() as i32
+    }
+
+    // Seconds of the given tick, relative to the anchor of its piece.
+    fn seconds_from_anchor(&self, piece: &Piece, tick: i32) -> f64 {
+        let ticks = tick as f64 - piece.anchor as f64;
+
+        piece.anchor_seconds + self.seconds(piece.bpm_at(piece.anchor), piece.slope, ticks)
+    }
+
+    // Seconds elapsed over `ticks` starting at `bpm`, which changes by `slope` (bpm per tick).
+    fn seconds(&self, bpm: f64, slope: f64, ticks: f64) -> f64 {
+        let ticks_per_beat = self.ticks_per_beat as f64;
+        if slope == 0.0 {
+            ticks * 60.0 / (bpm * ticks_per_beat)
+        } else {
+            60.0 / (ticks_per_beat * slope) * ((bpm + slope * ticks) / bpm).ln()
+        }
+    }
+
+    // The inverse of `seconds`: ticks elapsed over `seconds` (which may be negative).
+    fn ticks(&self, bpm: f64, slope: f64, seconds: f64) -> f64 {
+        let ticks_per_beat = self.ticks_per_beat as f64;
+        if slope == 0.0 {
+            seconds * bpm * ticks_per_beat / 60.0
+        } else {
+            bpm * ((seconds * ticks_per_beat * slope / 60.0).exp() - 1.0) / slope
+        }
+    }
+
+    // Contiguous pieces spanning all ticks, including constant tempo pieces filling any gaps. Each
+    // piece is anchored at its tick closest to `0`, with the time of that anchor precomputed.
+    fn build_pieces(&self) -> Vec<Piece> {
+        let mut pieces = vec![];
+        let (mut prev_end, mut bpm) = (i32::MIN, self.default_bpm);
+
+        let mut push = |start: i32, end: i32, bpm: f64, slope: f64| {
+            pieces.push(Piece {
+                start,
+                end,
+                bpm,
+                slope,
+                anchor: 0.clamp(start, end),
+                anchor_seconds: 0.0,
+                start_seconds: f64::NEG_INFINITY,
+            })
+        };
+        for section in &self.sections {
+            if section.timing.start > prev_end {
+                push(prev_end, section.timing.start, bpm, 0.0);
+            }
+            push(
+                section.timing.start,
+                section.timing.end,
+                section.start_bpm,
+                section.slope(),
+            );
+            (prev_end, bpm) = (section.timing.end, section.end_bpm);
+        }
+        push(prev_end, i32::MAX, bpm, 0.0);
+
+        // Anchor times accumulate outward from the piece containing tick `0`
+        let zero_idx = pieces.partition_point(|p| p.end <= 0);
+        for idx in (zero_idx + 1)..pieces.len() {
+            pieces[idx].anchor_seconds =
+                self.seconds_from_anchor(&pieces[idx - 1], pieces[idx].start);
+        }
+        for idx in (0..zero_idx).rev() {
+            pieces[idx].anchor_seconds =
+                self.seconds_from_anchor(&pieces[idx + 1], pieces[idx].end);
+        }
+        for piece in pieces.iter_mut().skip(1) {
+            piece.start_seconds = self.seconds_from_anchor(piece, piece.start);
+        }
+
+        pieces
+    }
+}
+
+// Tempos below 1 BPM are clamped, since a tempo of 0 would never reach the next beat.
+fn bpm(bpm: u32) -> f64 {
+    bpm.max(1) as f64
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Piece {
+    start: i32,
+    end: i32,
+    bpm: f64,
+    slope: f64,
+    anchor: i32,
+    anchor_seconds: f64,
+    start_seconds: f64,
+}
+
+impl Piece {
+    fn bpm_at(&self, tick: i32) -> f64 {
+        self.bpm + self.slope * (tick as f64 - self.start as f64)
+    }
+}
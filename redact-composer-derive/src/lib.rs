@@ -1,10 +1,16 @@
 #![deny(missing_docs)]
 //! Derive macros for `redact_composer`. Not needed as a direct dependency.
 
-use darling::FromDeriveInput;
+use darling::ast::NestedMeta;
+use darling::util::PathList;
+use darling::{Error, FromDeriveInput, FromMeta};
 use proc_macro::{self, TokenStream};
-use quote::quote;
-use syn::{parse_macro_input, DeriveInput, Expr};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Expr, Fields, FnArg, GenericArgument, ItemFn, Lit,
+    Member, PathArguments, Type,
+};
 
 #[derive(FromDeriveInput, Default)]
 #[darling(default, attributes(element))]
@@ -12,6 +18,57 @@ struct Opts {
     name: Option<String>,
     wrapped_element: Option<Expr>,
     wrapped_element_doc: Option<String>,
+    ticks: PathList,
+    #[darling(multiple)]
+    instance: Vec<Type>,
+}
+
+#[derive(FromMeta, Default)]
+#[darling(default)]
+struct RendererOpts {
+    name: Option<syn::Ident>,
+    register: Option<bool>,
+    requires: Requirements,
+    waits_for: Requirements,
+    outputs: Option<PathList>,
+}
+
+// Declared context requirements, as a list of `Element = TimingRelation`.
+#[derive(Default)]
+struct Requirements(Vec<(syn::Path, syn::Ident)>);
+
+impl FromMeta for Requirements {
+    fn from_list(items: &[NestedMeta]) -> darling::Result<Self> {
+        let mut errors = Error::accumulator();
+        let requirements = items
+            .iter()
+            .filter_map(|item| {
+                errors.handle(match item {
+                    NestedMeta::Meta(syn::Meta::NameValue(requirement)) => {
+                        let relation = match &requirement.value {
+                            Expr::Path(relation) => relation.path.get_ident(),
+                            _ => None,
+                        };
+
+                        relation
+                            .map(|relation| (requirement.path.clone(), relation.clone()))
+                            .ok_or_else(|| {
+                                Error::custom(
+                                    "Expected a `TimingRelation` variant (e.g. `During`).",
+                                )
+                                .with_span(&requirement.value)
+                            })
+                    }
+                    _ => Err(Error::custom(
+                        "Expected `Element = TimingRelation` (e.g. `Chord = During`).",
+                    )
+                    .with_span(item)),
+                })
+            })
+            .collect();
+
+        errors.finish_with(Requirements(requirements))
+    }
 }
 
 /// Derives a `redact-composer` `Element` impl for this type.
@@ -42,10 +99,28 @@ struct Opts {
 ///   another you can specify the expression to access it (e.g. `Some(self.wrapped_item())`). The
 ///   expression should return an `Option<&dyn Element>`.
 ///
+///   As a shorthand, the name (or index, for tuple structs) of the field holding the wrapped
+///   element may be given instead (e.g. `wrapped_element = inner`). The field may be any `Element`
+///   type, a `Box<dyn Element>`, or an `Option` of either.
+///
 ///   **Default:** `None`.
 ///
 /// * **`wrapped_element_doc: String`:** Use this to provide a doc comment (no /// necessary) for the
 ///   wrapped element. Only has an effect if `wrapped_element` is also present.
+///
+/// * **`ticks: PathList`:** Names the (`i32` or `Option<i32>`) fields holding tick values, such as
+///   a beat length (e.g. `ticks(beat_length)`). These are rescaled along with segment timings when
+///   rescaling a `Composition` to a different resolution (ticks per beat).
+///
+///   **Default:** none.
+///
+/// * `feature: serde`
+///
+///   **`instance: String`:** Generic types cannot be registered with `typetag` directly, since
+///   deserialization requires a concrete type. Instead, each concrete instantiation to be used as
+///   an `Element` must be listed (e.g. `#[element(instance = "Layer<Chord>", instance =
+///   "Layer<Note>")]`), and is registered using its type as its serialization name. Without the
+///   `serde` feature, a generic impl is derived instead and this option is ignored.
 #[proc_macro_derive(Element, attributes(element))]
 pub fn derive(input: TokenStream) -> TokenStream {
     derive_impl(quote! { ::redact_composer }, input)
@@ -59,43 +134,513 @@ pub fn core_derive(input: TokenStream) -> TokenStream {
 }
 
 fn derive_impl(crate_path: proc_macro2::TokenStream, input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input);
-    let opts = Opts::from_derive_input(&input).expect("Invalid element option");
-    let DeriveInput { ident, .. } = input;
+    let input: DeriveInput = parse_macro_input!(input);
 
-    let wrapped_element_comment = if let Some(comment) = opts.wrapped_element_doc {
-        quote! { #[doc= #comment ] }
-    } else {
-        quote! { #[doc= "Wrapped element." ] }
+    match element_impls(&crate_path, &input) {
+        Ok(output) => output.into(),
+        Err(err) => err.write_errors().into(),
+    }
+}
+
+fn element_impls(
+    crate_path: &proc_macro2::TokenStream,
+    input: &DeriveInput,
+) -> darling::Result<proc_macro2::TokenStream> {
+    let opts = Opts::from_derive_input(input)?;
+    let DeriveInput {
+        ident, generics, ..
+    } = input;
+    let mut errors = Error::accumulator();
+
+    let wrapped_element_comment = match &opts.wrapped_element_doc {
+        Some(comment) => quote! { #[doc= #comment ] },
+        None => quote! { #[doc= "Wrapped element." ] },
     };
 
-    let wrapped_element_accessor = match opts.wrapped_element {
-        Some(accessor) => quote! {
-            #wrapped_element_comment
-            fn wrapped_element(&self) -> Option<&dyn Element> {
-                #accessor
+    let wrapped_element_accessor = match &opts.wrapped_element {
+        Some(accessor) => {
+            let accessor = errors
+                .handle(wrapped_element_expr(crate_path, input, accessor))
+                .unwrap_or_else(|| accessor.to_token_stream());
+
+            quote! {
+                #wrapped_element_comment
+                fn wrapped_element(&self) -> Option<&dyn #crate_path::Element> {
+                    #accessor
+                }
             }
-        },
+        }
         None => quote! {},
     };
 
-    let typetag_attr = if cfg!(feature = "serde") {
-        let type_tag_opts = match opts.name {
+    let rescale_ticks = if opts.ticks.is_empty() {
+        quote! {}
+    } else {
+        let fields = opts.ticks.iter().map(|field| {
+            let field_type = match (&input.data, field.get_ident()) {
+                (Data::Struct(data), Some(ident)) => {
+                    find_field(&data.fields, &Member::Named(ident.clone()))
+                }
+                _ => None,
+            };
+
+            match field_type {
+                Some(ty) if option_inner(ty).is_some() => quote_spanned! { field.span() =>
+                    if let Some(ticks) = self.#field.as_mut() {
+                        *ticks = rounding.rescale(*ticks, from_ticks_per_beat, to_ticks_per_beat);
+                    }
+                },
+                _ => quote_spanned! { field.span() =>
+                    self.#field = rounding.rescale(self.#field, from_ticks_per_beat, to_ticks_per_beat);
+                },
+            }
+        });
+
+        quote! {
+            fn rescale_ticks(
+                &mut self,
+                from_ticks_per_beat: i32,
+                to_ticks_per_beat: i32,
+                rounding: #crate_path::timing::Rounding,
+            ) {
+                #(#fields)*
+            }
+        }
+    };
+
+    let is_generic = generics.params.iter().next().is_some();
+    let output = if !cfg!(feature = "serde") {
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let where_clause = match where_clause {
+            Some(clause) => quote! { #clause, Self: ::core::fmt::Debug + 'static },
+            None => quote! { where Self: ::core::fmt::Debug + 'static },
+        };
+
+        quote! {
+            impl #impl_generics #crate_path::Element for #ident #ty_generics #where_clause {
+                #wrapped_element_accessor
+                #rescale_ticks
+            }
+        }
+    } else if !is_generic {
+        if !opts.instance.is_empty() {
+            errors.push(
+                Error::custom("`instance` is only applicable to generic types.")
+                    .with_span(&opts.instance[0]),
+            );
+        }
+
+        let type_tag_opts = match &opts.name {
             Some(name_opt) => quote! { (name = #name_opt) },
             None => quote! {},
         };
 
-        quote! { #[typetag::serde #type_tag_opts] }
-    } else {
+        quote! {
+            #[typetag::serde #type_tag_opts]
+            impl #crate_path::Element for #ident {
+                #wrapped_element_accessor
+                #rescale_ticks
+            }
+        }
+    } else if opts.instance.is_empty() {
+        let message = format!(
+            "Generic `Element`s cannot be registered with `typetag` (required for serialization), \
+            since deserialization requires a concrete type. List each concrete instantiation to \
+            register via `#[element(instance = \"{}<..>\")]`.",
+            ident
+        );
+        errors.push(Error::custom(message).with_span(&generics.params));
+
         quote! {}
+    } else {
+        if opts.name.is_some() {
+            errors.push(option_error(
+                input,
+                "name",
+                "`name` cannot be used with generic types, as each `instance` is registered \
+                using its type as its name.",
+            ));
+        }
+
+        opts.instance
+            .iter()
+            .map(|instance| {
+                if !is_instance_of(instance, ident) {
+                    errors.push(
+                        Error::custom(format!(
+                            "`instance` must be an instantiation of `{}`.",
+                            ident
+                        ))
+                        .with_span(instance),
+                    );
+                }
+
+                let name = instance.to_token_stream().to_string().replace(' ', "");
+
+                quote_spanned! { instance.span() =>
+                    #[typetag::serde(name = #name)]
+                    impl #crate_path::Element for #instance {
+                        #wrapped_element_accessor
+                        #rescale_ticks
+                    }
+                }
+            })
+            .collect()
     };
 
-    let output = quote! {
-        #typetag_attr
-        impl #crate_path::Element for #ident {
-            #wrapped_element_accessor
+    errors.finish_with(output)
+}
+
+// Expands the `wrapped_element` shorthand (a field name/index) into an accessor expression.
+// Anything else is assumed to be a full accessor expression and returned as-is.
+fn wrapped_element_expr(
+    crate_path: &proc_macro2::TokenStream,
+    input: &DeriveInput,
+    accessor: &Expr,
+) -> darling::Result<proc_macro2::TokenStream> {
+    let member = match accessor {
+        Expr::Path(path) if path.qself.is_none() => match path.path.get_ident() {
+            Some(ident) => Member::Named(ident.clone()),
+            None => return Ok(accessor.to_token_stream()),
+        },
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(idx) => Member::Unnamed(syn::Index {
+                index: idx.base10_parse()?,
+                span: idx.span(),
+            }),
+            _ => return Ok(accessor.to_token_stream()),
+        },
+        _ => return Ok(accessor.to_token_stream()),
+    };
+
+    let field_type = match &input.data {
+        Data::Struct(data) => find_field(&data.fields, &member),
+        _ => None,
+    };
+
+    match (field_type, &member) {
+        (Some(ty), _) => {
+            let element = quote! { #crate_path::Element };
+            let field = quote_spanned! { accessor.span() => self.#member };
+
+            Ok(match option_inner(ty) {
+                Some(inner) if is_box(inner) => quote! { #field.as_deref() },
+                Some(_) => quote! { #field.as_ref().map(|e| e as &dyn #element) },
+                None if is_box(ty) => quote! { Some(&*#field) },
+                None => quote! { Some(&#field as &dyn #element) },
+            })
+        }
+        // Bare paths not matching a field name could be a valid expression (e.g. `None`)
+        (None, Member::Named(_)) => Ok(accessor.to_token_stream()),
+        (None, Member::Unnamed(idx)) => Err(Error::custom(format!(
+            "`wrapped_element` field index {} does not exist.",
+            idx.index
+        ))
+        .with_span(accessor)),
+    }
+}
+
+fn find_field<'a>(fields: &'a Fields, member: &Member) -> Option<&'a Type> {
+    fields
+        .iter()
+        .enumerate()
+        .find(|(idx, field)| match (member, &field.ident) {
+            (Member::Named(name), Some(ident)) => name == ident,
+            (Member::Unnamed(index), None) => index.index as usize == *idx,
+            _ => false,
+        })
+        .map(|(_, field)| &field.ty)
+}
+
+fn last_segment(ty: &Type) -> Option<&syn::PathSegment> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last(),
+        _ => None,
+    }
+}
+
+fn is_box(ty: &Type) -> bool {
+    last_segment(ty).is_some_and(|s| s.ident == "Box")
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let segment = last_segment(ty).filter(|s| s.ident == "Option")?;
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn is_instance_of(instance: &Type, ident: &syn::Ident) -> bool {
+    last_segment(instance).is_some_and(|s| &s.ident == ident)
+}
+
+// Creates an error spanning the given `#[element(..)]` option, for more precise error reporting.
+fn option_error(input: &DeriveInput, option: &str, message: &str) -> Error {
+    let option_path = input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("element"))
+        .find_map(|attr| {
+            let mut path = None;
+            let _ = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident(option) {
+                    path = Some(meta.path.clone());
+                }
+                // Consume any value, which is otherwise left unparsed
+                if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse::<Expr>()?;
+                }
+                Ok(())
+            });
+
+            path
+        });
+
+    match option_path {
+        Some(path) => Error::custom(message).with_span(&path),
+        None => Error::custom(message).with_span(&input.ident),
+    }
+}
+
+/// Generates a `redact-composer` `Renderer` from a plain function with the signature of
+/// `Renderer::render`. The function is kept as-is, and a unit struct implementing `Renderer` (by
+/// calling the function) is generated alongside it, with the same visibility.
+/// ```ignore
+/// #[renderer]
+/// fn play_chords(chords: SegmentRef<PlayChords>, ctx: CompositionContext) -> Result<Vec<Segment>> {
+///     // ...
+/// }
+///
+/// // Generates (roughly):
+/// #[derive(Debug, Clone, Copy, Default)]
+/// struct PlayChordsRenderer;
+///
+/// impl Renderer for PlayChordsRenderer {
+///     type Element = PlayChords;
+///     // ...
+/// }
+/// ```
+///
+/// The generated renderer is also registered, to be included in `RenderEngine::registered()` (and
+/// therefore `redact_composer::renderers()`).
+///
+/// Additional options (if needed) are specified via `#[renderer(params)]` which accepts any of the
+/// following params:
+/// * **`name: String`:** The name of the generated struct.
+///
+///   **Default:** the function's name in `PascalCase`, suffixed with `Renderer`.
+///
+/// * **`register: bool`:** Whether to register the generated renderer.
+///
+///   **Default:** `true`.
+///
+/// * **`requires(Element = TimingRelation, ..)`:** Declares context requirements of the renderer
+///   (e.g. `requires(Chord = Within, Key = During)`), relative to the segment being rendered. See
+///   `Renderer::requirements`.
+///
+///   **Default:** no requirements.
+///
+/// * **`waits_for(Element = TimingRelation, ..)`:** Same as `requires`, but declares soft
+///   requirements.
+///
+///   **Default:** no requirements.
+///
+/// * **`outputs(Element, ..)`:** Declares the `Element` types the renderer may produce (e.g.
+///   `outputs(Chord, PlayNote)`), including those wrapped by produced elements (e.g. `Melody` of
+///   `Part::instrument(Melody)`). Used for validation, and to render producers of declared
+///   requirements first -- see `Renderer::outputs`. An empty `outputs()` declares that the
+///   renderer produces nothing.
+///
+///   **Default:** no declared outputs.
+#[proc_macro_attribute]
+pub fn renderer(args: TokenStream, input: TokenStream) -> TokenStream {
+    renderer_attr_impl(quote! { ::redact_composer }, args, input)
+}
+
+/// See [`renderer`]. This version is used if only depending on `redact_composer_core` (i.e. for
+/// lib development).
+#[proc_macro_attribute]
+pub fn renderer_core(args: TokenStream, input: TokenStream) -> TokenStream {
+    renderer_attr_impl(quote! { ::redact_composer_core }, args, input)
+}
+
+fn renderer_attr_impl(
+    crate_path: proc_macro2::TokenStream,
+    args: TokenStream,
+    input: TokenStream,
+) -> TokenStream {
+    let item: ItemFn = parse_macro_input!(input);
+    let opts = NestedMeta::parse_meta_list(args.into())
+        .map_err(Error::from)
+        .and_then(|args| RendererOpts::from_list(&args));
+
+    match opts.and_then(|opts| renderer_impl(&crate_path, &opts, &item)) {
+        Ok(output) => quote! { #item #output }.into(),
+        // Keep the function itself, so errors are limited to the macro usage
+        Err(err) => {
+            let errors = err.write_errors();
+            quote! { #item #errors }.into()
+        }
+    }
+}
+
+fn renderer_impl(
+    crate_path: &proc_macro2::TokenStream,
+    opts: &RendererOpts,
+    item: &ItemFn,
+) -> darling::Result<proc_macro2::TokenStream> {
+    let ItemFn { vis, sig, .. } = item;
+    let fn_ident = &sig.ident;
+    let mut errors = Error::accumulator();
+
+    if !sig.generics.params.is_empty() {
+        errors.push(
+            Error::custom("`#[renderer]` functions cannot be generic.").with_span(&sig.generics),
+        );
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        errors.push(Error::custom("`#[renderer]` functions cannot be async.").with_span(asyncness));
+    }
+
+    let element = match sig.inputs.first() {
+        Some(FnArg::Typed(segment)) if sig.inputs.len() == 2 => {
+            errors.handle(segment_ref_element(&segment.ty).ok_or_else(|| {
+                Error::custom(
+                    "Expected a `SegmentRef<T>` parameter, where `T` is the `Element` to render.",
+                )
+                .with_span(&segment.ty)
+            }))
+        }
+        Some(FnArg::Receiver(receiver)) => {
+            errors.push(
+                Error::custom("`#[renderer]` functions cannot take `self`.").with_span(receiver),
+            );
+            None
+        }
+        _ => {
+            errors.push(
+                Error::custom(
+                    "`#[renderer]` functions must have the signature `fn(SegmentRef<T>, \
+                    CompositionContext) -> Result<Vec<Segment>>`.",
+                )
+                .with_span(&sig.ident),
+            );
+            None
         }
     };
 
-    output.into()
+    let output = element.map(|element| {
+        let name = opts.name.clone().unwrap_or_else(|| {
+            syn::Ident::new(
+                &format!("{}Renderer", pascal_case(&fn_ident.to_string())),
+                fn_ident.span(),
+            )
+        });
+        let doc = format!("`Renderer` generated from `{}`.", fn_ident);
+
+        let requirement = |(element, relation): &(syn::Path, syn::Ident)| {
+            quote! {
+                #crate_path::render::context::ContextRequirement::new::<#element>(
+                    #crate_path::render::context::TimingRelation::#relation
+                )
+            }
+        };
+        let required = opts.requires.0.iter().map(requirement);
+        let soft = opts.waits_for.0.iter().map(requirement);
+        let requirements = if opts.requires.0.is_empty() && opts.waits_for.0.is_empty() {
+            quote! {}
+        } else {
+            quote! {
+                fn requirements(
+                    &self,
+                ) -> ::std::vec::Vec<#crate_path::render::context::ContextRequirement> {
+                    ::std::vec![#(#required,)* #(#soft.soft(),)*]
+                }
+            }
+        };
+
+        let outputs = opts.outputs.as_ref().map(|outputs| {
+            let outputs = outputs.iter();
+            quote! {
+                fn outputs(
+                    &self,
+                ) -> ::std::option::Option<
+                    ::std::vec::Vec<#crate_path::render::validation::ElementType>,
+                > {
+                    ::std::option::Option::Some(::std::vec![
+                        #(#crate_path::render::validation::ElementType::of::<#outputs>(),)*
+                    ])
+                }
+            }
+        });
+
+        let registration = if opts.register.unwrap_or(true) {
+            quote! {
+                #crate_path::__private::inventory::submit! {
+                    #crate_path::render::RendererRegistration::of::<#name>()
+                }
+            }
+        } else {
+            quote! {}
+        };
+
+        quote! {
+            #[doc = #doc]
+            #[derive(Debug, Clone, Copy, Default)]
+            #vis struct #name;
+
+            impl #crate_path::render::Renderer for #name {
+                type Element = #element;
+
+                fn render(
+                    &self,
+                    segment: #crate_path::SegmentRef<Self::Element>,
+                    context: #crate_path::render::context::CompositionContext,
+                ) -> #crate_path::render::Result<::std::vec::Vec<#crate_path::Segment>> {
+                    #fn_ident(segment, context)
+                }
+
+                #requirements
+
+                #outputs
+            }
+
+            #registration
+        }
+    });
+
+    errors.finish_with(output.unwrap_or_default())
+}
+
+// Extracts `T` from a `SegmentRef<T>` (or `SegmentRef<'_, T>`) type.
+fn segment_ref_element(ty: &Type) -> Option<&Type> {
+    let segment = last_segment(ty).filter(|s| s.ident == "SegmentRef")?;
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn pascal_case(ident: &str) -> String {
+    ident
+        .trim_start_matches("r#")
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
 }
@@ -0,0 +1,309 @@
+use redact_composer_core::derive::Element;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Sends a [`MidiMessage::Controller`](midly::MidiMessage::Controller) to its [`Part`]'s channel
+/// at the start of its segment. The controller keeps its value until changed by another.
+///
+/// [`Part`]: redact_composer_core::elements::Part
+#[derive(Element, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ControlChange {
+    /// The controller number (`0..=127`).
+    pub controller: u8,
+    /// The controller value (`0..=127`).
+    pub value: u8,
+}
+
+impl ControlChange {
+    /// Modulation wheel controller number.
+    pub const MODULATION: u8 = 1;
+    /// Channel volume controller number.
+    pub const VOLUME: u8 = 7;
+    /// Pan controller number.
+    pub const PAN: u8 = 10;
+    /// Expression controller number.
+    pub const EXPRESSION: u8 = 11;
+    /// Sustain pedal controller number.
+    pub const SUSTAIN: u8 = 64;
+    /// Reverb send level controller number.
+    pub const REVERB: u8 = 91;
+    /// Chorus send level controller number.
+    pub const CHORUS: u8 = 93;
+
+    /// Sustain pedal down (`true`) or up (`false`).
+    pub fn sustain(down: bool) -> ControlChange {
+        ControlChange {
+            controller: Self::SUSTAIN,
+            value: if down { 127 } else { 0 },
+        }
+    }
+
+    /// Channel volume (`0..=127`).
+    pub fn volume(value: u8) -> ControlChange {
+        ControlChange {
+            controller: Self::VOLUME,
+            value,
+        }
+    }
+
+    /// Pan, from `0` (left) to `127` (right), with `64` as center.
+    pub fn pan(value: u8) -> ControlChange {
+        ControlChange {
+            controller: Self::PAN,
+            value,
+        }
+    }
+
+    /// Modulation wheel (`0..=127`).
+    pub fn modulation(value: u8) -> ControlChange {
+        ControlChange {
+            controller: Self::MODULATION,
+            value,
+        }
+    }
+
+    /// Expression (`0..=127`).
+    pub fn expression(value: u8) -> ControlChange {
+        ControlChange {
+            controller: Self::EXPRESSION,
+            value,
+        }
+    }
+}
+
+/// Mix settings for a [`Part`]'s channel, sent as [`ControlChange`]s
+/// ([`VOLUME`](ControlChange::VOLUME), [`PAN`](ControlChange::PAN),
+/// [`REVERB`](ControlChange::REVERB) and [`CHORUS`](ControlChange::CHORUS)) at the start of its
+/// segment. Settings left as `None` are not sent, keeping the synth's default.
+///
+/// Typically placed over the entire [`Part`], so it applies from the part's start.
+/// ```
+/// # use redact_composer_core::{elements::Part, IntoSegment};
+/// # use redact_composer_midi::control::Mix;
+/// # use redact_composer_midi::gm::elements::Instrument;
+/// let part = Part::instrument(Instrument::Cello).over(0..960);
+/// let mix = Mix::new().volume(90).pan(32).reverb(40).over(part.timing);
+/// ```
+///
+/// [`Part`]: redact_composer_core::elements::Part
+#[derive(Element, Debug, Copy, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Mix {
+    /// Channel volume (`0..=127`).
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub volume: Option<u8>,
+    /// Pan, from `0` (left) to `127` (right), with `64` as center.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub pan: Option<u8>,
+    /// Reverb send level (`0..=127`).
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub reverb: Option<u8>,
+    /// Chorus send level (`0..=127`).
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub chorus: Option<u8>,
+}
+
+impl Mix {
+    /// Creates a [`Mix`] without any settings.
+    pub fn new() -> Mix {
+        Mix::default()
+    }
+
+    /// Sets the channel volume (`0..=127`).
+    pub fn volume(mut self, volume: u8) -> Mix {
+        self.volume = Some(volume);
+
+        self
+    }
+
+    /// Sets the pan, from `0` (left) to `127` (right), with `64` as center.
+    pub fn pan(mut self, pan: u8) -> Mix {
+        self.pan = Some(pan);
+
+        self
+    }
+
+    /// Sets the reverb send level (`0..=127`).
+    pub fn reverb(mut self, reverb: u8) -> Mix {
+        self.reverb = Some(reverb);
+
+        self
+    }
+
+    /// Sets the chorus send level (`0..=127`).
+    pub fn chorus(mut self, chorus: u8) -> Mix {
+        self.chorus = Some(chorus);
+
+        self
+    }
+
+    /// The [`ControlChange`]s for this mix's settings. Values above `127` are clamped to `127`.
+    /// ```
+    /// # use redact_composer_midi::control::{ControlChange, Mix};
+    /// assert_eq!(
+    ///     Mix::new().volume(200).chorus(20).control_changes(),
+    ///     vec![
+    ///         ControlChange { controller: ControlChange::VOLUME, value: 127 },
+    ///         ControlChange { controller: ControlChange::CHORUS, value: 20 },
+    ///     ]
+    /// );
+    /// ```
+    pub fn control_changes(&self) -> Vec<ControlChange> {
+        [
+            (ControlChange::VOLUME, self.volume),
+            (ControlChange::PAN, self.pan),
+            (ControlChange::REVERB, self.reverb),
+            (ControlChange::CHORUS, self.chorus),
+        ]
+        .into_iter()
+        .filter_map(|(controller, value)| {
+            value.map(|value| ControlChange {
+                controller,
+                value: value.min(127),
+            })
+        })
+        .collect()
+    }
+}
+
+/// Sends a [`MidiMessage::PitchBend`](midly::MidiMessage::PitchBend) to its [`Part`]'s channel at
+/// the start of its segment. Ranges from `-8192` (full bend down) to `8191` (full bend up), with
+/// `0` as no bend.
+///
+/// [`Part`]: redact_composer_core::elements::Part
+#[derive(Element, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PitchBend(pub i16);
+
+/// Sends a [`MidiMessage::ChannelAftertouch`](midly::MidiMessage::ChannelAftertouch) (`0..=127`)
+/// to its [`Part`]'s channel at the start of its segment.
+///
+/// [`Part`]: redact_composer_core::elements::Part
+#[derive(Element, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChannelPressure(pub u8);
+
+/// Sends a [`MidiMessage::Aftertouch`](midly::MidiMessage::Aftertouch) for a single note to its
+/// [`Part`]'s channel at the start of its segment.
+///
+/// [`Part`]: redact_composer_core::elements::Part
+#[derive(Element, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PolyAftertouch {
+    /// The note (`0..=127`) the pressure applies to.
+    pub note: u8,
+    /// The pressure (`0..=127`).
+    pub pressure: u8,
+}
+
+/// Continuously changes an [`AutomationTarget`] over the duration of its segment, following a
+/// [`Curve`]. Sampled into events for its [`Part`]'s channel every
+/// [`resolution`](Automation::resolution) ticks (a sixteenth beat by default), with the first
+/// sample at the segment start and the last reaching the curve's end value.
+/// ```
+/// # use redact_composer_midi::control::{Automation, AutomationTarget, ControlChange, Curve};
+/// // Fade in the volume
+/// let fade_in = Automation::new(
+///     AutomationTarget::Control(ControlChange::VOLUME),
+///     Curve::Linear { from: 0.0, to: 100.0 },
+/// );
+/// ```
+///
+/// [`Part`]: redact_composer_core::elements::Part
+#[derive(Element, Debug, Clone, PartialEq)]
+#[element(ticks(resolution))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Automation {
+    /// What is being automated.
+    pub target: AutomationTarget,
+    /// The values over time, in the target's range.
+    pub curve: Curve,
+    /// Ticks between samples. Defaults to a sixteenth beat if `None`.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub resolution: Option<i32>,
+}
+
+impl Automation {
+    /// Creates an [`Automation`] with the default resolution.
+    pub fn new(target: AutomationTarget, curve: Curve) -> Automation {
+        Automation {
+            target,
+            curve,
+            resolution: None,
+        }
+    }
+
+    /// Sets the ticks between samples.
+    pub fn with_resolution(mut self, ticks: i32) -> Automation {
+        self.resolution = Some(ticks);
+
+        self
+    }
+}
+
+/// The value changed by an [`Automation`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AutomationTarget {
+    /// A controller number. Values range `0..=127` (see [`ControlChange`]).
+    Control(u8),
+    /// Values range `-8192..=8191` (see [`PitchBend`]).
+    PitchBend,
+    /// Values range `0..=127` (see [`ChannelPressure`]).
+    ChannelPressure,
+    /// Aftertouch for a single note. Values range `0..=127` (see [`PolyAftertouch`]).
+    PolyAftertouch(u8),
+}
+
+/// Describes how an [`Automation`]'s value changes over its duration.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Curve {
+    /// Straight line from one value to another.
+    Linear {
+        /// Starting value.
+        from: f32,
+        /// Ending value.
+        to: f32,
+    },
+    /// Straight lines between `(position, value)` points, with `position` as a fraction
+    /// (`0.0..=1.0`) of the duration. Holds the first/last value before/after the first/last point.
+    Points(Vec<(f32, f32)>),
+}
+
+impl Curve {
+    /// The curve's value at a given `position` (`0.0..=1.0`) of the duration.
+    /// ```
+    /// # use redact_composer_midi::control::Curve;
+    /// let curve = Curve::Points(vec![(0.0, 0.0), (0.5, 100.0), (1.0, 50.0)]);
+    ///
+    /// assert_eq!(curve.value_at(0.25), 50.0);
+    /// assert_eq!(curve.value_at(0.75), 75.0);
+    /// ```
+    pub fn value_at(&self, position: f32) -> f32 {
+        let position = position.clamp(0.0, 1.0);
+
+        match self {
+            Curve::Linear { from, to } => from + (to - from) * position,
+            Curve::Points(points) => {
+                let next = points.iter().position(|(pos, _)| *pos >= position);
+
+                match next {
+                    None => points.last().map(|(_, value)| *value).unwrap_or_default(),
+                    Some(0) => points[0].1,
+                    Some(idx) => {
+                        let ((start, from), (end, to)) = (points[idx - 1], points[idx]);
+
+                        if end <= start {
+                            to
+                        } else {
+                            from + (to - from) * (position - start) / (end - start)
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
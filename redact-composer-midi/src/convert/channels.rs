@@ -0,0 +1,339 @@
+use std::collections::{HashMap, HashSet};
+
+use log::debug;
+use midly::{MidiMessage, TrackEvent, TrackEventKind};
+use redact_composer_core::{
+    elements::{Part, PlayNote},
+    render::{
+        tree::{Node, Tree},
+        RenderSegment,
+    },
+    timing::Timing,
+    PartType,
+};
+
+use super::{MidiConverter, MidiConverterOptions};
+use crate::elements::Program;
+
+/// Strategy for [`Part`]s which cannot be assigned an exclusive channel, due to too many concurrent
+/// [`Part`]s (15 instrument channels and 1 percussion channel per port).
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ChannelStrategy {
+    /// The [`Part`] is dropped from the output.
+    #[default]
+    Drop,
+    /// Additional ports (each with their own 16 channels) are used as needed, up to 128 ports.
+    /// Each track addresses its port via a [`MetaMessage::MidiPort`](midly::MetaMessage::MidiPort)
+    /// event.
+    ///
+    /// Since a single track can only address one port, this falls back to
+    /// [`ChannelStrategy::Drop`] for [`SmfFormat::SingleTrack`](super::SmfFormat::SingleTrack)
+    /// output (see [`ConversionReport::channel_strategy`](super::ConversionReport::channel_strategy)).
+    Ports,
+    /// The [`Part`] is merged onto the channel of a concurrent [`Part`] (of the same [`PartType`])
+    /// using the same [`Program`]s.
+    MergePrograms,
+    /// The [`Part`] shares the channel of a concurrent [`Part`] (of the same [`PartType`]) whose
+    /// notes don't overlap its own, with [`MidiMessage::ProgramChange`]s between notes whenever
+    /// the sharing [`Part`]s' programs differ.
+    Steal,
+}
+
+/// A MIDI channel of a port.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct MidiChannel {
+    /// The port (`0..=127`).
+    pub port: u8,
+    /// The channel (`0..=15`).
+    pub channel: u8,
+}
+
+/// How a [`Part`]'s channel was decided during MIDI conversion.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ChannelDecision {
+    /// Exclusively assigned a channel (at least until stolen, see [`ChannelDecision::Shared`]).
+    Exclusive(MidiChannel),
+    /// Merged onto another [`Part`]'s channel via [`ChannelStrategy::MergePrograms`].
+    Merged {
+        /// The shared channel.
+        channel: MidiChannel,
+        /// Tree node index of the [`Part`] whose channel was merged onto.
+        with: usize,
+    },
+    /// Shares another [`Part`]'s channel via [`ChannelStrategy::Steal`].
+    Shared {
+        /// The shared channel.
+        channel: MidiChannel,
+        /// Tree node index of the [`Part`] whose channel is shared.
+        with: usize,
+    },
+    /// Not assigned a channel, and dropped from the output.
+    Dropped,
+}
+
+impl ChannelDecision {
+    /// The assigned channel, or `None` if [`ChannelDecision::Dropped`].
+    pub fn channel(&self) -> Option<MidiChannel> {
+        match self {
+            ChannelDecision::Exclusive(channel)
+            | ChannelDecision::Merged { channel, .. }
+            | ChannelDecision::Shared { channel, .. } => Some(*channel),
+            ChannelDecision::Dropped => None,
+        }
+    }
+}
+
+// A channel in use by one or more parts until `end`.
+struct ActiveChannel {
+    channel: MidiChannel,
+    part_type: PartType,
+    end: i32,
+    // Index (into the parts being assigned) of the part first assigned this channel
+    owner: usize,
+    // Owner's programs (for ChannelStrategy::MergePrograms)
+    programs: Vec<u8>,
+    // Notes of all parts on this channel, sorted by start (for ChannelStrategy::Steal)
+    notes: Vec<Timing>,
+}
+
+// Available channels of a port.
+struct PortChannels {
+    instrument: HashSet<u8>,
+    percussion: HashSet<u8>,
+}
+
+impl PortChannels {
+    fn pool(&mut self, part_type: PartType) -> &mut HashSet<u8> {
+        match part_type {
+            PartType::Instrument => &mut self.instrument,
+            PartType::Percussion => &mut self.percussion,
+        }
+    }
+}
+
+impl MidiConverter {
+    pub(super) fn assign_channels(
+        parts: &[&Node<RenderSegment>],
+        tree: &Tree<RenderSegment>,
+        options: &MidiConverterOptions,
+        strategy: ChannelStrategy,
+    ) -> Vec<ChannelDecision> {
+        let new_port = || PortChannels {
+            instrument: options.instrument_channels(),
+            percussion: options.drum_channels.clone(),
+        };
+        let max_ports = if strategy == ChannelStrategy::Ports {
+            128
+        } else {
+            1
+        };
+        let mut ports = vec![new_port()];
+        let mut decisions = vec![ChannelDecision::Dropped; parts.len()];
+        let mut active: Vec<ActiveChannel> = vec![];
+
+        let mut sorted_parts = (0..parts.len()).collect::<Vec<_>>();
+        sorted_parts.sort_by_key(|idx| parts[*idx].value.segment.timing.start);
+
+        for idx in sorted_parts {
+            let part = parts[idx];
+            let timing = part.value.segment.timing;
+            let part_type = *part.value.segment.element_as::<Part>().unwrap().part_type();
+
+            // release active channels for reuse if they're past the next part's start time
+            active.retain(|a| {
+                if a.end <= timing.start {
+                    ports[a.channel.port as usize]
+                        .pool(a.part_type)
+                        .insert(a.channel.channel);
+                    false
+                } else {
+                    true
+                }
+            });
+
+            // Assign a channel from available channels, on the lowest port possible
+            let mut available_channel = None;
+            for port in 0..max_ports {
+                if port == ports.len() {
+                    ports.push(new_port());
+                }
+                let pool = ports[port].pool(part_type);
+                if let Some(channel) = pool.iter().min().copied() {
+                    pool.remove(&channel);
+                    available_channel = Some(MidiChannel {
+                        port: port as u8,
+                        channel,
+                    });
+                    break;
+                }
+            }
+
+            if let Some(channel) = available_channel {
+                decisions[idx] = ChannelDecision::Exclusive(channel);
+                active.push(ActiveChannel {
+                    channel,
+                    part_type,
+                    end: timing.end,
+                    owner: idx,
+                    programs: if strategy == ChannelStrategy::MergePrograms {
+                        Self::part_programs(part, tree)
+                    } else {
+                        vec![]
+                    },
+                    notes: if strategy == ChannelStrategy::Steal {
+                        Self::part_notes(part, tree)
+                    } else {
+                        vec![]
+                    },
+                });
+
+                continue;
+            }
+
+            let (notes, programs) = match strategy {
+                ChannelStrategy::MergePrograms => (vec![], Self::part_programs(part, tree)),
+                ChannelStrategy::Steal => (Self::part_notes(part, tree), vec![]),
+                ChannelStrategy::Drop | ChannelStrategy::Ports => (vec![], vec![]),
+            };
+            let shared = match strategy {
+                ChannelStrategy::MergePrograms => active
+                    .iter_mut()
+                    .find(|a| a.part_type == part_type && a.programs == programs),
+                ChannelStrategy::Steal => active
+                    .iter_mut()
+                    .find(|a| a.part_type == part_type && !Self::overlaps(&a.notes, &notes)),
+                ChannelStrategy::Drop | ChannelStrategy::Ports => None,
+            };
+
+            if let Some(active_channel) = shared {
+                let (channel, with) = (active_channel.channel, parts[active_channel.owner].idx);
+                decisions[idx] = if strategy == ChannelStrategy::MergePrograms {
+                    ChannelDecision::Merged { channel, with }
+                } else {
+                    ChannelDecision::Shared { channel, with }
+                };
+                active_channel.end = active_channel.end.max(timing.end);
+                active_channel.notes.extend(notes);
+                active_channel.notes.sort_by_key(|t| t.start);
+            } else {
+                debug!(
+                    "Could not assign channel for {:?} (idx: {:?}). \
+                All available channels are occupied during this time.",
+                    part.value.segment, part.idx
+                );
+            }
+        }
+
+        decisions
+    }
+
+    // ProgramChange events for parts sharing channels via `ChannelStrategy::Steal`, whenever the
+    // program changes from one note to the next on a shared channel. Keyed by part node index.
+    pub(super) fn shared_program_events(
+        parts: &[&Node<RenderSegment>],
+        decisions: &[ChannelDecision],
+        tree: &Tree<RenderSegment>,
+    ) -> HashMap<usize, Vec<(i32, TrackEvent<'static>)>> {
+        // Sharing groups keyed by owning part node index
+        let mut groups: HashMap<usize, Vec<(&Node<RenderSegment>, MidiChannel)>> = HashMap::new();
+        for (part, decision) in parts.iter().zip(decisions) {
+            if let ChannelDecision::Shared { channel, with } = decision {
+                groups.entry(*with).or_default().push((*part, *channel));
+            }
+        }
+        for (owner, members) in groups.iter_mut() {
+            if let Some(part) = parts.iter().find(|p| p.idx == *owner) {
+                members.push((*part, members[0].1));
+            }
+        }
+
+        let mut events: HashMap<usize, Vec<(i32, TrackEvent<'static>)>> = HashMap::new();
+        for members in groups.into_values() {
+            let mut notes = members
+                .iter()
+                .flat_map(|(part, channel)| {
+                    let programs = tree
+                        .node_iter(part)
+                        .filter_map(|n| {
+                            n.value
+                                .segment
+                                .element_as::<Program>()
+                                .map(|p| (n.value.segment.timing, p.0))
+                        })
+                        .collect::<Vec<_>>();
+
+                    Self::part_notes(part, tree).into_iter().map(move |note| {
+                        let program = programs
+                            .iter()
+                            .rev()
+                            .find(|(timing, _)| timing.contains(&note.start))
+                            .map(|(_, program)| *program);
+
+                        (note.start, part.idx, *channel, program)
+                    })
+                })
+                .collect::<Vec<_>>();
+            notes.sort_by_key(|(start, ..)| *start);
+
+            let mut current_program = None;
+            for (start, part_idx, channel, program) in notes {
+                if program.is_some() && program != current_program {
+                    current_program = program;
+                    events.entry(part_idx).or_default().push((
+                        start,
+                        TrackEvent {
+                            delta: 0.into(),
+                            kind: TrackEventKind::Midi {
+                                channel: channel.channel.into(),
+                                message: MidiMessage::ProgramChange {
+                                    program: program.unwrap_or_default().into(),
+                                },
+                            },
+                        },
+                    ));
+                }
+            }
+        }
+
+        events
+    }
+
+    fn part_programs(part: &Node<RenderSegment>, tree: &Tree<RenderSegment>) -> Vec<u8> {
+        let mut programs = tree
+            .node_iter(part)
+            .filter_map(|n| n.value.segment.element_as::<Program>().map(|p| p.0))
+            .collect::<Vec<_>>();
+        programs.sort();
+        programs.dedup();
+
+        programs
+    }
+
+    // Note timings of a part, sorted by start.
+    fn part_notes(part: &Node<RenderSegment>, tree: &Tree<RenderSegment>) -> Vec<Timing> {
+        let mut notes = tree
+            .node_iter(part)
+            .filter(|n| n.value.segment.element_as::<PlayNote>().is_some())
+            .map(|n| n.value.segment.timing)
+            .collect::<Vec<_>>();
+        notes.sort_by_key(|t| t.start);
+
+        notes
+    }
+
+    // Whether any timings of two lists (each sorted by start) overlap.
+    fn overlaps(a: &[Timing], b: &[Timing]) -> bool {
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if a[i].end <= b[j].start {
+                i += 1;
+            } else if b[j].end <= a[i].start {
+                j += 1;
+            } else {
+                return true;
+            }
+        }
+
+        false
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::Path;
+
+use log::{info, warn};
+use midly::{MetaMessage, MidiMessage, Smf, Timing::Metrical, TrackEventKind};
+use redact_composer_core::derive::Element;
+use redact_composer_core::elements::{Part, PlayNote};
+use redact_composer_core::render::{tree::Tree, RenderSegment};
+use redact_composer_core::timing::elements::Tempo;
+use redact_composer_core::{Composition, CompositionOptions, Segment};
+use thiserror::Error;
+
+#[cfg(feature = "musical")]
+use redact_composer_musical::{Key, NoteName, Scale, TimeSignature};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::MidiConverter;
+use crate::elements::Program;
+
+/// The root element of a [`Composition`] imported from MIDI via [`MidiConverter::import`].
+#[derive(Element, Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ImportedMidi;
+
+/// Identifies the MIDI track/channel an imported [`Part`] originated from. Wrapped by each
+/// imported [`Part`].
+#[derive(Element, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MidiTrack {
+    /// Index of the track within the MIDI file.
+    pub track: usize,
+    /// The channel (`0..=15`) of the track's events.
+    pub channel: u8,
+    /// The track name, if named via a [`MetaMessage::TrackName`] event.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub name: Option<String>,
+}
+
+/// Error type for MIDI import.
+#[derive(Debug, Error)]
+pub enum ImportError {
+    /// The MIDI data could not be parsed.
+    #[error("Unable to parse MIDI data: {0}")]
+    Parse(#[from] midly::Error),
+    /// The MIDI file could not be read.
+    #[error("Unable to read MIDI file: {0}")]
+    Io(#[from] std::io::Error),
+    /// The MIDI file uses timecode (SMPTE) timing, which has no notion of beats.
+    #[error("Timecode based MIDI timing is not supported, only metrical (ticks per beat).")]
+    UnsupportedTiming,
+    /// The MIDI file's header declares a resolution of zero ticks per beat.
+    #[error("MIDI timing resolution must be at least one tick per beat.")]
+    ZeroTicksPerBeat,
+}
+
+// Events of a single channel within a track.
+#[derive(Default)]
+struct ChannelEvents {
+    programs: Vec<(i32, u8)>,
+    notes: Vec<(i32, i32, PlayNote)>,
+    open_notes: HashMap<u8, VecDeque<(i32, u8)>>,
+    end: i32,
+}
+
+impl MidiConverter {
+    /// Converts a MIDI file (parsed with [`midly`]) into a [`Composition`] -- the reverse of
+    /// [`MidiConverter::convert`].
+    ///
+    /// The imported tree's root is [`ImportedMidi`] spanning the entire file, with the following
+    /// children:
+    /// * [`Tempo`]
+    /// > One per [`MetaMessage::Tempo`] event, lasting until the next.
+    /// * `TimeSignature`, `Key` (`feature = musical`)
+    /// > One per [`MetaMessage::TimeSignature`]/[`MetaMessage::KeySignature`] event respectively,
+    /// > lasting until the next.
+    /// * [`Part`]
+    /// > One per track/channel combination with channel events, wrapping a [`MidiTrack`].
+    /// > Channel 9 is imported as [`Part::percussion`], others as [`Part::instrument`]. Each
+    /// > contains [`Program`] segments (lasting until the next program change) and [`PlayNote`]
+    /// > segments.
+    ///
+    /// All imported segments are marked as rendered. The result can be used as context for
+    /// renderers (e.g. `context.find::<PlayNote>().within::<ImportedMidi>()`) during composition via
+    /// [`Composer::compose_with_context`](redact_composer_core::Composer::compose_with_context).
+    pub fn import(smf: &Smf) -> Result<Composition, ImportError> {
+        info!("Importing MIDI.");
+        let ticks_per_beat = match smf.header.timing {
+            Metrical(ticks_per_beat) if ticks_per_beat > 0 => i32::from(ticks_per_beat.as_int()),
+            Metrical(_) => return Err(ImportError::ZeroTicksPerBeat),
+            _ => return Err(ImportError::UnsupportedTiming),
+        };
+
+        let mut tempos = BTreeMap::new();
+        #[cfg(feature = "musical")]
+        let (mut time_signatures, mut keys) = (BTreeMap::new(), BTreeMap::new());
+        let mut parts = vec![];
+        let mut end = 0;
+
+        for (track_idx, track) in smf.tracks.iter().enumerate() {
+            let mut tick = 0;
+            let mut name = None;
+            let mut channels: BTreeMap<u8, ChannelEvents> = BTreeMap::new();
+
+            for event in track {
+                tick += event.delta.as_int() as i32;
+
+                match event.kind {
+                    TrackEventKind::Meta(MetaMessage::Tempo(microseconds_per_beat)) => {
+                        let bpm = 60_000_000.0 / f64::from(microseconds_per_beat.as_int());
+                        tempos.insert(tick, Tempo::from_bpm(bpm.round() as u32));
+                    }
+                    #[cfg(feature = "musical")]
+                    TrackEventKind::Meta(MetaMessage::TimeSignature(
+                        numerator,
+                        denominator,
+                        ..,
+                    )) => {
+                        // The denominator is a power of 2
+                        let time_signature = 2_i32
+                            .checked_pow(u32::from(denominator))
+                            .and_then(|d| (ticks_per_beat * 4).checked_div(d))
+                            .map(|beat_length| TimeSignature {
+                                beats_per_bar: i32::from(numerator),
+                                beat_length,
+                            })
+                            .filter(TimeSignature::is_valid);
+
+                        if let Some(time_signature) = time_signature {
+                            time_signatures.insert(tick, time_signature);
+                        } else {
+                            warn!(
+                                "Skipping unsupported time signature {:?}/2^{:?} on track {:?} (tick {:?}).",
+                                numerator, denominator, track_idx, tick
+                            );
+                        }
+                    }
+                    #[cfg(feature = "musical")]
+                    TrackEventKind::Meta(MetaMessage::KeySignature(sharps, minor)) => {
+                        if let Some(key) = Self::key(sharps, minor) {
+                            keys.insert(tick, key);
+                        }
+                    }
+                    TrackEventKind::Meta(MetaMessage::TrackName(track_name)) => {
+                        name = Some(String::from_utf8_lossy(track_name).into_owned());
+                    }
+                    TrackEventKind::Midi { channel, message } => {
+                        let events = channels.entry(channel.as_int()).or_default();
+                        events.end = tick;
+
+                        match message {
+                            MidiMessage::ProgramChange { program } => {
+                                events.programs.push((tick, program.as_int()))
+                            }
+                            MidiMessage::NoteOn { key, vel } if vel > 0 => events
+                                .open_notes
+                                .entry(key.as_int())
+                                .or_default()
+                                .push_back((tick, vel.as_int())),
+                            MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                                if let Some((start, velocity)) = events
+                                    .open_notes
+                                    .get_mut(&key.as_int())
+                                    .and_then(VecDeque::pop_front)
+                                {
+                                    let note = key.as_int();
+                                    events
+                                        .notes
+                                        .push((start, tick, PlayNote { note, velocity }));
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            end = end.max(tick);
+
+            for (channel, mut events) in channels {
+                // Notes without a NoteOff are ended at the end of their track
+                let unterminated = events.open_notes.drain().flat_map(|(note, starts)| {
+                    starts
+                        .into_iter()
+                        .map(move |(start, velocity)| (start, tick, PlayNote { note, velocity }))
+                });
+                let terminated_count = events.notes.len();
+                events.notes.extend(unterminated);
+                if events.notes.len() > terminated_count {
+                    warn!(
+                        "{:?} note(s) on track {:?} (channel {:?}) were never ended, ending them at the track end.",
+                        events.notes.len() - terminated_count, track_idx, channel
+                    );
+                    events.end = tick;
+                }
+                events
+                    .notes
+                    .sort_by_key(|(start, end, n)| (*start, *end, n.note));
+
+                let midi_track = MidiTrack {
+                    track: track_idx,
+                    channel,
+                    name: name.clone(),
+                };
+                parts.push((midi_track, events));
+            }
+        }
+
+        let mut tree = Tree::new();
+        let mut insert = |segment: Segment, parent: Option<usize>| {
+            tree.insert(
+                RenderSegment {
+                    segment,
+                    seed: 0,
+                    rendered: true,
+                    error: None,
+                    context: false,
+                },
+                parent,
+            )
+        };
+        let root = insert(Segment::new(ImportedMidi, 0..end), None);
+
+        for (start, end, tempo) in Self::sections(tempos, end) {
+            insert(Segment::new(tempo, start..end), Some(root));
+        }
+        #[cfg(feature = "musical")]
+        {
+            for (start, end, time_signature) in Self::sections(time_signatures, end) {
+                insert(Segment::new(time_signature, start..end), Some(root));
+            }
+            for (start, end, key) in Self::sections(keys, end) {
+                insert(Segment::new(key, start..end), Some(root));
+            }
+        }
+
+        for (midi_track, events) in parts {
+            let part_start = events
+                .programs
+                .iter()
+                .map(|(start, _)| *start)
+                .chain(events.notes.iter().map(|(start, _, _)| *start))
+                .min()
+                .unwrap_or(events.end);
+            let part_end = events
+                .notes
+                .iter()
+                .map(|(_, end, _)| *end)
+                .max()
+                .unwrap_or(events.end)
+                .max(events.end);
+            let part = if midi_track.channel == 9 {
+                Part::percussion(midi_track)
+            } else {
+                Part::instrument(midi_track)
+            };
+            let part_idx = insert(Segment::new(part, part_start..part_end), Some(root));
+
+            let programs = events.programs.into_iter().map(|(t, p)| (t, Program(p)));
+            for (start, end, program) in Self::sections(programs.collect(), part_end) {
+                insert(Segment::new(program, start..end), Some(part_idx));
+            }
+            for (start, end, note) in events.notes {
+                insert(Segment::new(note, start..end), Some(part_idx));
+            }
+        }
+
+        info!("MIDI import complete. Total segments: {:?}.", tree.len());
+
+        Ok(Composition::new(
+            CompositionOptions { ticks_per_beat },
+            tree,
+        ))
+    }
+
+    /// Parses and imports MIDI file data. See [`MidiConverter::import`].
+    pub fn import_bytes(bytes: &[u8]) -> Result<Composition, ImportError> {
+        Self::import(&Smf::parse(bytes)?)
+    }
+
+    /// Reads and imports a MIDI file. See [`MidiConverter::import`].
+    pub fn import_file(path: impl AsRef<Path>) -> Result<Composition, ImportError> {
+        Self::import_bytes(&std::fs::read(path)?)
+    }
+
+    // Converts starting ticks of values into (start, end, value) sections, each lasting until the
+    // next (or `end`). Later values override earlier ones starting at the same tick.
+    fn sections<T>(values: BTreeMap<i32, T>, end: i32) -> Vec<(i32, i32, T)> {
+        let starts = values
+            .keys()
+            .skip(1)
+            .copied()
+            .chain([end])
+            .collect::<Vec<_>>();
+
+        values
+            .into_iter()
+            .zip(starts)
+            .map(|((start, value), next)| (start, next.max(start), value))
+            .collect()
+    }
+
+    #[cfg(feature = "musical")]
+    fn key(sharps: i8, minor: bool) -> Option<Key> {
+        use NoteName::*;
+        const MAJOR: [NoteName; 15] = [Cb, Gb, Db, Ab, Eb, Bb, F, C, G, D, A, E, B, Fs, Cs];
+        const MINOR: [NoteName; 15] = [Ab, Eb, Bb, F, C, G, D, A, E, B, Fs, Cs, Gs, Ds, As];
+
+        let idx = usize::try_from(i16::from(sharps) + 7).ok()?;
+        if minor {
+            MINOR
+                .get(idx)
+                .map(|tonic| Key::from((*tonic, Scale::NaturalMinor)))
+        } else {
+            MAJOR
+                .get(idx)
+                .map(|tonic| Key::from((*tonic, Scale::Major)))
+        }
+    }
+}
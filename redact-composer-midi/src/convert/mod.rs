@@ -1,87 +1,270 @@
-use crate::elements::Program;
-use log::{debug, info, log_enabled, warn, Level};
-use midly::num::u4;
+use crate::control::AutomationTarget;
+use crate::elements::{
+    Automation, ChannelPressure, ControlChange, Lyric, Marker, Mix, PitchBend, PolyAftertouch,
+    Program,
+};
+use log::{info, log_enabled, warn, Level};
+use midly::num::{u4, u7};
 use midly::{
-    Format::Parallel, Header, MetaMessage, MidiMessage, Smf, Timing::Metrical, TrackEvent,
+    Format, Header, MetaMessage, MidiMessage, Smf, Timing::Metrical, Timing::Timecode, TrackEvent,
     TrackEventKind,
 };
-use redact_composer_core::timing::Timing;
 use redact_composer_core::{
     elements::{Part, PlayNote},
     render::{
         tree::{Node, Tree},
         RenderSegment,
     },
-    timing::elements::Tempo,
-    Composition, PartType, SegmentRef,
+    timing::{elements::Tempo, TempoMap, Timing},
+    Composition, Segment,
+};
+use std::collections::HashMap;
+use std::io::Write;
+
+#[cfg(feature = "musical")]
+use redact_composer_musical::{
+    Degree, Key, PitchClass, PitchClassCollection, Scale, TimeSignature,
 };
-use std::{cmp::Ordering, collections::HashSet};
+#[cfg(feature = "musical")]
+use std::collections::BTreeMap;
 
 // Doc imports
 #[allow(unused_imports)]
-use redact_composer_core::Segment;
+use redact_composer_core::{timing::elements::TempoRamp, PartType};
+
+mod channels;
+mod import;
+mod notes;
+mod options;
+mod report;
+pub use channels::{ChannelDecision, ChannelStrategy, MidiChannel};
+pub use import::{ImportError, ImportedMidi, MidiTrack};
+pub use notes::NoteOverlap;
+pub use options::{
+    EventOrdering, MidiConverterOptions, NoteOffStyle, NoteOverlapPolicy, SmfFormat,
+};
+pub use report::ConversionReport;
 
 #[cfg(test)]
 mod test;
 
-/// Converter for [`Composition`] -> MIDI format.
+// The largest tempo value (microseconds per beat) a MIDI tempo event can hold (24 bits).
+const MAX_MICROSECONDS_PER_BEAT: u32 = 0xFF_FFFF;
+
+/// Converter for [`Composition`] -> MIDI format (and vice versa, via [`MidiConverter::import`]).
 ///
 /// The following [`Composition`] tree elements are relevant during MIDI conversion:
 /// * [`Part`]
 /// > Represents a MIDI channel. Descendent channel-related elements of a [`Part`] are all associated with this channel.
-/// > [`PartType::Percussion`] have a reserved channel (9), and [`PartType::Instrument`] are assigned any other channel
-/// > in `0..=15`.
+/// > [`PartType::Percussion`] have reserved channels ([`MidiConverterOptions::drum_channels`], 9 by default), and
+/// > [`PartType::Instrument`] are assigned any other channel in `0..=15`. Each [`Part`] is written to its own track
+/// > (unless [`MidiConverterOptions::format`] is [`SmfFormat::SingleTrack`]). [`Part`]s which cannot be assigned a
+/// > channel (due to too many concurrent [`Part`]s) are handled according to
+/// > [`MidiConverterOptions::channel_strategy`].
 /// * [`Program`]
 /// > Sends a [`MidiMessage::ProgramChange`] to its ancestor [`Part`]'s assigned channel. There can be multiple within a
 /// > single [`Part`]. For [`Program`]s' with overlapping [`Timing`]s, the lower tree-depth will
 /// > override the higher.
 /// * [`PlayNote`]
 /// > Sends a [`MidiMessage::NoteOn`]/[`MidiMessage::NoteOff`] pair to its [`Part`]'s channel, at the start/end of its
-/// > [`Segment`]'s [`Timing`] respectively.
+/// > [`Segment`]'s [`Timing`] respectively (see [`MidiConverterOptions::note_off_style`]). Overlapping notes of the
+/// > same pitch on a channel are resolved according to [`MidiConverterOptions::note_overlap_policy`]. Notes without
+/// > duration are skipped (see [`ConversionReport::skipped_notes`]).
+/// * [`ControlChange`], [`PitchBend`], [`ChannelPressure`], [`PolyAftertouch`]
+/// > Sends a [`MidiMessage::Controller`], [`MidiMessage::PitchBend`], [`MidiMessage::ChannelAftertouch`] or
+/// > [`MidiMessage::Aftertouch`] respectively to its [`Part`]'s channel, at the start of its [`Segment`]'s [`Timing`].
+/// > Values above `127` are clamped to `127`.
+/// * [`Mix`]
+/// > Sends [`MidiMessage::Controller`]s for its volume, pan, reverb and chorus settings (CC 7, 10, 91 and 93) to its
+/// > [`Part`]'s channel, at the start of its [`Segment`]'s [`Timing`].
+/// * [`Automation`]
+/// > Sends a series of messages to its [`Part`]'s channel (according to its
+/// > [`AutomationTarget`]), sampled from its [`Curve`](crate::control::Curve) over the duration of
+/// > its [`Segment`].
 /// * [`Tempo`]
 /// > Sends a [`MetaMessage::Tempo`] event, changing the tempo for the duration of its [`Segment`]. Can be located
 /// > anywhere in a [`Composition`] tree (not constrained to individual [`Part`]s). For [`Tempo`]s with overlapping
-/// > [`Timing`]s, the lower tree-depth will override the higher.
+/// > [`Timing`]s, the lower tree-depth will override the higher. Elsewhere, the tempo is
+/// > [`MidiConverterOptions::default_tempo`].
+/// * [`TempoRamp`]
+/// > Gradually changes the tempo over the duration of its [`Segment`], approximated by a [`MetaMessage::Tempo`] event
+/// > every quarter beat. Overlaps with [`Tempo`]s are resolved the same as between [`Tempo`]s.
+/// * `TimeSignature`, `Key` (`feature = musical`)
+/// > Sends a [`MetaMessage::TimeSignature`]/[`MetaMessage::KeySignature`] event respectively at the start of its
+/// > [`Segment`]. Can be located anywhere in a [`Composition`] tree. For those starting at the same time, the lower
+/// > tree-depth will override the higher. Time signatures whose beat is not a power of two fraction of a whole note
+/// > are skipped.
+/// * [`Marker`], [`Lyric`]
+/// > Sends a [`MetaMessage::Marker`]/[`MetaMessage::Lyric`] event respectively at the start of its [`Segment`]. Those
+/// > within a [`Part`] are written to that [`Part`]'s track, others to the first track.
+///
+/// Additionally, a [`Part`] [`Segment`]'s [`name`](Segment::name) is written as its track's
+/// [`MetaMessage::TrackName`].
+///
+/// [`Part`]s inserted as context (see [`RenderSegment::context`]) are skipped, so only rendered
+/// [`Part`]s are output. Other context elements (such as [`Tempo`]s) are still converted, as the
+/// rendered output is timed according to them.
 #[allow(missing_debug_implementations)]
 pub struct MidiConverter;
 
 impl MidiConverter {
     /// Converts [`Composition`]s into MIDI format using the [`midly`] crate.
     pub fn convert(composition: &Composition) -> Smf {
+        Self::convert_with_options(composition, &MidiConverterOptions::default()).0
+    }
+
+    /// Converts [`Composition`]s into MIDI format using the [`midly`] crate with custom
+    /// [`MidiConverterOptions`], also returning a [`ConversionReport`] of decisions made along the
+    /// way.
+    /// ```
+    /// # use redact_composer_core::Composition;
+    /// # use redact_composer_midi::convert::{ChannelStrategy, MidiConverter, MidiConverterOptions};
+    /// # let composition = Composition::new(Default::default(), Default::default());
+    /// let options = MidiConverterOptions::new().channel_strategy(ChannelStrategy::Ports);
+    /// let (smf, report) = MidiConverter::convert_with_options(&composition, &options);
+    ///
+    /// assert_eq!(report.dropped().count(), 0);
+    /// ```
+    pub fn convert_with_options<'a>(
+        composition: &'a Composition,
+        options: &MidiConverterOptions,
+    ) -> (Smf<'a>, ConversionReport) {
         info!("Converting to MIDI.");
         let start_instant = std::time::Instant::now();
         let track_subtrees: Vec<&Node<RenderSegment>> = composition
             .tree
             .iter()
-            .filter(|n| n.value.segment.element_as::<Part>().is_some())
+            .filter(|n| n.value.segment.element_as::<Part>().is_some() && !n.value.context)
             .collect();
 
-        let channel_assignments = Self::assign_channels(&track_subtrees);
+        // A single track can only address one port
+        let channel_strategy = match (options.channel_strategy, options.format) {
+            (ChannelStrategy::Ports, SmfFormat::SingleTrack) => {
+                warn!(
+                    "Warning: `ChannelStrategy::Ports` is unsupported by `SmfFormat::SingleTrack`, \
+                    using `ChannelStrategy::Drop` instead."
+                );
+                ChannelStrategy::Drop
+            }
+            (strategy, _) => strategy,
+        };
+        let decisions = Self::assign_channels(
+            &track_subtrees,
+            &composition.tree,
+            options,
+            channel_strategy,
+        );
+        let (note_timings, note_overlaps, skipped_notes) = Self::normalize_notes(
+            &track_subtrees,
+            &decisions,
+            &composition.tree,
+            options.note_overlap_policy,
+        );
+        let report = ConversionReport {
+            channel_strategy,
+            channels: track_subtrees
+                .iter()
+                .map(|n| n.idx)
+                .zip(decisions.iter().copied())
+                .collect(),
+            note_overlaps,
+            skipped_notes,
+        };
+
+        if !report.note_overlaps.is_empty() {
+            info!(
+                "Resolved {:?} overlapping same-pitch notes ({:?}).",
+                report.note_overlaps.len(),
+                options.note_overlap_policy
+            );
+        }
+        if !report.skipped_notes.is_empty() {
+            warn!(
+                "Warning: Skipped {:?} notes without duration.",
+                report.skipped_notes.len()
+            );
+        }
 
-        if channel_assignments.iter().any(Option::is_none) {
+        if report.dropped().next().is_some() {
             warn!("Warning: Some parts could not be assigned a channel due to too many concurrent parts.");
             warn!(
-                "Maximum allowed concurrent Parts: (Instrument: {:?}, Percussion: {:?})",
-                Self::instrument_channels().len(),
-                Self::drum_channels().len()
+                "Maximum allowed concurrent Parts: (Instrument: {:?}, Percussion: {:?}). \
+                Consider a different `ChannelStrategy` (current: {:?}).",
+                options.instrument_channels().len(),
+                options.drum_channels.len(),
+                report.channel_strategy
             );
         }
+        for (idx, decision) in &report.channels {
+            match decision {
+                ChannelDecision::Exclusive(MidiChannel { port, .. }) if *port > 0 => {
+                    info!("Part (idx: {:?}) assigned to port {:?}.", idx, port)
+                }
+                ChannelDecision::Merged { with, .. } => {
+                    info!("Part (idx: {:?}) merged with Part (idx: {:?}).", idx, with)
+                }
+                ChannelDecision::Shared { with, .. } => {
+                    info!(
+                        "Part (idx: {:?}) sharing channel of Part (idx: {:?}).",
+                        idx, with
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        let mut shared_program_events =
+            Self::shared_program_events(&track_subtrees, &decisions, &composition.tree);
+        let multi_port = report.ports() > 1;
 
         let mut global_events_added = false;
         let tracks: Vec<Vec<TrackEvent>> = track_subtrees
             .into_iter()
-            .zip(channel_assignments.iter())
-            .filter_map(|(node, opt_ch)| opt_ch.map(|ch| (node, ch)))
+            .zip(decisions.iter())
+            .filter_map(|(node, decision)| decision.channel().map(|ch| (node, ch)))
             .map(|(subtree_root, channel)| {
-                let initial_events = if !global_events_added {
+                let mut initial_events = if !global_events_added {
                     global_events_added = true;
-                    Some(Self::extract_tempo_events(&composition.tree))
+                    let mut global_events = Self::extract_tempo_events(
+                        &composition.tree,
+                        composition.options.ticks_per_beat,
+                        options.default_tempo,
+                    );
+                    global_events.append(&mut Self::extract_text_events(&composition.tree));
+                    #[cfg(feature = "musical")]
+                    global_events.append(&mut Self::extract_signature_events(
+                        &composition.tree,
+                        composition.options.ticks_per_beat,
+                    ));
+
+                    global_events
                 } else {
-                    None
+                    vec![]
                 };
-
-                let mut track =
-                    Self::convert_subtree(subtree_root, &composition.tree, channel, initial_events);
+                if multi_port {
+                    initial_events.push((
+                        0,
+                        TrackEvent {
+                            delta: 0.into(),
+                            kind: TrackEventKind::Meta(MetaMessage::MidiPort(channel.port.into())),
+                        },
+                    ));
+                }
+                // Parts sharing a channel have their program changes managed between notes
+                let program_events = shared_program_events.remove(&subtree_root.idx);
+                let emit_programs = program_events.is_none();
+                initial_events.extend(program_events.into_iter().flatten());
+
+                let mut track = Self::convert_subtree(
+                    subtree_root,
+                    &composition.tree,
+                    channel.channel,
+                    emit_programs,
+                    composition.options.ticks_per_beat,
+                    options,
+                    &note_timings,
+                    Some(initial_events),
+                );
 
                 track.append(&mut vec![TrackEvent {
                     delta: 0.into(),
@@ -93,12 +276,16 @@ impl MidiConverter {
 
         let duration = std::time::Instant::now().duration_since(start_instant);
         if log_enabled!(Level::Info) {
-            let used_channels = channel_assignments
+            let used_channels = decisions
+                .iter()
+                .filter_map(ChannelDecision::channel)
+                .map(|ch| ch.channel)
+                .collect::<Vec<_>>();
+            let drum_channels = options.drum_channels.iter().copied().collect::<Vec<_>>();
+            let instrument_channels = options
+                .instrument_channels()
                 .into_iter()
-                .flatten()
                 .collect::<Vec<_>>();
-            let drum_channels = Self::drum_channels().into_iter().collect::<Vec<_>>();
-            let instrument_channels = Self::instrument_channels().into_iter().collect::<Vec<_>>();
             let used_drum_channels = (0..u4::max_value().into())
                 .filter(|ch| drum_channels.contains(ch) && used_channels.contains(ch))
                 .collect::<Vec<_>>();
@@ -106,284 +293,479 @@ impl MidiConverter {
                 .filter(|ch| instrument_channels.contains(ch) && used_channels.contains(ch))
                 .collect::<Vec<_>>();
 
-            info!("MIDI conversion complete ({:?}). Total events: {:?}. Channels used: Instrument: {:?}, Percussion: {:?}.",
-                duration, tracks.iter().map(Vec::len).sum::<usize>(), used_instrument_channels, used_drum_channels);
+            info!("MIDI conversion complete ({:?}). Total events: {:?}. Ports used: {:?}. Channels used: Instrument: {:?}, Percussion: {:?}.",
+                duration, tracks.iter().map(Vec::len).sum::<usize>(), report.ports(), used_instrument_channels, used_drum_channels);
         }
 
-        Smf {
-            header: Header {
-                format: Parallel,
-                timing: Metrical((composition.options.ticks_per_beat as u16).into()),
+        let (format, tracks) = match options.format {
+            SmfFormat::SingleTrack => (Format::SingleTrack, vec![Self::merge_tracks(tracks)]),
+            SmfFormat::Parallel => (Format::Parallel, tracks),
+        };
+
+        (
+            Smf {
+                header: Header {
+                    format,
+                    timing: Metrical((composition.options.ticks_per_beat as u16).into()),
+                },
+                tracks,
             },
-            tracks,
+            report,
+        )
+    }
+
+    /// Writes a [`Smf`] in the Standard MIDI File format, omitting running status if
+    /// [`MidiConverterOptions::running_status`] is `false` ([`Smf::write`] always uses running
+    /// status).
+    /// ```
+    /// # use redact_composer_core::Composition;
+    /// # use redact_composer_midi::convert::{MidiConverter, MidiConverterOptions};
+    /// # let composition = Composition::new(Default::default(), Default::default());
+    /// let options = MidiConverterOptions::new().running_status(false);
+    /// let (smf, _) = MidiConverter::convert_with_options(&composition, &options);
+    ///
+    /// let mut bytes = vec![];
+    /// MidiConverter::write(&smf, &options, &mut bytes).unwrap();
+    /// ```
+    pub fn write<W: Write>(
+        smf: &Smf,
+        options: &MidiConverterOptions,
+        mut out: W,
+    ) -> std::io::Result<()> {
+        if options.running_status {
+            return smf.write_std(out);
+        }
+
+        let format: u16 = match smf.header.format {
+            Format::SingleTrack => 0,
+            Format::Parallel => 1,
+            Format::Sequential => 2,
+        };
+        let division = match smf.header.timing {
+            Metrical(ticks_per_beat) => ticks_per_beat.as_int(),
+            Timecode(fps, subframe) => {
+                u16::from_be_bytes([(fps.as_int() as i8).wrapping_neg() as u8, subframe])
+            }
+        };
+        out.write_all(b"MThd")?;
+        out.write_all(&6_u32.to_be_bytes())?;
+        out.write_all(&format.to_be_bytes())?;
+        out.write_all(&(smf.tracks.len() as u16).to_be_bytes())?;
+        out.write_all(&division.to_be_bytes())?;
+
+        for track in &smf.tracks {
+            let mut track_bytes = vec![];
+            for event in track {
+                Self::write_varlen(event.delta.as_int(), &mut track_bytes);
+                // Each event is encoded (by midly) as the first of its own single track file, so
+                // its status is never omitted. The event bytes follow the file header (14 bytes),
+                // track header (8 bytes) and zero delta (1 byte).
+                let mut single_event_bytes = vec![];
+                let single_event = TrackEvent {
+                    delta: 0.into(),
+                    kind: event.kind,
+                };
+                midly::write_std(
+                    &smf.header,
+                    [[single_event].iter()],
+                    &mut single_event_bytes,
+                )?;
+                track_bytes.extend_from_slice(&single_event_bytes[23..]);
+            }
+
+            out.write_all(b"MTrk")?;
+            out.write_all(&(track_bytes.len() as u32).to_be_bytes())?;
+            out.write_all(&track_bytes)?;
         }
+
+        Ok(())
     }
 
-    fn drum_channels() -> HashSet<u8> {
-        HashSet::from_iter([9])
+    fn write_varlen(value: u32, out: &mut Vec<u8>) {
+        let mut bytes = vec![(value & 0x7F) as u8];
+        let mut rest = value >> 7;
+        while rest > 0 {
+            bytes.push((rest & 0x7F) as u8 | 0x80);
+            rest >>= 7;
+        }
+        out.extend(bytes.into_iter().rev());
     }
 
-    fn instrument_channels() -> HashSet<u8> {
-        let drum_channels = Self::drum_channels();
-        (0..=u4::max_value().into())
-            .filter(|ch| !drum_channels.contains(ch))
-            .collect()
+    // Merges tracks into a single track (for SMF format 0), keeping the relative order of events
+    // occurring at the same time (other than note ends coming first).
+    fn merge_tracks<'a>(tracks: Vec<Vec<TrackEvent<'a>>>) -> Vec<TrackEvent<'a>> {
+        let mut abs_time_events = tracks
+            .into_iter()
+            .flat_map(|track| {
+                let mut time = 0;
+                track.into_iter().map(move |event| {
+                    time += event.delta.as_int();
+                    (time, event)
+                })
+            })
+            .filter(|(_, event)| event.kind != TrackEventKind::Meta(MetaMessage::EndOfTrack))
+            .collect::<Vec<_>>();
+        abs_time_events.sort_by_key(|(time, event)| (*time, !Self::is_note_off(&event.kind)));
+
+        let mut curr_time = 0;
+        let mut track = abs_time_events
+            .into_iter()
+            .map(|(time, mut event)| {
+                event.delta = (time - curr_time).into();
+                curr_time = time;
+                event
+            })
+            .collect::<Vec<_>>();
+        track.push(TrackEvent {
+            delta: 0.into(),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+
+        track
     }
 
-    fn assign_channels(parts: &[&Node<RenderSegment>]) -> Vec<Option<u8>> {
-        let mut drum_channels: HashSet<u8> = Self::drum_channels();
-        let mut inst_channels: HashSet<u8> = Self::instrument_channels();
-
-        let mut part_times: Vec<(&Node<RenderSegment>, Option<u8>)> =
-            parts.iter().map(|p| (*p, None)).collect();
-
-        let mut sorted_part_times: Vec<&mut (&Node<RenderSegment>, Option<u8>)> =
-            part_times.iter_mut().collect();
-        sorted_part_times.sort_by_key(|(p, _)| p.value.segment.timing.start);
-
-        let mut temp_channels: Vec<(&Node<RenderSegment>, u8)> = vec![];
-        for (next_part, opt_ch) in sorted_part_times {
-            // release temp channels for reuse if they're past the next part's start time
-            let mut i: usize = 0;
-            while i < temp_channels.len() {
-                if temp_channels[i].0.value.segment.timing.end
-                    <= next_part.value.segment.timing.start
-                {
-                    match temp_channels[i]
-                        .0
-                        .value
-                        .segment
-                        .element_as::<Part>()
-                        .unwrap()
-                        .part_type()
-                    {
-                        PartType::Instrument => inst_channels.insert(temp_channels[i].1),
-                        PartType::Percussion => drum_channels.insert(temp_channels[i].1),
+    fn extract_tempo_events(
+        tree: &Tree<RenderSegment>,
+        ticks_per_beat: i32,
+        default_tempo: Tempo,
+    ) -> Vec<(i32, TrackEvent<'_>)> {
+        let tempo_map = TempoMap::from_tree_with_default(tree, ticks_per_beat, default_tempo);
+        // Tempo ramps are approximated by a tempo change every quarter beat
+        let ramp_step = (ticks_per_beat / 4).max(1);
+
+        // Convert each tempo section into midi event(s)
+        tempo_map
+            .sections()
+            .iter()
+            .flat_map(|section| {
+                let microseconds_per_beat = |start: i32| {
+                    let microseconds = if section.is_ramp() {
+                        // Chosen such that the ramp step's duration is exact
+                        let end = (start + ramp_step).min(section.timing.end);
+                        let beats = (end - start) as f64 / ticks_per_beat as f64;
+                        (tempo_map.seconds_between(start, end) * 1_000_000.0 / beats).round()
+                    } else {
+                        60_000_000.0 / section.start_bpm
                     };
 
-                    temp_channels.remove(i);
-                    // No increment here because the removal shifts elements past `i` left by 1
+                    // Slower tempos than a MIDI tempo (24 bits) can represent are clamped
+                    microseconds.min(f64::from(MAX_MICROSECONDS_PER_BEAT)) as u32
+                };
+                let starts = if section.is_ramp() {
+                    section.timing.divide_into(ramp_step)
                 } else {
-                    i += 1;
-                }
-            }
-
-            // Assign a channel from available channels
-            let channel_pool = match next_part
-                .value
-                .segment
-                .element_as::<Part>()
-                .unwrap()
-                .part_type()
-            {
-                PartType::Instrument => &mut inst_channels,
-                PartType::Percussion => &mut drum_channels,
-            };
+                    vec![section.timing]
+                };
 
-            let available_channel: Option<u8> = channel_pool.iter().min().copied();
+                starts.into_iter().map(move |timing| {
+                    (
+                        timing.start,
+                        TrackEvent {
+                            delta: 0.into(),
+                            kind: TrackEventKind::Meta(MetaMessage::Tempo(
+                                microseconds_per_beat(timing.start).into(),
+                            )),
+                        },
+                    )
+                })
+            })
+            .collect::<Vec<_>>()
+    }
 
-            if let Some(ch) = available_channel {
-                *opt_ch = Some(ch);
-                channel_pool.remove(&ch);
-                temp_channels.insert(temp_channels.len(), (next_part, ch));
-            } else {
-                debug!(
-                    "Could not assign channel for {:?} (idx: {:?}). \
-                All available channels are occupied during this time.",
-                    next_part.value.segment, next_part.idx
-                );
-            };
-        }
+    // Marker/Lyric events located outside of any Part.
+    fn extract_text_events(tree: &Tree<RenderSegment>) -> Vec<(i32, TrackEvent<'_>)> {
+        let is_part =
+            |node: &Node<RenderSegment>| node.value.segment.element_as::<Part>().is_some();
 
-        part_times.into_iter().map(|(_, ch)| ch).collect()
+        tree.iter()
+            .filter(|n| !is_part(n) && !tree.ancestors_of(n.idx).any(is_part))
+            .filter_map(|n| {
+                Self::text_message(&n.value.segment).map(|message| {
+                    (
+                        n.value.segment.timing.start,
+                        TrackEvent {
+                            delta: 0.into(),
+                            kind: TrackEventKind::Meta(message),
+                        },
+                    )
+                })
+            })
+            .collect()
     }
 
-    fn extract_tempo_events(tree: &Tree<RenderSegment>) -> Vec<(i32, TrackEvent<'_>)> {
-        let timing = if let Some(root) = tree.root() {
-            root.value.segment.timing
-        } else {
-            return vec![];
-        };
+    #[cfg(feature = "musical")]
+    fn extract_signature_events(
+        tree: &Tree<RenderSegment>,
+        ticks_per_beat: i32,
+    ) -> Vec<(i32, TrackEvent<'_>)> {
+        // (start, is_key) -> (depth, message)
+        let mut signatures = BTreeMap::new();
+
+        for node in tree.iter() {
+            let segment = &node.value.segment;
+            let signature = if let Some(time_signature) = segment.element_as::<TimeSignature>() {
+                let message = Self::time_signature_message(time_signature, ticks_per_beat);
+                if message.is_none() {
+                    warn!(
+                        "{:?} cannot be represented as a MIDI time signature, skipping.",
+                        time_signature
+                    );
+                }
 
-        let default_tempo = Tempo::from_bpm(120);
-        let spanning_tempos = tree
-            .iter()
-            .filter_map(|n| (&n.value.segment).try_into().ok())
-            .fold(
-                vec![(&default_tempo, timing)],
-                |mut tempos, tempo: SegmentRef<Tempo>| {
-                    // Find the position of the first existing tempo starting after/at the new tempo
-                    let start_overlap =
-                        tempos.partition_point(|(_, timing)| timing.start < tempo.timing.start);
-                    // Find the position of the first existing tempo ending before the new tempo
-                    let end_overlap =
-                        tempos.partition_point(|(_, timing)| tempo.timing.end >= timing.end);
-
-                    if start_overlap > end_overlap {
-                        // This is the case if the new tempo is within an existing tempo segment
-                        // In this case the new tempo needs to be spliced within an existing tempo segment
-                        let splice_tempo = tempos.remove(end_overlap);
-
-                        let first_split = (
-                            splice_tempo.0,
-                            Timing::from(splice_tempo.1.start..tempo.timing.start),
-                        );
-                        let last_split = (
-                            splice_tempo.0,
-                            Timing::from(tempo.timing.end..splice_tempo.1.end),
-                        );
-
-                        tempos.insert(end_overlap, first_split);
-                        tempos.insert(end_overlap + 1, (tempo.element, *tempo.timing));
-                        tempos.insert(end_overlap + 2, last_split);
-                    } else {
-                        // Cut out the existing tempos during the overlapping range
-                        tempos.drain(start_overlap..end_overlap);
-
-                        // Update the existing tempo segment (before the cut region) and update its
-                        // timing to end at the inserted tempo's start time
-                        if let Some(ele) = if start_overlap == 0 {
-                            None
-                        } else {
-                            tempos.get_mut(start_overlap - 1)
-                        } {
-                            ele.1.end = ele.1.end.min(tempo.timing.start);
-                        }
-
-                        // Update the existing tempo segment (after the cut region) and update its
-                        // timing to start at the inserted tempo's end time
-                        if let Some(ele) = tempos.get_mut(start_overlap) {
-                            ele.1.start = ele.1.start.max(tempo.timing.end);
-                        }
-
-                        tempos.insert(start_overlap, (tempo.element, *tempo.timing));
-                    }
-
-                    tempos
-                },
-            );
+                message.map(|m| (false, m))
+            } else {
+                segment
+                    .element_as::<Key>()
+                    .map(|key| (true, Self::key_signature_message(key)))
+            };
 
-        // Convert each tempo segment into a midi event
-        spanning_tempos
+            if let Some((is_key, message)) = signature {
+                let depth = tree.ancestors_of(node.idx).count();
+                let slot = (segment.timing.start, is_key);
+                // Deeper signatures override shallower ones starting at the same time
+                if !matches!(signatures.get(&slot), Some((d, _)) if *d > depth) {
+                    signatures.insert(slot, (depth, message));
+                }
+            }
+        }
+
+        signatures
             .into_iter()
-            .map(|(tempo, timing)| {
+            .map(|((start, _), (_, message))| {
                 (
-                    timing.start,
+                    start,
                     TrackEvent {
                         delta: 0.into(),
-                        kind: TrackEventKind::Meta(MetaMessage::Tempo(
-                            tempo.microseconds_per_beat().into(),
-                        )),
+                        kind: TrackEventKind::Meta(message),
                     },
                 )
             })
-            .collect::<Vec<_>>()
+            .collect()
+    }
+
+    // The denominator must be a power of two, relative to a quarter note of `ticks_per_beat`.
+    #[cfg(feature = "musical")]
+    fn time_signature_message(
+        time_signature: &TimeSignature,
+        ticks_per_beat: i32,
+    ) -> Option<MetaMessage<'static>> {
+        let whole_note = ticks_per_beat * 4;
+        let beat_length = time_signature.beat_length;
+        if beat_length <= 0 || whole_note % beat_length != 0 {
+            return None;
+        }
+        let denominator = u32::try_from(whole_note / beat_length)
+            .ok()
+            .filter(|d| d.is_power_of_two())?;
+        // MIDI clocks (24 per quarter note) per metronome click (one click per beat)
+        let clocks_per_click = (24 * beat_length / ticks_per_beat).clamp(1, 255) as u8;
+
+        Some(MetaMessage::TimeSignature(
+            u8::try_from(time_signature.beats_per_bar).ok()?,
+            denominator.trailing_zeros() as u8,
+            clocks_per_click,
+            8,
+        ))
     }
 
+    // Uses the major key signature sharing the most pitch classes with the key (preferring fewer
+    // accidentals), and is minor if the key has a minor third.
+    #[cfg(feature = "musical")]
+    fn key_signature_message(key: &Key) -> MetaMessage<'static> {
+        let pitch_classes = key.pitch_classes();
+        let shared_count = |sharps: i8| {
+            let tonic = PitchClass((i16::from(sharps) * 7).rem_euclid(12) as u8);
+            Key::from((tonic, Scale::Major))
+                .pitch_classes()
+                .into_iter()
+                .filter(|pc| pitch_classes.contains(pc))
+                .count()
+        };
+        let sharps = [0, 1, -1, 2, -2, 3, -3, 4, -4, 5, -5, -6]
+            .into_iter()
+            .fold((0, 0), |(best, best_count), sharps| {
+                let count = shared_count(sharps);
+                if count > best_count {
+                    (sharps, count)
+                } else {
+                    (best, best_count)
+                }
+            })
+            .0;
+        let third = key.relative_pitch(Degree::III).0 % 12;
+        let minor = (third + 12 - key.root().0 % 12) % 12 == 3;
+
+        MetaMessage::KeySignature(sharps, minor)
+    }
+
+    fn text_message(segment: &Segment) -> Option<MetaMessage<'_>> {
+        if let Some(marker) = segment.element_as::<Marker>() {
+            Some(MetaMessage::Marker(marker.0.as_bytes()))
+        } else {
+            segment
+                .element_as::<Lyric>()
+                .map(|lyric| MetaMessage::Lyric(lyric.0.as_bytes()))
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn convert_subtree<'a>(
-        subtree_root: &Node<RenderSegment>,
+        subtree_root: &'a Node<RenderSegment>,
         tree: &'a Tree<RenderSegment>,
         channel: u8,
+        emit_programs: bool,
+        ticks_per_beat: i32,
+        options: &MidiConverterOptions,
+        note_timings: &HashMap<usize, Timing>,
         initial_abs_time_events: Option<Vec<(i32, TrackEvent<'a>)>>,
     ) -> Vec<TrackEvent<'a>> {
-        let mut abs_time_events: Vec<(i32, TrackEvent)> = tree
-            .node_iter(subtree_root)
-            .filter_map(|n| {
-                if let Some(instrument) = n.value.segment.element_as::<Program>() {
-                    Some(vec![(
-                        n.value.segment.timing.start,
-                        TrackEvent {
-                            delta: 0.into(),
-                            kind: TrackEventKind::Midi {
-                                channel: channel.into(),
-                                message: MidiMessage::ProgramChange {
-                                    program: instrument.0.into(),
-                                },
-                            },
-                        },
-                    )])
-                } else {
-                    n.value.segment.element_as::<PlayNote>().map(|play_note| {
-                        vec![
-                            (
-                                n.value.segment.timing.start,
-                                TrackEvent {
-                                    delta: 0.into(),
-                                    kind: TrackEventKind::Midi {
-                                        channel: channel.into(),
-                                        message: MidiMessage::NoteOn {
-                                            key: play_note.note.into(),
-                                            vel: play_note.velocity.into(),
-                                        },
-                                    },
-                                },
-                            ),
-                            (
-                                n.value.segment.timing.end,
-                                TrackEvent {
-                                    delta: 0.into(),
-                                    kind: TrackEventKind::Midi {
-                                        channel: channel.into(),
-                                        message: MidiMessage::NoteOff {
-                                            key: play_note.note.into(),
-                                            vel: play_note.velocity.into(),
-                                        },
-                                    },
-                                },
-                            ),
-                        ]
-                    })
-                }
-            })
-            .flatten()
-            .collect();
+        let channel_event = |message: MidiMessage| TrackEvent {
+            delta: 0.into(),
+            kind: TrackEventKind::Midi {
+                channel: channel.into(),
+                message,
+            },
+        };
 
+        let mut abs_time_events: Vec<(i32, TrackEvent)> = vec![];
+        if let Some(name) = &subtree_root.value.segment.name {
+            abs_time_events.push((
+                0,
+                TrackEvent {
+                    delta: 0.into(),
+                    kind: TrackEventKind::Meta(MetaMessage::TrackName(name.as_bytes())),
+                },
+            ));
+        }
         if let Some(mut initial_events) = initial_abs_time_events {
             abs_time_events.append(&mut initial_events);
         }
 
-        abs_time_events.sort_by(|a, b| {
-            let time_comparison = a.0.cmp(&b.0);
-            match time_comparison {
-                Ordering::Equal => {
-                    // Tempo and ProgramChange messages should come before others, assuming equal timing
-                    match (a.1.kind, b.1.kind) {
-                        (
-                            TrackEventKind::Meta(MetaMessage::Tempo(..)),
-                            TrackEventKind::Meta(MetaMessage::Tempo(..)),
-                        ) => Ordering::Equal,
-                        (TrackEventKind::Meta(MetaMessage::Tempo(..)), _) => Ordering::Less,
-                        (_, TrackEventKind::Meta(MetaMessage::Tempo(..))) => Ordering::Greater,
-                        (
-                            TrackEventKind::Midi {
-                                message: MidiMessage::ProgramChange { .. },
-                                ..
-                            },
-                            TrackEventKind::Midi {
-                                message: MidiMessage::ProgramChange { .. },
-                                ..
-                            },
-                        ) => Ordering::Equal,
-                        (
-                            TrackEventKind::Midi {
-                                message: MidiMessage::ProgramChange { .. },
-                                ..
-                            },
-                            _,
-                        ) => Ordering::Less,
-                        (
-                            _,
-                            TrackEventKind::Midi {
-                                message: MidiMessage::ProgramChange { .. },
-                                ..
-                            },
-                        ) => Ordering::Greater,
-                        _ => Ordering::Equal,
-                    }
+        let subtree_events = tree.node_iter(subtree_root).flat_map(|n| {
+            let segment = &n.value.segment;
+            let start = segment.timing.start;
+
+            if let Some(instrument) = segment.element_as::<Program>() {
+                if !emit_programs {
+                    return vec![];
                 }
-                _ => time_comparison,
+                vec![(
+                    start,
+                    channel_event(MidiMessage::ProgramChange {
+                        program: instrument.0.into(),
+                    }),
+                )]
+            } else if let Some(play_note) = segment.element_as::<PlayNote>() {
+                let Some(timing) = note_timings.get(&n.idx) else {
+                    return vec![];
+                };
+                let note_off = match options.note_off_style {
+                    NoteOffStyle::NoteOff => MidiMessage::NoteOff {
+                        key: play_note.note.into(),
+                        vel: play_note.velocity.into(),
+                    },
+                    NoteOffStyle::NoteOnZeroVelocity => MidiMessage::NoteOn {
+                        key: play_note.note.into(),
+                        vel: 0.into(),
+                    },
+                };
+                vec![
+                    (
+                        timing.start,
+                        channel_event(MidiMessage::NoteOn {
+                            key: play_note.note.into(),
+                            vel: play_note.velocity.into(),
+                        }),
+                    ),
+                    (timing.end, channel_event(note_off)),
+                ]
+            } else if let Some(control_change) = segment.element_as::<ControlChange>() {
+                vec![(
+                    start,
+                    channel_event(MidiMessage::Controller {
+                        controller: Self::data_byte(control_change.controller),
+                        value: Self::data_byte(control_change.value),
+                    }),
+                )]
+            } else if let Some(mix) = segment.element_as::<Mix>() {
+                mix.control_changes()
+                    .into_iter()
+                    .map(|control_change| {
+                        (
+                            start,
+                            channel_event(MidiMessage::Controller {
+                                controller: Self::data_byte(control_change.controller),
+                                value: Self::data_byte(control_change.value),
+                            }),
+                        )
+                    })
+                    .collect()
+            } else if let Some(pitch_bend) = segment.element_as::<PitchBend>() {
+                vec![(
+                    start,
+                    channel_event(MidiMessage::PitchBend {
+                        bend: midly::PitchBend::from_int(pitch_bend.0),
+                    }),
+                )]
+            } else if let Some(pressure) = segment.element_as::<ChannelPressure>() {
+                vec![(
+                    start,
+                    channel_event(MidiMessage::ChannelAftertouch {
+                        vel: Self::data_byte(pressure.0),
+                    }),
+                )]
+            } else if let Some(aftertouch) = segment.element_as::<PolyAftertouch>() {
+                vec![(
+                    start,
+                    channel_event(MidiMessage::Aftertouch {
+                        key: Self::data_byte(aftertouch.note),
+                        vel: Self::data_byte(aftertouch.pressure),
+                    }),
+                )]
+            } else if let Some(automation) = segment.element_as::<Automation>() {
+                Self::sample_automation(automation, segment.timing, ticks_per_beat)
+                    .into_iter()
+                    .map(|(tick, message)| (tick, channel_event(message)))
+                    .collect()
+            } else if let Some(message) = Self::text_message(segment) {
+                vec![(
+                    start,
+                    TrackEvent {
+                        delta: 0.into(),
+                        kind: TrackEventKind::Meta(message),
+                    },
+                )]
+            } else {
+                vec![]
             }
         });
+        abs_time_events.extend(subtree_events);
+
+        // TrackName, then other meta messages (except lyrics), then ProgramChange, then other
+        // channel control messages, then note ends should come before others, assuming equal timing
+        let order = |kind: &TrackEventKind| match kind {
+            TrackEventKind::Meta(MetaMessage::TrackName(..)) => 0,
+            TrackEventKind::Meta(MetaMessage::Lyric(..)) => 5,
+            TrackEventKind::Meta(..) => 1,
+            TrackEventKind::Midi { message, .. } => match message {
+                MidiMessage::ProgramChange { .. } => 2,
+                MidiMessage::Controller { .. }
+                | MidiMessage::PitchBend { .. }
+                | MidiMessage::ChannelAftertouch { .. }
+                | MidiMessage::Aftertouch { .. } => 3,
+                _ if Self::is_note_off(kind) => 4,
+                _ => 5,
+            },
+            _ => 5,
+        };
+        match options.event_ordering {
+            EventOrdering::ControlsFirst => abs_time_events.sort_by(|a, b| {
+                a.0.cmp(&b.0)
+                    .then_with(|| order(&a.1.kind).cmp(&order(&b.1.kind)))
+            }),
+            EventOrdering::TreeOrder => abs_time_events
+                .sort_by_key(|(time, event)| (*time, !Self::is_note_off(&event.kind))),
+        }
 
         let mut curr_time: i32 = 0;
         for (timing, track_event) in &mut abs_time_events {
@@ -393,4 +775,63 @@ impl MidiConverter {
 
         abs_time_events.iter().map(|t| t.1).collect()
     }
+
+    fn is_note_off(kind: &TrackEventKind) -> bool {
+        matches!(kind, TrackEventKind::Midi { message, .. } if Self::ends_note(message))
+    }
+
+    // Whether the message ends a note (NoteOff, or NoteOn with zero velocity).
+    pub(crate) fn ends_note(message: &MidiMessage) -> bool {
+        match message {
+            MidiMessage::NoteOff { .. } => true,
+            MidiMessage::NoteOn { vel, .. } => *vel == 0,
+            _ => false,
+        }
+    }
+
+    // Clamps a value to the 7 bits of a MIDI data byte, rather than discarding the high bit (as
+    // `u7::from` does).
+    fn data_byte(value: u8) -> u7 {
+        value.min(u7::max_value().as_int()).into()
+    }
+
+    // Samples an automation curve into messages every `resolution` ticks over `timing`, skipping
+    // samples which would not change the value.
+    fn sample_automation(
+        automation: &Automation,
+        timing: Timing,
+        ticks_per_beat: i32,
+    ) -> Vec<(i32, MidiMessage)> {
+        let resolution = automation.resolution.unwrap_or(ticks_per_beat / 16).max(1);
+        let steps = timing.divide_into(resolution);
+        let last_step = steps.len().saturating_sub(1).max(1) as f32;
+
+        let mut last_message = None;
+        steps
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, step)| {
+                let value = automation.curve.value_at(idx as f32 / last_step).round();
+                let midi_value = value.clamp(0.0, 127.0) as u8;
+                let message = match automation.target {
+                    AutomationTarget::Control(controller) => MidiMessage::Controller {
+                        controller: Self::data_byte(controller),
+                        value: midi_value.into(),
+                    },
+                    AutomationTarget::PitchBend => MidiMessage::PitchBend {
+                        bend: midly::PitchBend::from_int(value.clamp(-8192.0, 8191.0) as i16),
+                    },
+                    AutomationTarget::ChannelPressure => MidiMessage::ChannelAftertouch {
+                        vel: midi_value.into(),
+                    },
+                    AutomationTarget::PolyAftertouch(note) => MidiMessage::Aftertouch {
+                        key: Self::data_byte(note),
+                        vel: midi_value.into(),
+                    },
+                };
+
+                (last_message.replace(message) != Some(message)).then_some((step.start, message))
+            })
+            .collect()
+    }
 }
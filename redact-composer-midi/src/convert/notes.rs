@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use redact_composer_core::{
+    elements::PlayNote,
+    render::{
+        tree::{Node, Tree},
+        RenderSegment,
+    },
+    timing::Timing,
+};
+
+use super::{ChannelDecision, MidiChannel, MidiConverter, NoteOverlapPolicy};
+
+#[allow(unused_imports)] // Imports used in doc comments only
+use super::MidiConverterOptions;
+
+/// Two overlapping [`PlayNote`]s of the same pitch on a channel, resolved during MIDI conversion
+/// according to [`MidiConverterOptions::note_overlap_policy`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct NoteOverlap {
+    /// The channel of the notes.
+    pub channel: MidiChannel,
+    /// The pitch of the notes.
+    pub note: u8,
+    /// Tree node index of the earlier [`PlayNote`].
+    pub earlier: usize,
+    /// Tree node index of the later [`PlayNote`].
+    pub later: usize,
+    /// The tick at which the notes started overlapping (the later note's start).
+    pub tick: i32,
+}
+
+impl MidiConverter {
+    // Timings of the assigned parts' notes (keyed by PlayNote node index), after resolving
+    // overlapping same-pitch notes on each channel. Notes without a timing (merged into another
+    // note, or left without duration by the overlap policy) are not sent. Notes without duration to
+    // begin with are skipped, and returned separately (in tree order).
+    pub(super) fn normalize_notes(
+        parts: &[&Node<RenderSegment>],
+        decisions: &[ChannelDecision],
+        tree: &Tree<RenderSegment>,
+        policy: NoteOverlapPolicy,
+    ) -> (HashMap<usize, Timing>, Vec<NoteOverlap>, Vec<usize>) {
+        let mut channel_notes: HashMap<(MidiChannel, u8), Vec<(usize, Timing)>> = HashMap::new();
+        let mut skipped = vec![];
+        for (part, decision) in parts.iter().zip(decisions) {
+            let Some(channel) = decision.channel() else {
+                continue;
+            };
+            for node in tree.node_iter(part) {
+                if let Some(play_note) = node.value.segment.element_as::<PlayNote>() {
+                    if node.value.segment.timing.is_empty() {
+                        skipped.push(node.idx);
+                        continue;
+                    }
+
+                    channel_notes
+                        .entry((channel, play_note.note))
+                        .or_default()
+                        .push((node.idx, node.value.segment.timing));
+                }
+            }
+        }
+
+        let mut timings = HashMap::new();
+        let mut overlaps = vec![];
+        for ((channel, note), mut notes) in channel_notes {
+            notes.sort_by_key(|(_, timing)| timing.start);
+
+            let mut resolved: Vec<(usize, Timing)> = vec![];
+            for (idx, mut timing) in notes {
+                let previous = resolved
+                    .last_mut()
+                    .filter(|(_, prev)| prev.end > timing.start);
+                if let Some((prev_idx, prev)) = previous {
+                    overlaps.push(NoteOverlap {
+                        channel,
+                        note,
+                        earlier: *prev_idx,
+                        later: idx,
+                        tick: timing.start,
+                    });
+
+                    let end = prev.end.max(timing.end);
+                    match policy {
+                        NoteOverlapPolicy::Truncate => prev.end = timing.start,
+                        NoteOverlapPolicy::Merge => {
+                            prev.end = end;
+                            continue;
+                        }
+                        NoteOverlapPolicy::Retrigger => {
+                            prev.end = timing.start;
+                            timing.end = end;
+                        }
+                    }
+                }
+
+                resolved.push((idx, timing));
+            }
+
+            timings.extend(resolved.into_iter().filter(|(_, t)| t.start < t.end));
+        }
+        overlaps.sort_by_key(|o| (o.tick, o.earlier, o.later));
+        skipped.sort();
+
+        (timings, overlaps, skipped)
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+
+use redact_composer_core::timing::elements::Tempo;
+
+use super::channels::ChannelStrategy;
+
+#[allow(unused_imports)] // Imports used in doc comments only
+use super::MidiConverter;
+#[allow(unused_imports)]
+use midly::{MetaMessage, MidiMessage};
+#[allow(unused_imports)]
+use redact_composer_core::{elements::Part, PartType};
+
+/// Options for [`MidiConverter::convert_with_options`] (and [`MidiConverter::write`]).
+///
+/// Built from the defaults (matching [`MidiConverter::convert`]) by chaining the setter methods:
+/// ```
+/// # use redact_composer_midi::convert::{MidiConverterOptions, NoteOffStyle, SmfFormat};
+/// let options = MidiConverterOptions::new()
+///     .default_tempo(90)
+///     .drum_channels([9, 10])
+///     .format(SmfFormat::SingleTrack)
+///     .note_off_style(NoteOffStyle::NoteOnZeroVelocity)
+///     .running_status(false);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MidiConverterOptions {
+    /// Tempo wherever the composition does not specify a [`Tempo`]. Default: 120 BPM.
+    pub default_tempo: Tempo,
+    /// Channels (`0..=15`) reserved for [`PartType::Percussion`] parts. All others are used for
+    /// [`PartType::Instrument`] parts. Default: `{9}` (General MIDI percussion).
+    pub drum_channels: HashSet<u8>,
+    /// Standard MIDI File format of the output. Default: [`SmfFormat::Parallel`].
+    pub format: SmfFormat,
+    /// How the end of a note is sent. Default: [`NoteOffStyle::NoteOff`].
+    pub note_off_style: NoteOffStyle,
+    /// How overlapping notes of the same pitch on a channel are resolved. Default:
+    /// [`NoteOverlapPolicy::Truncate`].
+    pub note_overlap_policy: NoteOverlapPolicy,
+    /// Whether repeated status bytes are omitted from consecutive channel messages when written
+    /// via [`MidiConverter::write`]. Default: `true`.
+    pub running_status: bool,
+    /// Ordering of a track's events occurring at the same time. Default:
+    /// [`EventOrdering::ControlsFirst`].
+    pub event_ordering: EventOrdering,
+    /// How parts which cannot be assigned an exclusive channel are handled. Default:
+    /// [`ChannelStrategy::Drop`].
+    pub channel_strategy: ChannelStrategy,
+}
+
+impl Default for MidiConverterOptions {
+    fn default() -> Self {
+        MidiConverterOptions {
+            default_tempo: Tempo::from_bpm(120),
+            drum_channels: HashSet::from_iter([9]),
+            format: SmfFormat::default(),
+            note_off_style: NoteOffStyle::default(),
+            note_overlap_policy: NoteOverlapPolicy::default(),
+            running_status: true,
+            event_ordering: EventOrdering::default(),
+            channel_strategy: ChannelStrategy::default(),
+        }
+    }
+}
+
+impl MidiConverterOptions {
+    /// Creates the default options.
+    pub fn new() -> MidiConverterOptions {
+        MidiConverterOptions::default()
+    }
+
+    /// Sets the tempo (in BPM) used wherever the composition does not specify a [`Tempo`].
+    pub fn default_tempo(mut self, bpm: u32) -> MidiConverterOptions {
+        self.default_tempo = Tempo::from_bpm(bpm);
+
+        self
+    }
+
+    /// Sets the channels (`0..=15`) reserved for [`PartType::Percussion`] parts.
+    pub fn drum_channels(mut self, channels: impl IntoIterator<Item = u8>) -> MidiConverterOptions {
+        self.drum_channels = channels.into_iter().filter(|ch| *ch <= 15).collect();
+
+        self
+    }
+
+    /// Sets the Standard MIDI File format of the output.
+    pub fn format(mut self, format: SmfFormat) -> MidiConverterOptions {
+        self.format = format;
+
+        self
+    }
+
+    /// Sets how the end of a note is sent.
+    pub fn note_off_style(mut self, note_off_style: NoteOffStyle) -> MidiConverterOptions {
+        self.note_off_style = note_off_style;
+
+        self
+    }
+
+    /// Sets how overlapping notes of the same pitch on a channel are resolved.
+    pub fn note_overlap_policy(
+        mut self,
+        note_overlap_policy: NoteOverlapPolicy,
+    ) -> MidiConverterOptions {
+        self.note_overlap_policy = note_overlap_policy;
+
+        self
+    }
+
+    /// Sets whether running status is used when written via [`MidiConverter::write`].
+    pub fn running_status(mut self, running_status: bool) -> MidiConverterOptions {
+        self.running_status = running_status;
+
+        self
+    }
+
+    /// Sets the ordering of a track's events occurring at the same time.
+    pub fn event_ordering(mut self, event_ordering: EventOrdering) -> MidiConverterOptions {
+        self.event_ordering = event_ordering;
+
+        self
+    }
+
+    /// Sets how parts which cannot be assigned an exclusive channel are handled.
+    pub fn channel_strategy(mut self, channel_strategy: ChannelStrategy) -> MidiConverterOptions {
+        self.channel_strategy = channel_strategy;
+
+        self
+    }
+
+    pub(super) fn instrument_channels(&self) -> HashSet<u8> {
+        (0..=15)
+            .filter(|ch| !self.drum_channels.contains(ch))
+            .collect()
+    }
+}
+
+/// Standard MIDI File formats supported by [`MidiConverter`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum SmfFormat {
+    /// Format 0: All events are merged into a single track.
+    SingleTrack,
+    /// Format 1: Each [`Part`] is written to its own track, played simultaneously.
+    #[default]
+    Parallel,
+}
+
+/// How the end of a note is sent.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum NoteOffStyle {
+    /// A [`MidiMessage::NoteOff`], with the same velocity as its [`MidiMessage::NoteOn`].
+    #[default]
+    NoteOff,
+    /// A [`MidiMessage::NoteOn`] with zero velocity. Combined with running status, this can
+    /// significantly reduce the size of the output.
+    NoteOnZeroVelocity,
+}
+
+/// How two overlapping notes of the same pitch on a channel are resolved, since a channel can only
+/// sound one note per pitch at a time. Otherwise, the earlier note's end would also end the later.
+///
+/// For example, with a note over `0..480` and a later one over `240..360`:
+/// * [`Truncate`](NoteOverlapPolicy::Truncate): `0..240` and `240..360`.
+/// * [`Merge`](NoteOverlapPolicy::Merge): `0..480`.
+/// * [`Retrigger`](NoteOverlapPolicy::Retrigger): `0..240` and `240..480`.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum NoteOverlapPolicy {
+    /// The earlier note ends where the later note starts.
+    #[default]
+    Truncate,
+    /// The later note is merged into the earlier note, which ends when the later of the two does.
+    Merge,
+    /// The earlier note ends where the later note starts, and the later note ends when the later
+    /// of the two does.
+    Retrigger,
+}
+
+/// Ordering of a track's events occurring at the same time. Either way, the end of a note comes
+/// before the start of another.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum EventOrdering {
+    /// [`MetaMessage::TrackName`] first, then other meta messages (except lyrics), then
+    /// [`MidiMessage::ProgramChange`]s, then other channel control messages, then note ends, then
+    /// the rest (such as note starts and lyrics).
+    #[default]
+    ControlsFirst,
+    /// Note ends first, then the track name and global events (such as tempo changes), then the
+    /// order in which their elements appear in the composition tree.
+    TreeOrder,
+}
@@ -0,0 +1,45 @@
+use std::collections::HashSet;
+
+use super::channels::{ChannelDecision, ChannelStrategy};
+use super::notes::NoteOverlap;
+
+#[allow(unused_imports)] // Imports used in doc comments only
+use super::{MidiConverter, MidiConverterOptions};
+#[allow(unused_imports)]
+use redact_composer_core::elements::{Part, PlayNote};
+
+/// Decisions made during MIDI conversion via [`MidiConverter::convert_with_options`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ConversionReport {
+    /// The [`ChannelStrategy`] used. This is [`MidiConverterOptions::channel_strategy`], unless
+    /// unsupported by the output format (see [`ChannelStrategy::Ports`]).
+    pub channel_strategy: ChannelStrategy,
+    /// The channel decision for each [`Part`], as `(tree node index, decision)`, in tree order.
+    pub channels: Vec<(usize, ChannelDecision)>,
+    /// Overlapping notes of the same pitch on a channel which were resolved, ordered by the tick
+    /// they started overlapping.
+    pub note_overlaps: Vec<NoteOverlap>,
+    /// Tree node indices of [`PlayNote`]s which were not sent since their timing has no duration
+    /// (i.e. its end is not after its start), in tree order.
+    pub skipped_notes: Vec<usize>,
+}
+
+impl ConversionReport {
+    /// Tree node indices of [`Part`]s which were dropped from the output.
+    pub fn dropped(&self) -> impl Iterator<Item = usize> + '_ {
+        self.channels
+            .iter()
+            .filter(|(_, decision)| *decision == ChannelDecision::Dropped)
+            .map(|(idx, _)| *idx)
+    }
+
+    /// The number of ports used.
+    pub fn ports(&self) -> usize {
+        self.channels
+            .iter()
+            .filter_map(|(_, decision)| decision.channel())
+            .map(|channel| channel.port)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+}
@@ -3,7 +3,8 @@ use midly::TrackEventKind::Meta;
 use midly::{MetaMessage, TrackEvent};
 use redact_composer_core::derive::Element;
 use redact_composer_core::render::tree::Tree;
-use redact_composer_core::timing::elements::Tempo;
+use redact_composer_core::timing::elements::{Tempo, TempoRamp};
+use redact_composer_core::timing::STANDARD_BEAT_LENGTH;
 use redact_composer_core::{render::RenderSegment, Segment};
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +20,7 @@ fn tempo_splice_beginning() {
             seed: 0,
             segment: Segment::new(Composition, 0..30),
             error: None,
+            context: false,
         },
         None,
     );
@@ -29,11 +31,16 @@ fn tempo_splice_beginning() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
 
-    let tempo_events = MidiConverter::extract_tempo_events(&render_tree);
+    let tempo_events = MidiConverter::extract_tempo_events(
+        &render_tree,
+        STANDARD_BEAT_LENGTH,
+        Tempo::from_bpm(120),
+    );
 
     assert_eq!(
         tempo_events,
@@ -65,6 +72,7 @@ fn tempo_splice_end() {
             seed: 0,
             segment: Segment::new(Composition, 0..30),
             error: None,
+            context: false,
         },
         None,
     );
@@ -75,11 +83,16 @@ fn tempo_splice_end() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
 
-    let tempo_events = MidiConverter::extract_tempo_events(&render_tree);
+    let tempo_events = MidiConverter::extract_tempo_events(
+        &render_tree,
+        STANDARD_BEAT_LENGTH,
+        Tempo::from_bpm(120),
+    );
 
     assert_eq!(
         tempo_events,
@@ -111,6 +124,7 @@ fn tempo_splice_middle() {
             seed: 0,
             segment: Segment::new(Composition, 0..30),
             error: None,
+            context: false,
         },
         None,
     );
@@ -121,11 +135,16 @@ fn tempo_splice_middle() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
 
-    let tempo_events = MidiConverter::extract_tempo_events(&render_tree);
+    let tempo_events = MidiConverter::extract_tempo_events(
+        &render_tree,
+        STANDARD_BEAT_LENGTH,
+        Tempo::from_bpm(120),
+    );
 
     assert_eq!(
         tempo_events,
@@ -164,6 +183,7 @@ fn tempo_splice_into_multiple() {
             seed: 0,
             segment: Segment::new(Composition, 0..30),
             error: None,
+            context: false,
         },
         None,
     );
@@ -174,6 +194,7 @@ fn tempo_splice_into_multiple() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -184,11 +205,16 @@ fn tempo_splice_into_multiple() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
 
-    let tempo_events = MidiConverter::extract_tempo_events(&render_tree);
+    let tempo_events = MidiConverter::extract_tempo_events(
+        &render_tree,
+        STANDARD_BEAT_LENGTH,
+        Tempo::from_bpm(120),
+    );
 
     assert_eq!(
         tempo_events,
@@ -211,6 +237,39 @@ fn tempo_splice_into_multiple() {
     );
 }
 
+#[test]
+fn tempo_slowest() {
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    for (segment, parent) in [
+        (Segment::new(Composition, 0..960), None),
+        (Segment::new(Tempo::from_bpm(1), 0..480), Some(0)),
+        (Segment::new(TempoRamp::from_bpm(1, 2), 480..960), Some(0)),
+    ] {
+        render_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        );
+    }
+
+    let tempo_events = MidiConverter::extract_tempo_events(
+        &render_tree,
+        STANDARD_BEAT_LENGTH,
+        Tempo::from_bpm(120),
+    );
+
+    // Tempos slower than ~3.6 BPM exceed the 24 bit MIDI tempo, and are clamped
+    assert_eq!(tempo_events.len(), 5);
+    assert!(tempo_events
+        .iter()
+        .all(|(_, e)| e.kind == Meta(MetaMessage::Tempo(0xFF_FFFF.into()))));
+}
+
 #[test]
 fn tempo_splice_spanning() {
     let mut render_tree: Tree<RenderSegment> = Tree::new();
@@ -220,6 +279,7 @@ fn tempo_splice_spanning() {
             seed: 0,
             segment: Segment::new(Composition, 0..30),
             error: None,
+            context: false,
         },
         None,
     );
@@ -230,6 +290,7 @@ fn tempo_splice_spanning() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -240,6 +301,7 @@ fn tempo_splice_spanning() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -250,11 +312,16 @@ fn tempo_splice_spanning() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
 
-    let tempo_events = MidiConverter::extract_tempo_events(&render_tree);
+    let tempo_events = MidiConverter::extract_tempo_events(
+        &render_tree,
+        STANDARD_BEAT_LENGTH,
+        Tempo::from_bpm(120),
+    );
 
     assert_eq!(
         tempo_events,
@@ -293,6 +360,7 @@ fn tempo_splice_spanning2() {
             seed: 0,
             segment: Segment::new(Composition, 0..30),
             error: None,
+            context: false,
         },
         None,
     );
@@ -303,6 +371,7 @@ fn tempo_splice_spanning2() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -313,6 +382,7 @@ fn tempo_splice_spanning2() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -323,6 +393,7 @@ fn tempo_splice_spanning2() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -333,11 +404,16 @@ fn tempo_splice_spanning2() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
 
-    let tempo_events = MidiConverter::extract_tempo_events(&render_tree);
+    let tempo_events = MidiConverter::extract_tempo_events(
+        &render_tree,
+        STANDARD_BEAT_LENGTH,
+        Tempo::from_bpm(120),
+    );
 
     assert_eq!(
         tempo_events,
@@ -376,6 +452,7 @@ fn tempo_splice_spanning3() {
             seed: 0,
             segment: Segment::new(Composition, 0..30),
             error: None,
+            context: false,
         },
         None,
     );
@@ -386,6 +463,7 @@ fn tempo_splice_spanning3() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -396,6 +474,7 @@ fn tempo_splice_spanning3() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -406,6 +485,7 @@ fn tempo_splice_spanning3() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -416,11 +496,16 @@ fn tempo_splice_spanning3() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
 
-    let tempo_events = MidiConverter::extract_tempo_events(&render_tree);
+    let tempo_events = MidiConverter::extract_tempo_events(
+        &render_tree,
+        STANDARD_BEAT_LENGTH,
+        Tempo::from_bpm(120),
+    );
 
     assert_eq!(
         tempo_events,
@@ -452,6 +537,7 @@ fn tempo_splice_spanning4() {
             seed: 0,
             segment: Segment::new(Composition, 0..30),
             error: None,
+            context: false,
         },
         None,
     );
@@ -462,6 +548,7 @@ fn tempo_splice_spanning4() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -472,6 +559,7 @@ fn tempo_splice_spanning4() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -482,6 +570,7 @@ fn tempo_splice_spanning4() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -492,11 +581,16 @@ fn tempo_splice_spanning4() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
 
-    let tempo_events = MidiConverter::extract_tempo_events(&render_tree);
+    let tempo_events = MidiConverter::extract_tempo_events(
+        &render_tree,
+        STANDARD_BEAT_LENGTH,
+        Tempo::from_bpm(120),
+    );
 
     assert_eq!(
         tempo_events,
@@ -528,6 +622,7 @@ fn tempo_splice_multi_spanning() {
             seed: 0,
             segment: Segment::new(Composition, 0..40),
             error: None,
+            context: false,
         },
         None,
     );
@@ -538,6 +633,7 @@ fn tempo_splice_multi_spanning() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -548,6 +644,7 @@ fn tempo_splice_multi_spanning() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -558,6 +655,7 @@ fn tempo_splice_multi_spanning() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -568,6 +666,7 @@ fn tempo_splice_multi_spanning() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
@@ -578,11 +677,16 @@ fn tempo_splice_multi_spanning() {
             seed: 0,
             rendered: true,
             error: None,
+            context: false,
         },
         Some(0),
     );
 
-    let tempo_events = MidiConverter::extract_tempo_events(&render_tree);
+    let tempo_events = MidiConverter::extract_tempo_events(
+        &render_tree,
+        STANDARD_BEAT_LENGTH,
+        Tempo::from_bpm(120),
+    );
 
     assert_eq!(
         tempo_events,
@@ -611,3 +715,1364 @@ fn tempo_splice_multi_spanning() {
         ]
     );
 }
+
+#[test]
+fn tempo_ramp() {
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    render_tree.insert(
+        RenderSegment {
+            rendered: false,
+            seed: 0,
+            segment: Segment::new(Composition, 0..960),
+            error: None,
+            context: false,
+        },
+        None,
+    );
+
+    render_tree.insert(
+        RenderSegment {
+            segment: Segment::new(TempoRamp::from_bpm(60, 120), 0..480),
+            seed: 0,
+            rendered: true,
+            error: None,
+            context: false,
+        },
+        Some(0),
+    );
+
+    let tempo_events = MidiConverter::extract_tempo_events(
+        &render_tree,
+        STANDARD_BEAT_LENGTH,
+        Tempo::from_bpm(120),
+    );
+
+    // One event per quarter beat during the ramp, then back to the default tempo
+    assert_eq!(
+        tempo_events.iter().map(|(t, _)| *t).collect::<Vec<_>>(),
+        vec![0, 120, 240, 360, 480]
+    );
+    let microseconds_per_beat = tempo_events
+        .iter()
+        .map(|(_, e)| match e.kind {
+            Meta(MetaMessage::Tempo(t)) => t.as_int(),
+            _ => panic!("Expected only tempo events."),
+        })
+        .collect::<Vec<_>>();
+    assert!(microseconds_per_beat.windows(2).all(|w| w[0] > w[1]));
+    assert!(microseconds_per_beat[0] < 1_000_000);
+    assert_eq!(microseconds_per_beat[4], 500_000);
+}
+
+fn import_segments<T: redact_composer_core::Element + Clone>(
+    composition: &redact_composer_core::Composition,
+) -> Vec<(i32, i32, T)> {
+    composition
+        .tree
+        .iter()
+        .filter_map(|n| {
+            n.value.segment.element_as::<T>().map(|e| {
+                (
+                    n.value.segment.timing.start,
+                    n.value.segment.timing.end,
+                    e.clone(),
+                )
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn import_round_trip() {
+    use crate::elements::{MidiTrack, Program};
+    use redact_composer_core::elements::{Part, PlayNote};
+
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    let mut insert = |segment: Segment, parent: Option<usize>| {
+        render_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        )
+    };
+    let root = insert(Segment::new(Composition, 0..960), None);
+    insert(Segment::new(Tempo::from_bpm(100), 0..480), Some(root));
+    let part = insert(
+        Segment::new(Part::instrument(Composition), 0..960),
+        Some(root),
+    );
+    insert(Segment::new(Program(5), 0..960), Some(part));
+    let notes = [
+        (
+            0,
+            480,
+            PlayNote {
+                note: 60,
+                velocity: 100,
+            },
+        ),
+        (
+            240,
+            720,
+            PlayNote {
+                note: 64,
+                velocity: 90,
+            },
+        ),
+        (
+            480,
+            960,
+            PlayNote {
+                note: 60,
+                velocity: 80,
+            },
+        ),
+    ];
+    for (start, end, note) in notes {
+        insert(Segment::new(note, start..end), Some(part));
+    }
+    let composition = redact_composer_core::Composition::new(Default::default(), render_tree);
+
+    let smf = MidiConverter::convert(&composition);
+    let imported = MidiConverter::import(&smf).unwrap();
+
+    assert_eq!(imported.options, composition.options);
+    assert!(imported.tree.iter().all(|n| n.value.rendered));
+    assert_eq!(
+        import_segments::<Tempo>(&imported)
+            .into_iter()
+            .map(|(start, end, tempo)| (start, end, tempo.bpm()))
+            .collect::<Vec<_>>(),
+        [(0, 480, 100), (480, 960, 120)]
+    );
+    assert_eq!(
+        import_segments::<Program>(&imported)
+            .into_iter()
+            .map(|(start, end, program)| (start, end, program.0))
+            .collect::<Vec<_>>(),
+        [(0, 960, 5)]
+    );
+    assert_eq!(import_segments::<PlayNote>(&imported), notes);
+
+    let parts = imported
+        .tree
+        .iter()
+        .filter_map(|n| n.value.segment.element_as::<Part>())
+        .collect::<Vec<_>>();
+    assert_eq!(parts.len(), 1);
+    assert_eq!(
+        parts[0]
+            .wrapped_element()
+            .and_then(|e| e.as_any().downcast_ref::<MidiTrack>()),
+        Some(&MidiTrack {
+            track: 0,
+            channel: 0,
+            name: None
+        })
+    );
+}
+
+#[test]
+fn import_events() {
+    use crate::elements::MidiTrack;
+    use midly::{Format, Header, MidiMessage, Timing::Metrical, TrackEventKind::Midi};
+    use redact_composer_core::elements::{Part, PlayNote};
+
+    let event = |delta: u32, kind| TrackEvent {
+        delta: delta.into(),
+        kind,
+    };
+    let note = |delta, channel: u8, key: u8, vel: u8| {
+        event(
+            delta,
+            Midi {
+                channel: channel.into(),
+                message: MidiMessage::NoteOn {
+                    key: key.into(),
+                    vel: vel.into(),
+                },
+            },
+        )
+    };
+    let smf = midly::Smf {
+        header: Header {
+            format: Format::SingleTrack,
+            timing: Metrical(96.into()),
+        },
+        tracks: vec![vec![
+            event(0, Meta(MetaMessage::TrackName(b"Keys"))),
+            event(0, Meta(MetaMessage::TimeSignature(3, 3, 24, 8))),
+            event(0, Meta(MetaMessage::KeySignature(-3, true))),
+            note(0, 0, 60, 100),
+            note(0, 9, 36, 127),
+            // Velocity 0 NoteOn is equivalent to NoteOff
+            note(96, 0, 60, 0),
+            note(0, 9, 36, 0),
+            event(0, Meta(MetaMessage::KeySignature(2, false))),
+            // Never ended
+            note(48, 0, 62, 90),
+            event(48, Meta(MetaMessage::EndOfTrack)),
+        ]],
+    };
+
+    let imported = MidiConverter::import(&smf).unwrap();
+    assert_eq!(imported.options.ticks_per_beat, 96);
+    assert_eq!(imported.tree.root().unwrap().value.segment.timing.end, 192);
+
+    let parts = imported
+        .tree
+        .iter()
+        .filter_map(|n| {
+            n.value.segment.element_as::<Part>().map(|part| {
+                let track = part
+                    .wrapped_element()
+                    .and_then(|e| e.as_any().downcast_ref::<MidiTrack>())
+                    .unwrap();
+                (
+                    track.channel,
+                    track.name.clone(),
+                    matches!(part.part_type(), redact_composer_core::PartType::Percussion),
+                    n.value.segment.timing,
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        parts,
+        [
+            (0, Some("Keys".to_string()), false, (0..192).into()),
+            (9, Some("Keys".to_string()), true, (0..96).into()),
+        ]
+    );
+
+    assert_eq!(
+        import_segments::<PlayNote>(&imported),
+        [
+            (
+                0,
+                96,
+                PlayNote {
+                    note: 60,
+                    velocity: 100
+                }
+            ),
+            (
+                144,
+                192,
+                PlayNote {
+                    note: 62,
+                    velocity: 90
+                }
+            ),
+            (
+                0,
+                96,
+                PlayNote {
+                    note: 36,
+                    velocity: 127
+                }
+            ),
+        ]
+    );
+
+    #[cfg(feature = "musical")]
+    {
+        use redact_composer_musical::{Key, NoteName, Scale, TimeSignature};
+
+        let time_signatures = import_segments::<TimeSignature>(&imported);
+        assert_eq!(time_signatures.len(), 1);
+        assert_eq!(time_signatures[0].2.beats_per_bar, 3);
+        assert_eq!(time_signatures[0].2.beat_length, 48);
+
+        assert_eq!(
+            import_segments::<Key>(&imported),
+            [
+                (0, 96, Key::from((NoteName::C, Scale::NaturalMinor))),
+                (96, 192, Key::from((NoteName::D, Scale::Major))),
+            ]
+        );
+    }
+}
+
+#[cfg(feature = "musical")]
+#[test]
+fn import_malformed_time_signatures() {
+    use midly::{Format, Header, Timing::Metrical};
+    use redact_composer_musical::TimeSignature;
+
+    let event = |delta: u32, kind| TrackEvent {
+        delta: delta.into(),
+        kind,
+    };
+    let smf = midly::Smf {
+        header: Header {
+            format: Format::SingleTrack,
+            timing: Metrical(480.into()),
+        },
+        tracks: vec![vec![
+            // 4/2^12: Shorter than a tick
+            event(0, Meta(MetaMessage::TimeSignature(4, 12, 24, 8))),
+            // 4/2^31 and 4/2^255: Overflowing
+            event(0, Meta(MetaMessage::TimeSignature(4, 31, 24, 8))),
+            event(0, Meta(MetaMessage::TimeSignature(4, 255, 24, 8))),
+            // 0/4: No beats
+            event(480, Meta(MetaMessage::TimeSignature(0, 2, 24, 8))),
+            event(480, Meta(MetaMessage::TimeSignature(3, 2, 24, 8))),
+            event(480, Meta(MetaMessage::EndOfTrack)),
+        ]],
+    };
+
+    let imported = MidiConverter::import(&smf).unwrap();
+    let time_signatures = import_segments::<TimeSignature>(&imported);
+    assert_eq!(time_signatures.len(), 1);
+    assert_eq!(time_signatures[0].0, 960);
+    assert_eq!(time_signatures[0].2.beats_per_bar, 3);
+    assert_eq!(time_signatures[0].2.beat_length, 480);
+}
+
+#[cfg(feature = "musical")]
+#[test]
+fn compose_with_imported_context() {
+    use crate::elements::Program;
+    use midly::{Format, Header, MidiMessage, Timing::Metrical, TrackEventKind::Midi};
+    use redact_composer_core::elements::{Part, PlayNote};
+    use redact_composer_core::render::{AdhocRenderer, RenderEngine};
+    use redact_composer_core::{Composer, ComposerOptions, IntoSegment};
+    use redact_composer_musical::TimeSignature;
+
+    let event = |delta: u32, kind| TrackEvent {
+        delta: delta.into(),
+        kind,
+    };
+    let note = |delta, key: u8, vel: u8| {
+        event(
+            delta,
+            Midi {
+                channel: 0.into(),
+                message: MidiMessage::NoteOn {
+                    key: key.into(),
+                    vel: vel.into(),
+                },
+            },
+        )
+    };
+    let smf = midly::Smf {
+        header: Header {
+            format: Format::SingleTrack,
+            timing: Metrical(960.into()),
+        },
+        tracks: vec![vec![
+            event(0, Meta(MetaMessage::TimeSignature(4, 2, 24, 8))),
+            note(0, 60, 100),
+            note(960, 60, 0),
+            event(0, Meta(MetaMessage::EndOfTrack)),
+        ]],
+    };
+    let reference = MidiConverter::import(&smf).unwrap();
+
+    let engine = RenderEngine::new()
+        + AdhocRenderer::<Composition>::new(|segment, _| {
+            Ok(vec![Part::instrument(Program(0)).over(segment)])
+        })
+        + AdhocRenderer::<Part>::new(|_, ctx| {
+            let reference_note = ctx
+                .find::<PlayNote>()
+                .within::<super::ImportedMidi>()
+                .require()?;
+            let note = reference_note.element.note + 4;
+
+            Ok(vec![PlayNote {
+                note,
+                velocity: 100,
+            }
+            .over(reference_note)])
+        });
+    let mut composer = Composer::from(engine);
+    composer.options = ComposerOptions::new().ticks_per_beat(480);
+    let composition = composer.compose_with_context(Composition.over(0..480), [reference]);
+
+    let context_nodes = composition
+        .tree
+        .iter()
+        .filter(|n| n.value.context)
+        .map(|n| n.idx)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        context_nodes,
+        (1..composition.tree.len() - 2).collect::<Vec<_>>()
+    );
+    assert!(composition.tree.iter().all(|n| n.value.rendered));
+    let time_signatures = import_segments::<TimeSignature>(&composition);
+    assert_eq!(time_signatures.len(), 1);
+    assert_eq!(time_signatures[0].2.beat_length, 480);
+
+    let smf = MidiConverter::convert(&composition);
+    let events = smf.tracks.iter().flatten().collect::<Vec<_>>();
+    assert!(events
+        .iter()
+        .any(|e| e.kind == Meta(MetaMessage::TimeSignature(4, 2, 24, 8))));
+
+    let notes = events
+        .iter()
+        .filter_map(|e| match e.kind {
+            Midi {
+                message: MidiMessage::NoteOn { key, vel },
+                ..
+            } => Some((key.as_int(), vel.as_int())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(notes, [(64, 100)]);
+}
+
+#[test]
+fn import_timecode_unsupported() {
+    use midly::{Format, Fps, Header, Timing::Timecode};
+
+    let smf = midly::Smf {
+        header: Header {
+            format: Format::SingleTrack,
+            timing: Timecode(Fps::Fps24, 4),
+        },
+        tracks: vec![],
+    };
+
+    assert!(matches!(
+        MidiConverter::import(&smf),
+        Err(super::ImportError::UnsupportedTiming)
+    ));
+}
+
+#[test]
+fn import_zero_ticks_per_beat() {
+    use midly::{Format, Header, Timing::Metrical};
+
+    let smf = midly::Smf {
+        header: Header {
+            format: Format::SingleTrack,
+            timing: Metrical(0.into()),
+        },
+        tracks: vec![],
+    };
+
+    assert!(matches!(
+        MidiConverter::import(&smf),
+        Err(super::ImportError::ZeroTicksPerBeat)
+    ));
+}
+
+#[test]
+fn rescale_automation_resolution() {
+    use crate::control::{AutomationTarget, Curve};
+    use crate::elements::Automation;
+    use redact_composer_core::timing::Rounding;
+
+    let automation = Automation::new(
+        AutomationTarget::PitchBend,
+        Curve::Linear { from: 0.0, to: 1.0 },
+    );
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    for (automation, parent) in [
+        (automation.clone().with_resolution(120), None),
+        (automation, Some(0)),
+    ] {
+        render_tree.insert(
+            RenderSegment {
+                segment: Segment::new(automation, 0..960),
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        );
+    }
+    let mut composition = redact_composer_core::Composition::new(Default::default(), render_tree);
+    composition.rescale(960, Rounding::Nearest);
+
+    let resolutions = import_segments::<Automation>(&composition)
+        .into_iter()
+        .map(|(_, _, automation)| automation.resolution)
+        .collect::<Vec<_>>();
+    assert_eq!(resolutions, [Some(240), None]);
+}
+
+#[test]
+fn control_events() {
+    use crate::control::{AutomationTarget, Curve};
+    use crate::elements::{Automation, ChannelPressure, ControlChange, PitchBend, PolyAftertouch};
+    use midly::{MidiMessage, TrackEventKind::Midi};
+    use redact_composer_core::elements::{Part, PlayNote};
+
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    let mut insert = |segment: Segment, parent: Option<usize>| {
+        render_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        )
+    };
+    let root = insert(Segment::new(Composition, 0..960), None);
+    let part = insert(
+        Segment::new(Part::instrument(Composition), 0..960),
+        Some(root),
+    );
+    insert(
+        Segment::new(
+            PlayNote {
+                note: 60,
+                velocity: 100,
+            },
+            0..480,
+        ),
+        Some(part),
+    );
+    insert(
+        Segment::new(ControlChange::sustain(true), 0..480),
+        Some(part),
+    );
+    insert(Segment::new(PitchBend(-8192), 240..480), Some(part));
+    insert(Segment::new(ChannelPressure(30), 240..480), Some(part));
+    insert(
+        Segment::new(
+            PolyAftertouch {
+                note: 60,
+                pressure: 40,
+            },
+            240..480,
+        ),
+        Some(part),
+    );
+    insert(
+        Segment::new(
+            Automation::new(
+                AutomationTarget::Control(ControlChange::VOLUME),
+                Curve::Linear {
+                    from: 0.0,
+                    to: 100.0,
+                },
+            )
+            .with_resolution(120),
+            480..960,
+        ),
+        Some(part),
+    );
+    let composition = redact_composer_core::Composition::new(Default::default(), render_tree);
+
+    let smf = MidiConverter::convert(&composition);
+    let mut tick = 0;
+    let messages = smf.tracks[0]
+        .iter()
+        .filter_map(|event| {
+            tick += event.delta.as_int();
+            match event.kind {
+                Midi { message, .. } => Some((tick, message)),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        messages,
+        [
+            (
+                0,
+                MidiMessage::Controller {
+                    controller: 64.into(),
+                    value: 127.into()
+                }
+            ),
+            (
+                0,
+                MidiMessage::NoteOn {
+                    key: 60.into(),
+                    vel: 100.into()
+                }
+            ),
+            (
+                240,
+                MidiMessage::PitchBend {
+                    bend: midly::PitchBend::min_raw_value()
+                }
+            ),
+            (240, MidiMessage::ChannelAftertouch { vel: 30.into() }),
+            (
+                240,
+                MidiMessage::Aftertouch {
+                    key: 60.into(),
+                    vel: 40.into()
+                }
+            ),
+            (
+                480,
+                MidiMessage::Controller {
+                    controller: 7.into(),
+                    value: 0.into()
+                }
+            ),
+            (
+                480,
+                MidiMessage::NoteOff {
+                    key: 60.into(),
+                    vel: 100.into()
+                }
+            ),
+            (
+                600,
+                MidiMessage::Controller {
+                    controller: 7.into(),
+                    value: 33.into()
+                }
+            ),
+            (
+                720,
+                MidiMessage::Controller {
+                    controller: 7.into(),
+                    value: 67.into()
+                }
+            ),
+            (
+                840,
+                MidiMessage::Controller {
+                    controller: 7.into(),
+                    value: 100.into()
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn control_events_out_of_range() {
+    use crate::control::{AutomationTarget, Curve};
+    use crate::elements::{Automation, ChannelPressure, ControlChange, PolyAftertouch};
+    use midly::{MidiMessage, TrackEventKind::Midi};
+    use redact_composer_core::elements::Part;
+
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    let mut insert = |segment: Segment, parent: Option<usize>| {
+        render_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        )
+    };
+    let root = insert(Segment::new(Composition, 0..960), None);
+    let part = insert(
+        Segment::new(Part::instrument(Composition), 0..960),
+        Some(root),
+    );
+    insert(
+        Segment::new(
+            ControlChange {
+                controller: ControlChange::VOLUME,
+                value: 200,
+            },
+            0..480,
+        ),
+        Some(part),
+    );
+    insert(Segment::new(ChannelPressure(128), 0..480), Some(part));
+    insert(
+        Segment::new(
+            PolyAftertouch {
+                note: 255,
+                pressure: 255,
+            },
+            0..480,
+        ),
+        Some(part),
+    );
+    insert(
+        Segment::new(
+            Automation::new(
+                AutomationTarget::Control(200),
+                Curve::Linear {
+                    from: 64.0,
+                    to: 64.0,
+                },
+            ),
+            480..960,
+        ),
+        Some(part),
+    );
+    let composition = redact_composer_core::Composition::new(Default::default(), render_tree);
+
+    let smf = MidiConverter::convert(&composition);
+    let messages = smf.tracks[0]
+        .iter()
+        .filter_map(|event| match event.kind {
+            Midi { message, .. } => Some(message),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // Clamped to 127, rather than wrapping around
+    assert_eq!(
+        messages,
+        [
+            MidiMessage::Controller {
+                controller: 7.into(),
+                value: 127.into()
+            },
+            MidiMessage::ChannelAftertouch { vel: 127.into() },
+            MidiMessage::Aftertouch {
+                key: 127.into(),
+                vel: 127.into()
+            },
+            MidiMessage::Controller {
+                controller: 127.into(),
+                value: 64.into()
+            },
+        ]
+    );
+}
+
+#[test]
+fn mix_events() {
+    use crate::elements::Mix;
+    use midly::{MidiMessage, TrackEventKind::Midi};
+    use redact_composer_core::elements::Part;
+
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    let mut insert = |segment: Segment, parent: Option<usize>| {
+        render_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        )
+    };
+    let root = insert(Segment::new(Composition, 0..960), None);
+    let part = insert(
+        Segment::new(Part::instrument(Composition), 480..960),
+        Some(root),
+    );
+    insert(
+        Segment::new(Mix::new().volume(90).pan(0).reverb(40).chorus(10), 480..960),
+        Some(part),
+    );
+    let composition = redact_composer_core::Composition::new(Default::default(), render_tree);
+
+    let smf = MidiConverter::convert(&composition);
+    let mut tick = 0;
+    let controls = smf.tracks[0]
+        .iter()
+        .filter_map(|event| {
+            tick += event.delta.as_int();
+            match event.kind {
+                Midi {
+                    message: MidiMessage::Controller { controller, value },
+                    ..
+                } => Some((tick, controller.as_int(), value.as_int())),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        controls,
+        [(480, 7, 90), (480, 10, 0), (480, 91, 40), (480, 93, 10)]
+    );
+}
+
+#[test]
+fn meta_events() {
+    use crate::elements::{Lyric, Marker};
+    use midly::TrackEventKind;
+    use redact_composer_core::elements::Part;
+
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    let mut insert = |segment: Segment, parent: Option<usize>| {
+        render_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        )
+    };
+    let root = insert(Segment::new(Composition, 0..960), None);
+    insert(Segment::new(Marker("Verse".into()), 0..480), Some(root));
+    insert(Segment::new(Marker("Chorus".into()), 480..960), Some(root));
+    let vocals = insert(
+        Segment::new(Part::instrument(Composition), 0..960).named("Vocals".into()),
+        Some(root),
+    );
+    insert(Segment::new(Lyric("la".into()), 240..480), Some(vocals));
+    insert(
+        Segment::new(Part::instrument(Composition), 0..960).named("Bass".into()),
+        Some(root),
+    );
+    let composition = redact_composer_core::Composition::new(Default::default(), render_tree);
+
+    let smf = MidiConverter::convert(&composition);
+    let meta_events = smf
+        .tracks
+        .iter()
+        .map(|track| {
+            let mut tick = 0;
+            track
+                .iter()
+                .filter_map(|event| {
+                    tick += event.delta.as_int();
+                    match event.kind {
+                        TrackEventKind::Meta(
+                            MetaMessage::Tempo(..)
+                            | MetaMessage::TimeSignature(..)
+                            | MetaMessage::KeySignature(..)
+                            | MetaMessage::EndOfTrack,
+                        ) => None,
+                        TrackEventKind::Meta(message) => Some((tick, message)),
+                        _ => None,
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        meta_events,
+        [
+            vec![
+                (0, MetaMessage::TrackName(b"Vocals")),
+                (0, MetaMessage::Marker(b"Verse")),
+                (240, MetaMessage::Lyric(b"la")),
+                (480, MetaMessage::Marker(b"Chorus")),
+            ],
+            vec![(0, MetaMessage::TrackName(b"Bass"))],
+        ]
+    );
+}
+
+#[cfg(feature = "musical")]
+#[test]
+fn signature_events() {
+    use redact_composer_core::elements::Part;
+    use redact_composer_musical::{Key, Mode::Dorian, NoteName::*, Scale::*, TimeSignature};
+
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    let mut insert = |segment: Segment, parent: Option<usize>| {
+        render_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        )
+    };
+    let beat = STANDARD_BEAT_LENGTH;
+    let root = insert(Segment::new(Composition, 0..beat * 16), None);
+    let time_signature = |beats_per_bar, beat_length| TimeSignature {
+        beats_per_bar,
+        beat_length,
+    };
+    let outer = insert(
+        Segment::new(time_signature(4, beat), 0..beat * 8),
+        Some(root),
+    );
+    // Overrides the outer time signature, since it starts at the same time and is deeper
+    insert(
+        Segment::new(time_signature(6, beat / 2), 0..beat * 8),
+        Some(outer),
+    );
+    // Not representable (beat isn't a power of two fraction of a whole note)
+    insert(
+        Segment::new(time_signature(5, beat / 3), beat * 8..beat * 12),
+        Some(root),
+    );
+    insert(Segment::new(Key::from((E, Major)), 0..beat * 4), Some(root));
+    insert(
+        Segment::new(Key::from((D, NaturalMinor)), beat * 4..beat * 8),
+        Some(root),
+    );
+    insert(
+        Segment::new(Key::from((F, Major, Dorian)), beat * 8..beat * 12),
+        Some(root),
+    );
+    insert(
+        Segment::new(Key::from((Fs, Major)), beat * 12..beat * 16),
+        Some(root),
+    );
+    insert(
+        Segment::new(Part::instrument(Composition), 0..beat * 16),
+        Some(root),
+    );
+    let composition = redact_composer_core::Composition::new(Default::default(), render_tree);
+
+    let smf = MidiConverter::convert(&composition);
+    let mut tick = 0;
+    let signatures = smf.tracks[0]
+        .iter()
+        .filter_map(|event| {
+            tick += event.delta.as_int();
+            match event.kind {
+                Meta(
+                    message @ (MetaMessage::TimeSignature(..) | MetaMessage::KeySignature(..)),
+                ) => Some((tick, message)),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let beat = beat as u32;
+    assert_eq!(
+        signatures,
+        [
+            (0, MetaMessage::TimeSignature(6, 3, 12, 8)),
+            (0, MetaMessage::KeySignature(4, false)),
+            (beat * 4, MetaMessage::KeySignature(-1, true)),
+            // F Dorian (the second mode of Eb major, with a minor third)
+            (beat * 8, MetaMessage::KeySignature(-3, true)),
+            (beat * 12, MetaMessage::KeySignature(-6, false)),
+        ]
+    );
+}
+
+#[test]
+fn channel_strategies() {
+    use super::{ChannelDecision, ChannelStrategy, MidiChannel, MidiConverterOptions, SmfFormat};
+    use crate::elements::Program;
+    use midly::{MidiMessage, TrackEventKind};
+    use redact_composer_core::elements::{Part, PlayNote};
+
+    // 17 concurrent instrument parts, as (program, note timing)
+    let parts = (0..15)
+        .map(|program| (program, 0..480))
+        .chain([(0, 0..480), (40, 480..960)])
+        .collect::<Vec<_>>();
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    let mut insert = |segment: Segment, parent: Option<usize>| {
+        render_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        )
+    };
+    let root = insert(Segment::new(Composition, 0..960), None);
+    let part_idxs = parts
+        .into_iter()
+        .map(|(program, note_timing)| {
+            let part = insert(
+                Segment::new(Part::instrument(Composition), 0..960),
+                Some(root),
+            );
+            insert(Segment::new(Program(program), 0..960), Some(part));
+            insert(
+                Segment::new(
+                    PlayNote {
+                        note: 60,
+                        velocity: 100,
+                    },
+                    note_timing,
+                ),
+                Some(part),
+            );
+
+            part
+        })
+        .collect::<Vec<_>>();
+    let composition = redact_composer_core::Composition::new(Default::default(), render_tree);
+    let convert = |channel_strategy| {
+        MidiConverter::convert_with_options(
+            &composition,
+            &MidiConverterOptions::new().channel_strategy(channel_strategy),
+        )
+    };
+    let channel = |port, channel| MidiChannel { port, channel };
+    let program_changes = |track: &[TrackEvent]| {
+        let mut tick = 0;
+        track
+            .iter()
+            .filter_map(|event| {
+                tick += event.delta.as_int();
+                match event.kind {
+                    TrackEventKind::Midi {
+                        message: MidiMessage::ProgramChange { program },
+                        ..
+                    } => Some((tick, program.as_int())),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let (smf, report) = convert(ChannelStrategy::Drop);
+    assert_eq!(smf.tracks.len(), 15);
+    assert_eq!(report.dropped().collect::<Vec<_>>(), part_idxs[15..]);
+    assert_eq!(
+        report.channels[9].1,
+        ChannelDecision::Exclusive(channel(0, 10))
+    );
+    assert_eq!(MidiConverter::convert(&composition).tracks.len(), 15);
+
+    let (smf, report) = convert(ChannelStrategy::Ports);
+    assert_eq!(smf.tracks.len(), 17);
+    assert_eq!(report.ports(), 2);
+    assert_eq!(
+        report.channels[15].1,
+        ChannelDecision::Exclusive(channel(1, 0))
+    );
+    assert_eq!(
+        report.channels[16].1,
+        ChannelDecision::Exclusive(channel(1, 1))
+    );
+    assert!(smf.tracks[16].contains(&TrackEvent {
+        delta: 0.into(),
+        kind: Meta(MetaMessage::MidiPort(1.into()))
+    }));
+
+    // A single track can't address multiple ports, so falls back to dropping parts
+    let (smf, report) = MidiConverter::convert_with_options(
+        &composition,
+        &MidiConverterOptions::new()
+            .channel_strategy(ChannelStrategy::Ports)
+            .format(SmfFormat::SingleTrack),
+    );
+    assert_eq!(report.channel_strategy, ChannelStrategy::Drop);
+    assert_eq!(report.ports(), 1);
+    assert_eq!(report.dropped().collect::<Vec<_>>(), part_idxs[15..]);
+    assert_eq!(smf.tracks.len(), 1);
+    assert!(!smf.tracks[0]
+        .iter()
+        .any(|event| matches!(event.kind, Meta(MetaMessage::MidiPort(_)))));
+
+    let (smf, report) = convert(ChannelStrategy::MergePrograms);
+    assert_eq!(smf.tracks.len(), 16);
+    assert_eq!(
+        report.channels[15].1,
+        ChannelDecision::Merged {
+            channel: channel(0, 0),
+            with: part_idxs[0]
+        }
+    );
+    assert_eq!(report.channels[16].1, ChannelDecision::Dropped);
+
+    let (smf, report) = convert(ChannelStrategy::Steal);
+    assert_eq!(smf.tracks.len(), 16);
+    assert_eq!(report.channels[15].1, ChannelDecision::Dropped);
+    assert_eq!(
+        report.channels[16].1,
+        ChannelDecision::Shared {
+            channel: channel(0, 0),
+            with: part_idxs[0]
+        }
+    );
+    // Program changes between the sharing parts' notes
+    assert_eq!(program_changes(&smf.tracks[0]), [(0, 0)]);
+    assert_eq!(program_changes(&smf.tracks[15]), [(480, 40)]);
+    assert_eq!(program_changes(&smf.tracks[1]), [(0, 1)]);
+}
+
+#[test]
+fn converter_options() {
+    use super::{EventOrdering, MidiConverterOptions, NoteOffStyle, SmfFormat};
+    use crate::elements::ControlChange;
+    use midly::{Format, MidiMessage, Smf, TrackEventKind};
+    use redact_composer_core::elements::{Part, PlayNote};
+
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    let mut insert = |segment: Segment, parent: Option<usize>| {
+        render_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        )
+    };
+    let note = PlayNote {
+        note: 60,
+        velocity: 100,
+    };
+    let root = insert(Segment::new(Composition, 0..960), None);
+    let drums = insert(
+        Segment::new(Part::percussion(Composition), 0..960),
+        Some(root),
+    );
+    insert(Segment::new(note, 0..480), Some(drums));
+    let piano = insert(
+        Segment::new(Part::instrument(Composition), 0..960),
+        Some(root),
+    );
+    insert(Segment::new(note, 480..960), Some(piano));
+    insert(
+        Segment::new(ControlChange::volume(90), 480..960),
+        Some(piano),
+    );
+    let composition = redact_composer_core::Composition::new(Default::default(), render_tree);
+    fn timed_events<'a>(track: &[TrackEvent<'a>]) -> Vec<(u32, TrackEventKind<'a>)> {
+        let mut tick = 0;
+        track
+            .iter()
+            .map(|event| {
+                tick += event.delta.as_int();
+                (tick, event.kind)
+            })
+            .collect()
+    }
+    let midi = |channel: u8, message| TrackEventKind::Midi {
+        channel: channel.into(),
+        message,
+    };
+
+    // Defaults
+    let (smf, _) = MidiConverter::convert_with_options(&composition, &Default::default());
+    assert_eq!(smf.header.format, Format::Parallel);
+    assert_eq!(
+        timed_events(&smf.tracks[1]),
+        [
+            (
+                480,
+                midi(
+                    0,
+                    MidiMessage::Controller {
+                        controller: ControlChange::VOLUME.into(),
+                        value: 90.into()
+                    }
+                )
+            ),
+            (
+                480,
+                midi(
+                    0,
+                    MidiMessage::NoteOn {
+                        key: 60.into(),
+                        vel: 100.into()
+                    }
+                )
+            ),
+            (
+                960,
+                midi(
+                    0,
+                    MidiMessage::NoteOff {
+                        key: 60.into(),
+                        vel: 100.into()
+                    }
+                )
+            ),
+            (960, Meta(MetaMessage::EndOfTrack)),
+        ]
+    );
+
+    let options = MidiConverterOptions::new()
+        .default_tempo(90)
+        .drum_channels([10])
+        .format(SmfFormat::SingleTrack)
+        .note_off_style(NoteOffStyle::NoteOnZeroVelocity)
+        .event_ordering(EventOrdering::TreeOrder);
+    let (smf, _) = MidiConverter::convert_with_options(&composition, &options);
+    assert_eq!(smf.header.format, Format::SingleTrack);
+    assert_eq!(smf.tracks.len(), 1);
+    assert_eq!(
+        timed_events(&smf.tracks[0]),
+        [
+            (0, Meta(MetaMessage::Tempo(666_666.into()))),
+            (
+                0,
+                midi(
+                    10,
+                    MidiMessage::NoteOn {
+                        key: 60.into(),
+                        vel: 100.into()
+                    }
+                )
+            ),
+            (
+                480,
+                midi(
+                    10,
+                    MidiMessage::NoteOn {
+                        key: 60.into(),
+                        vel: 0.into()
+                    }
+                )
+            ),
+            (
+                480,
+                midi(
+                    0,
+                    MidiMessage::NoteOn {
+                        key: 60.into(),
+                        vel: 100.into()
+                    }
+                )
+            ),
+            (
+                480,
+                midi(
+                    0,
+                    MidiMessage::Controller {
+                        controller: ControlChange::VOLUME.into(),
+                        value: 90.into()
+                    }
+                )
+            ),
+            (
+                960,
+                midi(
+                    0,
+                    MidiMessage::NoteOn {
+                        key: 60.into(),
+                        vel: 0.into()
+                    }
+                )
+            ),
+            (960, Meta(MetaMessage::EndOfTrack)),
+        ]
+    );
+
+    // Running status omits the repeated status byte of the consecutive channel 10 NoteOns
+    let mut running_status_bytes = vec![];
+    MidiConverter::write(&smf, &options, &mut running_status_bytes).unwrap();
+    let mut full_status_bytes = vec![];
+    MidiConverter::write(&smf, &options.running_status(false), &mut full_status_bytes).unwrap();
+    assert_eq!(full_status_bytes.len(), running_status_bytes.len() + 1);
+    for bytes in [&running_status_bytes, &full_status_bytes] {
+        let parsed = Smf::parse(bytes).unwrap();
+        assert_eq!(parsed.header, smf.header);
+        assert_eq!(parsed.tracks, smf.tracks);
+    }
+}
+
+#[test]
+fn note_overlaps() {
+    use super::{MidiChannel, MidiConverterOptions, NoteOverlap, NoteOverlapPolicy};
+    use midly::{MidiMessage, TrackEventKind};
+    use redact_composer_core::elements::{Part, PlayNote};
+
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    let mut insert = |segment: Segment, parent: Option<usize>| {
+        render_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        )
+    };
+    let note = PlayNote {
+        note: 60,
+        velocity: 100,
+    };
+    let root = insert(Segment::new(Composition, 0..960), None);
+    let part = insert(
+        Segment::new(Part::instrument(Composition), 0..960),
+        Some(root),
+    );
+    // Starts exactly when the first note ends
+    insert(Segment::new(note, 480..720), Some(part));
+    let first = insert(Segment::new(note, 0..480), Some(part));
+    let second = insert(Segment::new(note, 240..360), Some(part));
+    // Notes without duration are skipped
+    let zero_length = insert(Segment::new(note, 800..800), Some(part));
+    let inverted = insert(Segment::new(note, 900..850), Some(part));
+    let composition = redact_composer_core::Composition::new(Default::default(), render_tree);
+    let note_events = |policy| {
+        let (smf, report) = MidiConverter::convert_with_options(
+            &composition,
+            &MidiConverterOptions::new().note_overlap_policy(policy),
+        );
+        let mut tick = 0;
+        let events = smf.tracks[0]
+            .iter()
+            .filter_map(|event| {
+                tick += event.delta.as_int();
+                match event.kind {
+                    TrackEventKind::Midi {
+                        message: MidiMessage::NoteOn { .. },
+                        ..
+                    } => Some((tick, true)),
+                    TrackEventKind::Midi {
+                        message: MidiMessage::NoteOff { .. },
+                        ..
+                    } => Some((tick, false)),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        (events, report)
+    };
+
+    let (events, report) = note_events(NoteOverlapPolicy::Truncate);
+    assert_eq!(
+        events,
+        [
+            (0, true),
+            (240, false),
+            (240, true),
+            (360, false),
+            (480, true),
+            (720, false)
+        ]
+    );
+    assert_eq!(
+        report.note_overlaps,
+        [NoteOverlap {
+            channel: MidiChannel {
+                port: 0,
+                channel: 0
+            },
+            note: 60,
+            earlier: first,
+            later: second,
+            tick: 240,
+        }]
+    );
+    assert_eq!(report.skipped_notes, [zero_length, inverted]);
+
+    let (events, report) = note_events(NoteOverlapPolicy::Merge);
+    assert_eq!(events, [(0, true), (480, false), (480, true), (720, false)]);
+    assert_eq!(report.note_overlaps.len(), 1);
+
+    let (events, _) = note_events(NoteOverlapPolicy::Retrigger);
+    assert_eq!(
+        events,
+        [
+            (0, true),
+            (240, false),
+            (240, true),
+            (480, false),
+            (480, true),
+            (720, false)
+        ]
+    );
+}
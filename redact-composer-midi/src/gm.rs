@@ -205,6 +205,7 @@ impl Instrument {
         AdhocRenderer::<Self>::new(|segment, _| {
             Result::Ok(vec![Program::from(*segment.element).over(segment.timing)])
         })
+        .producing::<Program>()
     }
 }
 
@@ -537,6 +538,7 @@ impl DrumHit {
                 segment.timing,
             )])
         })
+        .producing::<PlayNote>()
     }
 }
 
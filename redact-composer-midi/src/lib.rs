@@ -10,6 +10,15 @@ pub mod convert;
 /// General Midi Level 1 types and elements.
 pub mod gm;
 
+/// Channel control elements (control changes, mix, pitch bend, aftertouch and automation).
+pub mod control;
+
+/// Meta event elements (markers and lyrics).
+pub mod meta;
+
+/// Real-time MIDI playback of [`Composition`]s.
+pub mod playback;
+
 use redact_composer_core::derive::Element;
 use redact_composer_core::render::{AdhocRenderer, RenderEngine, Renderer};
 use redact_composer_core::IntoSegment;
@@ -19,6 +28,11 @@ use serde::{Deserialize, Serialize};
 
 /// Elements implementing [`Element`].
 pub mod elements {
+    pub use super::control::{
+        Automation, ChannelPressure, ControlChange, Mix, PitchBend, PolyAftertouch,
+    };
+    pub use super::convert::{ImportedMidi, MidiTrack};
+    pub use super::meta::{Lyric, Marker};
     pub use super::{DrumKit, Program};
 }
 
@@ -28,7 +42,7 @@ pub fn renderers() -> RenderEngine {
 }
 
 /// A program number (instrument) that should play during a [`Part`].
-#[derive(Element, Debug)]
+#[derive(Element, Debug, Copy, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Program(pub u8);
 
@@ -43,6 +57,7 @@ impl DrumKit {
         AdhocRenderer::<Self>::new(|segment, _| {
             Ok(vec![Program::from(segment.element).over(segment.timing)])
         })
+        .producing::<Program>()
     }
 }
 
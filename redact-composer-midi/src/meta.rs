@@ -0,0 +1,26 @@
+use redact_composer_core::derive::Element;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Labels a point in time (such as the start of a section) via a
+/// [`MetaMessage::Marker`](midly::MetaMessage::Marker) at the start of its segment.
+///
+/// Markers outside of any [`Part`] are written to the first track, otherwise to their [`Part`]'s
+/// track.
+///
+/// [`Part`]: redact_composer_core::elements::Part
+#[derive(Element, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Marker(pub String);
+
+/// A lyric (typically a syllable) sung at the start of its segment, sent as a
+/// [`MetaMessage::Lyric`](midly::MetaMessage::Lyric).
+///
+/// Lyrics outside of any [`Part`] are written to the first track, otherwise to their [`Part`]'s
+/// track.
+///
+/// [`Part`]: redact_composer_core::elements::Part
+#[derive(Element, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Lyric(pub String);
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt::{Debug, Formatter};
+
+use alsa::seq::{EvCtrl, EvNote, Event, EventType, PortCap, PortType, Seq};
+use alsa::Direction;
+use log::info;
+use midly::MidiMessage;
+
+use super::{MidiSink, PlaybackError, PlaybackEvent, Result};
+
+/// A [`MidiSink`] sending events through the ALSA sequencer (Linux), from virtual output ports
+/// which other sequencer clients (such as software synthesizers) can subscribe to, e.g. via
+/// `aconnect`. A virtual port is created for each MIDI port used.
+pub struct AlsaSink {
+    seq: Seq,
+    client_name: String,
+    // Virtual ALSA port ids, keyed by MIDI port
+    ports: HashMap<u8, i32>,
+}
+
+impl Debug for AlsaSink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AlsaSink")
+            .field("client_name", &self.client_name)
+            .field("ports", &self.ports)
+            .finish()
+    }
+}
+
+impl AlsaSink {
+    /// Opens the ALSA sequencer as a client with the given name.
+    pub fn new(client_name: &str) -> Result<AlsaSink> {
+        let seq = Seq::open(None, Some(Direction::Playback), false)?;
+        seq.set_client_name(&Self::c_string(client_name)?)?;
+        let mut sink = AlsaSink {
+            seq,
+            client_name: client_name.to_string(),
+            ports: HashMap::new(),
+        };
+        // Create the first port up front, so it can be subscribed to before playback
+        sink.port(0)?;
+
+        info!(
+            "Opened ALSA sequencer client '{}' (id: {:?}).",
+            client_name,
+            sink.seq.client_id()?
+        );
+
+        Ok(sink)
+    }
+
+    fn port(&mut self, midi_port: u8) -> Result<i32> {
+        if let Some(port) = self.ports.get(&midi_port) {
+            return Ok(*port);
+        }
+
+        let port = self.seq.create_simple_port(
+            &Self::c_string(&format!("{} {}", self.client_name, midi_port))?,
+            PortCap::READ | PortCap::SUBS_READ,
+            PortType::MIDI_GENERIC | PortType::APPLICATION,
+        )?;
+        self.ports.insert(midi_port, port);
+
+        Ok(port)
+    }
+
+    fn c_string(name: &str) -> Result<CString> {
+        CString::new(name).map_err(|err| PlaybackError::Sink(err.to_string()))
+    }
+}
+
+impl MidiSink for AlsaSink {
+    fn send(&mut self, event: &PlaybackEvent) -> Result<()> {
+        let channel = event.channel.channel;
+        let note = |note: u8, velocity: u8| EvNote {
+            channel,
+            note,
+            velocity,
+            ..Default::default()
+        };
+        let ctrl = |param: u32, value: i32| EvCtrl {
+            channel,
+            param,
+            value,
+        };
+
+        let mut alsa_event = match event.message {
+            MidiMessage::NoteOn { key, vel } => {
+                Event::new(EventType::Noteon, &note(key.as_int(), vel.as_int()))
+            }
+            MidiMessage::NoteOff { key, vel } => {
+                Event::new(EventType::Noteoff, &note(key.as_int(), vel.as_int()))
+            }
+            MidiMessage::Aftertouch { key, vel } => {
+                Event::new(EventType::Keypress, &note(key.as_int(), vel.as_int()))
+            }
+            MidiMessage::Controller { controller, value } => Event::new(
+                EventType::Controller,
+                &ctrl(controller.as_int().into(), value.as_int().into()),
+            ),
+            MidiMessage::ProgramChange { program } => {
+                Event::new(EventType::Pgmchange, &ctrl(0, program.as_int().into()))
+            }
+            MidiMessage::ChannelAftertouch { vel } => {
+                Event::new(EventType::Chanpress, &ctrl(0, vel.as_int().into()))
+            }
+            MidiMessage::PitchBend { bend } => {
+                Event::new(EventType::Pitchbend, &ctrl(0, bend.as_int().into()))
+            }
+        };
+
+        alsa_event.set_source(self.port(event.channel.port)?);
+        alsa_event.set_subs();
+        alsa_event.set_direct();
+        self.seq.event_output_direct(&mut alsa_event)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use log::{debug, info};
+use midly::{MetaMessage, MidiMessage, TrackEventKind};
+use redact_composer_core::{timing::TempoMap, Composition};
+use thiserror::Error;
+
+use crate::convert::{MidiChannel, MidiConverter, MidiConverterOptions};
+
+#[cfg(feature = "alsa")]
+mod alsa;
+mod sink;
+#[cfg(feature = "alsa")]
+pub use self::alsa::AlsaSink;
+pub use sink::{MidiSink, RecordingSink};
+
+#[cfg(test)]
+mod test;
+
+/// Result type which may produce [`PlaybackError`].
+pub type Result<T, E = PlaybackError> = std::result::Result<T, E>;
+
+/// Error type for MIDI playback.
+#[derive(Debug, Error)]
+pub enum PlaybackError {
+    /// A [`MidiSink`] failed to send an event.
+    #[error("Unable to send MIDI event: {0}")]
+    Sink(String),
+    /// The ALSA sequencer returned an error.
+    #[cfg(feature = "alsa")]
+    #[error("ALSA sequencer error: {0}")]
+    Alsa(#[from] ::alsa::Error),
+    /// The real-time playback thread panicked.
+    #[error("The playback thread panicked.")]
+    ThreadPanicked,
+}
+
+/// A MIDI channel message sent at a playback position.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PlaybackEvent {
+    /// The playback position (time since the start of the composition).
+    pub time: Duration,
+    /// The channel the message is sent to.
+    pub channel: MidiChannel,
+    /// The message.
+    pub message: MidiMessage,
+}
+
+/// Plays a [`Composition`] by scheduling the events produced by [`MidiConverter`] against its
+/// [`TempoMap`], sending them to a [`MidiSink`].
+///
+/// Playback can be driven manually via [`advance`](Playback::advance) (useful for tests, or
+/// when synchronizing with another clock), or in real-time on a separate thread via
+/// [`play`](Playback::play).
+/// ```
+/// # use std::time::Duration;
+/// # use redact_composer_core::Composition;
+/// # use redact_composer_midi::playback::{Playback, RecordingSink};
+/// # let composition = Composition::new(Default::default(), Default::default());
+/// let mut playback = Playback::new(&composition, RecordingSink::new());
+/// playback.set_loop(Some(Duration::from_secs(4)..Duration::from_secs(8)));
+///
+/// let handle = playback.play();
+/// // ...
+/// handle.seek(Duration::from_secs(2));
+/// // ...
+/// let playback = handle.stop().unwrap();
+/// ```
+///
+/// When the position jumps (by seeking or looping), sounding notes are ended, and each channel's
+/// latest program, controller values, pitch bend and pressure from before the new position are
+/// re-sent.
+#[derive(Debug)]
+pub struct Playback<S> {
+    sink: S,
+    events: Vec<PlaybackEvent>,
+    duration: Duration,
+    position: Duration,
+    // Index of the first event at or after `position`, which has not been sent
+    next: usize,
+    loop_range: Option<Range<Duration>>,
+    sounding: HashSet<(MidiChannel, u8)>,
+}
+
+impl<S: MidiSink> Playback<S> {
+    /// Prepares playback of a [`Composition`] to a [`MidiSink`], converted with the default
+    /// [`MidiConverterOptions`].
+    pub fn new(composition: &Composition, sink: S) -> Playback<S> {
+        Self::with_options(composition, &MidiConverterOptions::default(), sink)
+    }
+
+    /// Prepares playback of a [`Composition`] to a [`MidiSink`], converted with custom
+    /// [`MidiConverterOptions`].
+    pub fn with_options(
+        composition: &Composition,
+        options: &MidiConverterOptions,
+        sink: S,
+    ) -> Playback<S> {
+        let (smf, _) = MidiConverter::convert_with_options(composition, options);
+        let tempo_map = TempoMap::from_tree_with_default(
+            &composition.tree,
+            composition.options.ticks_per_beat,
+            options.default_tempo,
+        );
+        // Saturates times too large to be represented
+        let time_at = |tick: u32| {
+            Duration::try_from_secs_f64(tempo_map.seconds_at(tick as i32).max(0.0))
+                .unwrap_or(Duration::MAX)
+        };
+
+        let mut end = 0;
+        let mut timed_messages = vec![];
+        for track in &smf.tracks {
+            let (mut tick, mut port) = (0, 0);
+            for event in track {
+                tick += event.delta.as_int();
+                end = end.max(tick);
+                match event.kind {
+                    TrackEventKind::Meta(MetaMessage::MidiPort(midi_port)) => {
+                        port = midi_port.as_int()
+                    }
+                    TrackEventKind::Midi { channel, message } => timed_messages.push((
+                        tick,
+                        MidiChannel {
+                            port,
+                            channel: channel.as_int(),
+                        },
+                        message,
+                    )),
+                    _ => {}
+                }
+            }
+        }
+        // Note ends come first at the same time, even between tracks
+        timed_messages
+            .sort_by_key(|(tick, _, message)| (*tick, !MidiConverter::ends_note(message)));
+
+        let events = timed_messages
+            .into_iter()
+            .map(|(tick, channel, message)| PlaybackEvent {
+                time: time_at(tick),
+                channel,
+                message,
+            })
+            .collect::<Vec<_>>();
+        debug!("Prepared {:?} events for playback.", events.len());
+
+        Playback {
+            sink,
+            events,
+            duration: time_at(end),
+            position: Duration::ZERO,
+            next: 0,
+            loop_range: None,
+            sounding: HashSet::new(),
+        }
+    }
+
+    /// The total duration.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// The current playback position.
+    pub fn position(&self) -> Duration {
+        self.position
+    }
+
+    /// The events which will be sent during playback, ordered by time.
+    pub fn events(&self) -> &[PlaybackEvent] {
+        &self.events
+    }
+
+    /// The sink events are sent to.
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Consumes the playback, returning its sink.
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+
+    /// Whether the playback position has reached the end (outside of any loop).
+    pub fn is_finished(&self) -> bool {
+        self.loop_end().is_none()
+            && self.next == self.events.len()
+            && self.position >= self.duration
+    }
+
+    /// Sets (or clears, if `None`) the range to loop. Looping occurs whenever the playback reaches
+    /// the range's end from within the range. Empty ranges are ignored.
+    pub fn set_loop(&mut self, range: Option<Range<Duration>>) {
+        self.loop_range = range.filter(|r| r.start < r.end);
+    }
+
+    /// Moves the playback position, ending any sounding notes.
+    pub fn seek(&mut self, position: Duration) -> Result<()> {
+        self.silence()?;
+        self.position = position.min(self.duration);
+        self.next = self.events.partition_point(|e| e.time < self.position);
+
+        // Latest channel state from before the new position
+        let mut state = HashMap::new();
+        for (idx, event) in self.events[..self.next].iter().enumerate() {
+            let key = match event.message {
+                MidiMessage::ProgramChange { .. } => (0, 0),
+                MidiMessage::Controller { controller, .. } => (1, controller.as_int()),
+                MidiMessage::PitchBend { .. } => (2, 0),
+                MidiMessage::ChannelAftertouch { .. } => (3, 0),
+                _ => continue,
+            };
+            state.insert((event.channel, key), idx);
+        }
+        let mut state = state.into_values().collect::<Vec<_>>();
+        state.sort();
+
+        for idx in state {
+            let event = PlaybackEvent {
+                time: self.position,
+                ..self.events[idx]
+            };
+            self.send(event)?;
+        }
+
+        Ok(())
+    }
+
+    /// Advances the playback position by `elapsed`, sending the events passed along the way
+    /// (looping as needed).
+    pub fn advance(&mut self, elapsed: Duration) -> Result<()> {
+        let mut target = self.position + elapsed;
+
+        while let Some(loop_end) = self.loop_end().filter(|end| target >= *end) {
+            self.send_until(loop_end, false)?;
+            let loop_start = self.loop_range.as_ref().map_or(Duration::ZERO, |r| r.start);
+            target = loop_start + (target - loop_end);
+            self.seek(loop_start)?;
+        }
+
+        self.send_until(target, target >= self.duration)?;
+        self.position = target.min(self.duration);
+
+        Ok(())
+    }
+
+    /// Ends any sounding notes.
+    pub fn silence(&mut self) -> Result<()> {
+        let mut sounding = self.sounding.drain().collect::<Vec<_>>();
+        sounding.sort_by_key(|(channel, key)| (channel.port, channel.channel, *key));
+
+        for (channel, key) in sounding {
+            self.sink.send(&PlaybackEvent {
+                time: self.position,
+                channel,
+                message: MidiMessage::NoteOff {
+                    key: key.into(),
+                    vel: 0.into(),
+                },
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Starts real-time playback (from the current position) on a separate thread, returning a
+    /// [`PlaybackHandle`] to control it.
+    pub fn play(self) -> PlaybackHandle<S>
+    where
+        S: Send + 'static,
+    {
+        info!(
+            "Starting playback at {:?} (duration: {:?}).",
+            self.position, self.duration
+        );
+        let (commands, receiver) = mpsc::channel();
+        let mut playback = self;
+
+        let thread = thread::spawn(move || {
+            let mut last = Instant::now();
+            while let Some(wait) = playback.time_until_next() {
+                let command = receiver.recv_timeout(wait);
+                let now = Instant::now();
+                playback.advance(now.duration_since(last))?;
+                last = now;
+
+                match command {
+                    Ok(Command::Seek(position)) => playback.seek(position)?,
+                    Ok(Command::Loop(range)) => playback.set_loop(range),
+                    Ok(Command::Stop) | Err(RecvTimeoutError::Disconnected) => {
+                        playback.silence()?;
+                        break;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                }
+            }
+            info!("Playback stopped at {:?}.", playback.position);
+
+            Ok(playback)
+        });
+
+        PlaybackHandle { commands, thread }
+    }
+
+    // The end of the active loop, if the position is within the loop range.
+    fn loop_end(&self) -> Option<Duration> {
+        self.loop_range
+            .as_ref()
+            .filter(|r| self.position < r.end && r.start < self.duration)
+            .map(|r| r.end.min(self.duration))
+    }
+
+    // Time until the next event (or loop end) is due, or `None` if finished.
+    fn time_until_next(&self) -> Option<Duration> {
+        if self.is_finished() {
+            return None;
+        }
+
+        let next_time = self.events.get(self.next).map_or(self.duration, |e| e.time);
+        let next_time = self.loop_end().map_or(next_time, |end| next_time.min(end));
+
+        Some(next_time.saturating_sub(self.position))
+    }
+
+    // Sends the unsent events before `time` (or also at `time` if `inclusive`).
+    fn send_until(&mut self, time: Duration, inclusive: bool) -> Result<()> {
+        while let Some(event) = self.events.get(self.next).copied() {
+            if event.time > time || (event.time == time && !inclusive) {
+                break;
+            }
+            self.next += 1;
+            self.send(event)?;
+        }
+        self.position = self.position.max(time.min(self.duration));
+
+        Ok(())
+    }
+
+    fn send(&mut self, event: PlaybackEvent) -> Result<()> {
+        match event.message {
+            MidiMessage::NoteOn { key, .. } if !MidiConverter::ends_note(&event.message) => {
+                self.sounding.insert((event.channel, key.as_int()));
+            }
+            MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                self.sounding.remove(&(event.channel, key.as_int()));
+            }
+            _ => {}
+        }
+
+        self.sink.send(&event)
+    }
+}
+
+/// Controls real-time playback started via [`Playback::play`]. Dropping the handle stops
+/// playback.
+#[derive(Debug)]
+pub struct PlaybackHandle<S> {
+    commands: Sender<Command>,
+    thread: JoinHandle<Result<Playback<S>>>,
+}
+
+#[derive(Debug)]
+enum Command {
+    Seek(Duration),
+    Loop(Option<Range<Duration>>),
+    Stop,
+}
+
+impl<S> PlaybackHandle<S> {
+    /// Moves the playback position (see [`Playback::seek`]).
+    pub fn seek(&self, position: Duration) {
+        // Playback may have already finished, in which case there is nothing to do
+        let _ = self.commands.send(Command::Seek(position));
+    }
+
+    /// Sets (or clears) the range to loop (see [`Playback::set_loop`]).
+    pub fn set_loop(&self, range: Option<Range<Duration>>) {
+        let _ = self.commands.send(Command::Loop(range));
+    }
+
+    /// Whether playback has finished (or failed).
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stops playback, ending any sounding notes. Returns the [`Playback`] at the position it
+    /// was stopped, which can be [`play`](Playback::play)ed again to resume.
+    pub fn stop(self) -> Result<Playback<S>> {
+        let _ = self.commands.send(Command::Stop);
+
+        self.thread
+            .join()
+            .map_err(|_| PlaybackError::ThreadPanicked)?
+    }
+
+    /// Waits for playback to finish, returning the finished [`Playback`]. Never returns while
+    /// looping.
+    pub fn wait(self) -> Result<Playback<S>> {
+        let PlaybackHandle { commands, thread } = self;
+        let playback = thread.join().map_err(|_| PlaybackError::ThreadPanicked)?;
+        drop(commands);
+
+        playback
+    }
+}
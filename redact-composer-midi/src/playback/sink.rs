@@ -0,0 +1,39 @@
+use super::{PlaybackEvent, Result};
+
+#[allow(unused_imports)] // Imports used in doc comments only
+use super::Playback;
+
+/// A destination for MIDI events during [`Playback`].
+pub trait MidiSink {
+    /// Sends a MIDI event. Called as soon as the event is due during real-time playback.
+    fn send(&mut self, event: &PlaybackEvent) -> Result<()>;
+}
+
+impl<S: MidiSink + ?Sized> MidiSink for Box<S> {
+    fn send(&mut self, event: &PlaybackEvent) -> Result<()> {
+        (**self).send(event)
+    }
+}
+
+/// A [`MidiSink`] which records the events it receives (timestamped with their playback position),
+/// rather than sending them anywhere. Useful for tests.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingSink {
+    /// The received events, in the order received.
+    pub events: Vec<PlaybackEvent>,
+}
+
+impl RecordingSink {
+    /// Creates a [`RecordingSink`] without any recorded events.
+    pub fn new() -> RecordingSink {
+        RecordingSink::default()
+    }
+}
+
+impl MidiSink for RecordingSink {
+    fn send(&mut self, event: &PlaybackEvent) -> Result<()> {
+        self.events.push(*event);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+use std::time::{Duration, Instant};
+
+use midly::MidiMessage;
+use redact_composer_core::derive::Element;
+use redact_composer_core::elements::{Part, PlayNote};
+use redact_composer_core::render::{tree::Tree, RenderSegment};
+use redact_composer_core::timing::elements::Tempo;
+use redact_composer_core::Segment;
+use serde::{Deserialize, Serialize};
+
+use super::{Playback, RecordingSink};
+use crate::elements::{ControlChange, Program};
+
+#[derive(Element, Serialize, Deserialize, Debug)]
+struct Composition;
+
+// A part with program and volume set at the start, and two notes where the second is played at
+// half tempo (60 BPM).
+fn test_composition(notes: [(u8, std::ops::Range<i32>); 2]) -> redact_composer_core::Composition {
+    let mut render_tree: Tree<RenderSegment> = Tree::new();
+    let mut insert = |segment: Segment, parent: Option<usize>| {
+        render_tree.insert(
+            RenderSegment {
+                segment,
+                seed: 0,
+                rendered: true,
+                error: None,
+                context: false,
+            },
+            parent,
+        )
+    };
+    let end = notes[1].1.end;
+    let root = insert(Segment::new(Composition, 0..end), None);
+    insert(
+        Segment::new(Tempo::from_bpm(60), notes[1].1.clone()),
+        Some(root),
+    );
+    let part = insert(
+        Segment::new(Part::instrument(Composition), 0..end),
+        Some(root),
+    );
+    insert(Segment::new(Program(5), 0..end), Some(part));
+    insert(Segment::new(ControlChange::volume(90), 0..end), Some(part));
+    for (note, timing) in notes {
+        insert(
+            Segment::new(
+                PlayNote {
+                    note,
+                    velocity: 100,
+                },
+                timing,
+            ),
+            Some(part),
+        );
+    }
+
+    redact_composer_core::Composition::new(Default::default(), render_tree)
+}
+
+fn recorded(sink: &RecordingSink) -> Vec<(u128, MidiMessage)> {
+    sink.events
+        .iter()
+        .map(|event| (event.time.as_millis(), event.message))
+        .collect()
+}
+
+fn note_on(key: u8) -> MidiMessage {
+    MidiMessage::NoteOn {
+        key: key.into(),
+        vel: 100.into(),
+    }
+}
+
+fn note_off(key: u8, vel: u8) -> MidiMessage {
+    MidiMessage::NoteOff {
+        key: key.into(),
+        vel: vel.into(),
+    }
+}
+
+const PROGRAM: MidiMessage = MidiMessage::ProgramChange {
+    program: midly::num::u7::new(5),
+};
+const VOLUME: MidiMessage = MidiMessage::Controller {
+    controller: midly::num::u7::new(ControlChange::VOLUME),
+    value: midly::num::u7::new(90),
+};
+
+#[test]
+fn scheduled_playback() {
+    let composition = test_composition([(60, 0..480), (64, 480..960)]);
+    let mut playback = Playback::new(&composition, RecordingSink::new());
+    assert_eq!(playback.duration(), Duration::from_millis(1500));
+
+    playback.advance(Duration::from_millis(250)).unwrap();
+    assert_eq!(
+        recorded(playback.sink()),
+        [(0, PROGRAM), (0, VOLUME), (0, note_on(60))]
+    );
+
+    // Events exactly at the new position are not yet due
+    playback.advance(Duration::from_millis(250)).unwrap();
+    assert_eq!(playback.sink().events.len(), 3);
+
+    playback.advance(Duration::from_millis(1)).unwrap();
+    playback.advance(Duration::from_secs(10)).unwrap();
+    assert_eq!(
+        recorded(playback.sink())[3..],
+        [
+            (500, note_off(60, 100)),
+            (500, note_on(64)),
+            (1500, note_off(64, 100))
+        ]
+    );
+    assert_eq!(playback.position(), playback.duration());
+    assert!(playback.is_finished());
+}
+
+#[test]
+fn seek_and_loop() {
+    let composition = test_composition([(60, 0..480), (64, 480..960)]);
+    let mut playback = Playback::new(&composition, RecordingSink::new());
+    playback.set_loop(Some(Duration::from_millis(250)..Duration::from_secs(1)));
+
+    playback.advance(Duration::from_millis(300)).unwrap();
+    playback.advance(Duration::from_millis(800)).unwrap();
+    assert_eq!(playback.position(), Duration::from_millis(350));
+    assert_eq!(
+        recorded(playback.sink())[3..],
+        [
+            (500, note_off(60, 100)),
+            (500, note_on(64)),
+            // Looping back ends sounding notes, and restores the channel state
+            (1000, note_off(64, 0)),
+            (250, PROGRAM),
+            (250, VOLUME),
+        ]
+    );
+    assert!(!playback.is_finished());
+
+    playback.set_loop(None);
+    playback.seek(Duration::from_millis(1200)).unwrap();
+    playback.advance(Duration::from_secs(1)).unwrap();
+    assert_eq!(
+        recorded(playback.sink())[8..],
+        [(1200, PROGRAM), (1200, VOLUME), (1500, note_off(64, 100))]
+    );
+    assert!(playback.is_finished());
+}
+
+#[test]
+fn real_time_playback() {
+    // 0.1s and 0.2s long notes
+    let composition = test_composition([(60, 0..96), (64, 96..192)]);
+    let playback = Playback::new(&composition, RecordingSink::new());
+    let expected_events = playback.events().to_vec();
+
+    let start = Instant::now();
+    let playback = playback.play().wait().unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert!(playback.is_finished());
+    assert_eq!(playback.into_sink().events, expected_events);
+
+    // Stopping ends the sounding note
+    let composition = test_composition([(60, 0..4800), (64, 4800..9600)]);
+    let handle = Playback::new(&composition, RecordingSink::new()).play();
+    std::thread::sleep(Duration::from_millis(50));
+    let playback = handle.stop().unwrap();
+    assert!(!playback.is_finished());
+    assert!(playback.position() < Duration::from_secs(5));
+    assert_eq!(
+        recorded(playback.sink())
+            .into_iter()
+            .map(|(_, message)| message)
+            .collect::<Vec<_>>(),
+        [PROGRAM, VOLUME, note_on(60), note_off(60, 0)]
+    );
+}
+
+#[test]
+fn zero_bpm_playback() {
+    use crate::convert::MidiConverterOptions;
+
+    let composition = test_composition([(60, 0..480), (64, 480..960)]);
+    let options = MidiConverterOptions::new().default_tempo(0);
+    let playback = Playback::with_options(&composition, &options, RecordingSink::new());
+
+    // Treated as 1 BPM
+    assert_eq!(playback.duration(), Duration::from_secs(61));
+}
@@ -0,0 +1,348 @@
+use crate::{Chord, ChordShape, Key, Note, PitchClass, PitchClassCollection, Scale};
+
+#[cfg(feature = "redact-composer")]
+use redact_composer_core::{elements::PlayNote, timing::Timing, IntoSegment, Segment, SegmentRef};
+
+/// Krumhansl-Kessler major key profile, starting from the tonic.
+const MAJOR_PROFILE: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+/// Krumhansl-Kessler minor key profile, starting from the tonic.
+const MINOR_PROFILE: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// A detected value, along with a confidence score in `0.0..=1.0`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Detection<T> {
+    /// The detected value.
+    pub value: T,
+    /// How well the value fits the analyzed notes, from `0.0` (not at all) to `1.0` (perfectly).
+    pub confidence: f32,
+}
+
+/// A weighted pitch class distribution (along with the lowest sounding note), used to detect the
+/// most likely [`Chord`] or [`Key`] of a set of notes.
+/// ```
+/// # use redact_composer_musical::{Chord, ChordShape::maj7, Note, NoteName::{C, E, G, B}, PitchProfile};
+/// let profile = PitchProfile::from_notes([(C, 3), (E, 4), (G, 4), (B, 4)].map(Note::from));
+/// let chord = profile.chord().unwrap();
+///
+/// assert_eq!(chord.value, Chord::from((C, maj7)));
+/// assert_eq!(chord.confidence, 1.0);
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct PitchProfile {
+    weights: [f32; 12],
+    bass: Option<Note>,
+}
+
+impl PitchProfile {
+    /// Creates an empty [`PitchProfile`].
+    pub fn new() -> PitchProfile {
+        PitchProfile::default()
+    }
+
+    /// Creates a [`PitchProfile`] with equal weight for each note.
+    pub fn from_notes(notes: impl IntoIterator<Item = Note>) -> PitchProfile {
+        notes
+            .into_iter()
+            .fold(PitchProfile::new(), |mut profile, note| {
+                profile.add(note, 1.0);
+
+                profile
+            })
+    }
+
+    /// Creates a [`PitchProfile`] from [`PlayNote`] segments overlapping a time window, with each
+    /// note weighted by its duration within the window.
+    #[cfg(feature = "redact-composer")]
+    pub fn from_segments<'a>(
+        notes: impl IntoIterator<Item = SegmentRef<'a, PlayNote>>,
+        window: impl Into<Timing>,
+    ) -> PitchProfile {
+        let window = window.into();
+
+        notes
+            .into_iter()
+            .fold(PitchProfile::new(), |mut profile, segment| {
+                let overlap =
+                    segment.timing.end.min(window.end) - segment.timing.start.max(window.start);
+                if overlap > 0 {
+                    profile.add(Note(segment.element.note), overlap as f32);
+                }
+
+                profile
+            })
+    }
+
+    /// Adds a weighted note to this profile. Non-positive weights are ignored.
+    pub fn add(&mut self, note: Note, weight: f32) {
+        if weight > 0.0 {
+            self.weights[note.pitch_class().0 as usize % 12] += weight;
+            self.bass = Some(self.bass.map_or(note, |bass| bass.min(note)));
+        }
+    }
+
+    /// The accumulated weight of a pitch class.
+    pub fn weight(&self, pitch_class: PitchClass) -> f32 {
+        self.weights[pitch_class.0 as usize % 12]
+    }
+
+    /// The total accumulated weight of all pitch classes.
+    pub fn total(&self) -> f32 {
+        self.weights.iter().sum()
+    }
+
+    /// The lowest note added to this profile, if any.
+    pub fn bass(&self) -> Option<Note> {
+        self.bass
+    }
+
+    /// Returns the most likely [`Chord`] among all [`ChordShape`]s. See [`chords`](Self::chords).
+    pub fn chord(&self) -> Option<Detection<Chord>> {
+        self.chords(&ChordShape::all()).into_iter().next()
+    }
+
+    /// Ranks chords of every root and the given shapes by how well they fit this profile, from
+    /// most to least likely. Chords not sharing any pitch class with this profile are omitted.
+    ///
+    /// A chord's confidence is the fraction of this profile's weight belonging to chord tones,
+    /// multiplied by the fraction of chord tones present. Chords whose root is not the bass note
+    /// are slightly penalized, which resolves otherwise ambiguous chords (such as C6 vs Am7).
+    pub fn chords(&self, shapes: &[ChordShape]) -> Vec<Detection<Chord>> {
+        let total = self.total();
+        if total <= 0.0 {
+            return vec![];
+        }
+
+        let mut detections = PitchClass::values()
+            .into_iter()
+            .flat_map(|root| shapes.iter().map(move |shape| Chord::new(root, *shape)))
+            .filter_map(|chord| {
+                let mut tones = chord.pitch_classes();
+                tones.dedup_by_key(|pc| pc.0 % 12);
+
+                let chord_weight = tones.iter().map(|pc| self.weight(*pc)).sum::<f32>();
+                let present = tones.iter().filter(|pc| self.weight(**pc) > 0.0).count();
+                let bass_factor = match self.bass {
+                    Some(bass) if bass.pitch_class() != chord.root() => 0.9,
+                    _ => 1.0,
+                };
+
+                (present > 0).then_some(Detection {
+                    value: chord,
+                    confidence: (chord_weight / total)
+                        * (present as f32 / tones.len() as f32)
+                        * bass_factor,
+                })
+            })
+            .collect::<Vec<_>>();
+        detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+        detections
+    }
+
+    /// Returns the most likely major or minor [`Key`]. See [`keys`](Self::keys).
+    /// ```
+    /// # use redact_composer_musical::{Key, Note, NoteName::A, PitchProfile, Scale};
+    /// // A natural minor scale, with emphasis on the tonic and fifth
+    /// let notes = [57, 59, 60, 62, 64, 65, 67, 57, 64, 57].map(Note);
+    /// let key = PitchProfile::from_notes(notes).key().unwrap();
+    ///
+    /// assert_eq!(key.value, Key::from((A, Scale::NaturalMinor)));
+    /// ```
+    pub fn key(&self) -> Option<Detection<Key>> {
+        self.keys().into_iter().next()
+    }
+
+    /// Ranks all major ([`Scale::Major`]) and minor ([`Scale::NaturalMinor`]) keys by how well they
+    /// fit this profile, from most to least likely, using the Krumhansl-Schmuckler key-finding
+    /// algorithm.
+    ///
+    /// A key's confidence is the correlation between this profile and the key's profile, clamped to
+    /// `0.0..=1.0`.
+    pub fn keys(&self) -> Vec<Detection<Key>> {
+        if self.total() <= 0.0 {
+            return vec![];
+        }
+
+        let mut detections = PitchClass::values()
+            .into_iter()
+            .flat_map(|tonic| {
+                [
+                    (Scale::Major, MAJOR_PROFILE),
+                    (Scale::NaturalMinor, MINOR_PROFILE),
+                ]
+                .into_iter()
+                .map(move |(scale, profile)| {
+                    let rotated = std::array::from_fn::<f32, 12, _>(|pc| {
+                        profile[(pc + 12 - tonic.0 as usize % 12) % 12]
+                    });
+
+                    Detection {
+                        value: Key::from((tonic, scale)),
+                        confidence: correlation(&self.weights, &rotated).clamp(0.0, 1.0),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+        detections
+    }
+}
+
+// Pearson correlation coefficient.
+fn correlation(a: &[f32; 12], b: &[f32; 12]) -> f32 {
+    let (mean_a, mean_b) = (a.iter().sum::<f32>() / 12.0, b.iter().sum::<f32>() / 12.0);
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+
+    for (a, b) in a.iter().zip(b) {
+        covariance += (a - mean_a) * (b - mean_b);
+        variance_a += (a - mean_a).powi(2);
+        variance_b += (b - mean_b).powi(2);
+    }
+
+    if variance_a <= 0.0 || variance_b <= 0.0 {
+        0.0
+    } else {
+        covariance / (variance_a * variance_b).sqrt()
+    }
+}
+
+/// Detects the most likely [`Chord`] of [`PlayNote`] segments within each time window, returning
+/// a [`Chord`] segment for each window with a detection of at least `min_confidence`. Useful for
+/// annotating imported material with chords.
+/// ```
+/// # use redact_composer_core::{elements::PlayNote, Segment, SegmentRef};
+/// # use redact_composer_musical::{annotate_chords, Chord, ChordShape::{maj, min}, NoteName::{A, C}};
+/// let notes = [(60, 0..4), (64, 0..4), (67, 0..4), (57, 4..8), (60, 4..8), (64, 4..8)]
+///     .map(|(note, timing)| Segment::new(PlayNote { note, velocity: 100 }, timing));
+/// let notes = notes.iter().filter_map(|s| SegmentRef::<PlayNote>::try_from(s).ok()).collect::<Vec<_>>();
+///
+/// let chords = annotate_chords(&notes, [0..4, 4..8], 0.5);
+/// assert_eq!(chords[0].element_as::<Chord>(), Some(&Chord::from((C, maj))));
+/// assert_eq!(chords[1].element_as::<Chord>(), Some(&Chord::from((A, min))));
+/// ```
+#[cfg(feature = "redact-composer")]
+pub fn annotate_chords<'a, T: Into<Timing>>(
+    notes: &[SegmentRef<'a, PlayNote>],
+    windows: impl IntoIterator<Item = T>,
+    min_confidence: f32,
+) -> Vec<Segment> {
+    windows
+        .into_iter()
+        .map(Into::into)
+        .filter_map(|window: Timing| {
+            PitchProfile::from_segments(notes.iter().copied(), window)
+                .chord()
+                .filter(|detection| detection.confidence >= min_confidence)
+                .map(|detection| detection.value.over(window))
+        })
+        .collect()
+}
+
+/// Detects the most likely [`Key`] of [`PlayNote`] segments within each time window, returning a
+/// [`Key`] segment for each window with a detection of at least `min_confidence`. Useful for
+/// annotating imported material with keys.
+#[cfg(feature = "redact-composer")]
+pub fn annotate_keys<'a, T: Into<Timing>>(
+    notes: &[SegmentRef<'a, PlayNote>],
+    windows: impl IntoIterator<Item = T>,
+    min_confidence: f32,
+) -> Vec<Segment> {
+    windows
+        .into_iter()
+        .map(Into::into)
+        .filter_map(|window: Timing| {
+            PitchProfile::from_segments(notes.iter().copied(), window)
+                .key()
+                .filter(|detection| detection.confidence >= min_confidence)
+                .map(|detection| detection.value.over(window))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PitchProfile;
+    use crate::ChordShape::{maj, maj6, min, min7, sus2, sus4};
+    use crate::NoteName::{A, C, D, E, F, G};
+    use crate::{Chord, Key, Note, Scale};
+
+    fn profile(notes: &[u8]) -> PitchProfile {
+        PitchProfile::from_notes(notes.iter().copied().map(Note))
+    }
+
+    #[test]
+    fn chord_detection() {
+        assert_eq!(
+            profile(&[60, 64, 67]).chord().unwrap().value,
+            Chord::from((C, maj))
+        );
+        assert_eq!(
+            profile(&[57, 60, 64]).chord().unwrap().value,
+            Chord::from((A, min))
+        );
+
+        // Inversions are still detected, at slightly lower confidence
+        let inverted = profile(&[52, 55, 60]).chord().unwrap();
+        assert_eq!(inverted.value, Chord::from((C, maj)));
+        assert!(inverted.confidence < 1.0 && inverted.confidence >= 0.9);
+
+        // Ambiguous pitch classes resolved by the bass note
+        assert_eq!(
+            profile(&[48, 64, 67, 69]).chord().unwrap().value,
+            Chord::from((C, maj6))
+        );
+        assert_eq!(
+            profile(&[45, 60, 64, 67]).chord().unwrap().value,
+            Chord::from((A, min7))
+        );
+
+        // Sus chords are symmetrical, C sus4 == F sus2
+        let shapes = [sus2, sus4];
+        assert_eq!(
+            profile(&[48, 53, 55]).chords(&shapes)[0].value,
+            Chord::from((C, sus4))
+        );
+        assert_eq!(
+            profile(&[53, 55, 60]).chords(&shapes)[0].value,
+            Chord::from((F, sus2))
+        );
+
+        // Non-chord tones reduce confidence (C D E G is an exact Cadd9, so limit shapes to triads)
+        let passing = profile(&[60, 62, 64, 67]).chords(&[maj, min])[0];
+        assert_eq!(passing.value.root(), C);
+        assert!(passing.confidence < 1.0);
+
+        assert!(PitchProfile::new().chord().is_none());
+    }
+
+    #[test]
+    fn key_detection() {
+        let c_major_scale = profile(&[60, 62, 64, 65, 67, 69, 71, 72, 67, 60]);
+        assert_eq!(
+            c_major_scale.key().unwrap().value,
+            Key::from((C, Scale::Major))
+        );
+
+        let g_major_melody = profile(&[67, 71, 74, 66, 69, 72, 67, 62, 67]);
+        assert_eq!(
+            g_major_melody.key().unwrap().value,
+            Key::from((G, Scale::Major))
+        );
+
+        let d_minor = profile(&[62, 65, 69, 62, 64, 65, 67, 69, 70, 61, 62]);
+        let keys = d_minor.keys();
+        assert_eq!(keys[0].value, Key::from((D, Scale::NaturalMinor)));
+        assert!(keys.windows(2).all(|w| w[0].confidence >= w[1].confidence));
+        assert_eq!(keys.len(), 24);
+
+        assert!(keys[0].confidence > 0.5);
+        assert!(profile(&[64]).key().unwrap().confidence < keys[0].confidence);
+        assert_eq!(profile(&[64]).keys()[0].value.root(), E);
+        assert!(PitchProfile::new().key().is_none());
+    }
+}
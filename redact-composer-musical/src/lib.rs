@@ -25,6 +25,9 @@ pub use key::*;
 mod scale;
 pub use scale::*;
 
+mod analysis;
+pub use analysis::*;
+
 /// Types implementing [`Element`](redact_composer_core::Element).
 #[cfg(feature = "redact-composer")]
 pub mod elements {
@@ -1,12 +1,19 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
 #[cfg(feature = "redact-composer")]
-use redact_composer_core::derive::Element;
+use redact_composer_core::{derive::Element, SegmentRef};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 /// A musical time signature as a combination of beats per bar, and beat length.
 #[derive(Debug, Copy, Clone)]
-#[cfg_attr(feature = "redact-composer", derive(Element))]
+#[cfg_attr(
+    feature = "redact-composer",
+    derive(Element),
+    element(ticks(beat_length))
+)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TimeSignature {
     /// The number of beats per bar.
@@ -16,6 +23,12 @@ pub struct TimeSignature {
 }
 
 impl TimeSignature {
+    /// Whether this time signature has a positive number of beats per bar, and a positive beat
+    /// length.
+    pub fn is_valid(&self) -> bool {
+        self.beats_per_bar > 0 && self.beat_length > 0
+    }
+
     /// Length of a bar in ticks.
     pub fn bar(&self) -> i32 {
         self.beats_per_bar * self.beat_length
@@ -86,3 +99,280 @@ impl TimeSignature {
         self.eighth_beat() * n
     }
 }
+
+/// A position expressed in musical terms, as opposed to raw ticks. `bar` and `beat` are 1-based
+/// (the first beat of the first bar is `1:1`), while `tick` is the 0-based tick offset within the
+/// beat.
+///
+/// Conversion to/from ticks depends on the time signature(s) in effect, see [`TimeSignatureMap`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MusicalTime {
+    /// The (1-based) bar number.
+    pub bar: i32,
+    /// The (1-based) beat number within the bar.
+    pub beat: i32,
+    /// The (0-based) tick offset within the beat.
+    pub tick: i32,
+}
+
+impl MusicalTime {
+    /// Creates a [`MusicalTime`] at the given bar, beat and tick.
+    pub fn new(bar: i32, beat: i32, tick: i32) -> MusicalTime {
+        MusicalTime { bar, beat, tick }
+    }
+
+    /// Creates a [`MusicalTime`] at the start of the given bar.
+    pub fn bar(bar: i32) -> MusicalTime {
+        MusicalTime::new(bar, 1, 0)
+    }
+}
+
+impl From<(i32, i32)> for MusicalTime {
+    fn from((bar, beat): (i32, i32)) -> Self {
+        MusicalTime::new(bar, beat, 0)
+    }
+}
+
+impl From<(i32, i32, i32)> for MusicalTime {
+    fn from((bar, beat, tick): (i32, i32, i32)) -> Self {
+        MusicalTime::new(bar, beat, tick)
+    }
+}
+
+impl Display for MusicalTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.bar, self.beat, self.tick)
+    }
+}
+
+/// Converts between ticks and [`MusicalTime`] according to a sequence of [`TimeSignature`]s, each
+/// taking effect at a given tick.
+///
+/// Bars are counted from the first time signature's start tick. A time signature change always
+/// begins a new bar, so a change landing mid-bar leaves the preceding bar incomplete (but still
+/// counted). Positions before the first time signature use the first time signature. Invalid time
+/// signatures (see [`TimeSignature::is_valid`]) are never part of the map.
+/// ```
+/// # use redact_composer_musical::{MusicalTime, TimeSignature, TimeSignatureMap};
+/// let four_four = TimeSignature { beats_per_bar: 4, beat_length: 480 };
+/// let three_four = TimeSignature { beats_per_bar: 3, beat_length: 480 };
+///
+/// // 4/4 for the first 2 bars, then 3/4
+/// let map = TimeSignatureMap::new(four_four)
+///     .unwrap()
+///     .with_change(four_four.bars(2), three_four);
+///
+/// assert_eq!(map.ticks((2, 1)), four_four.bar());
+/// assert_eq!(map.ticks((3, 2)), four_four.bars(2) + three_four.beat());
+/// assert_eq!(map.musical_time(four_four.bars(2) + three_four.bar()), MusicalTime::bar(4));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimeSignatureMap {
+    /// Sections ordered by start tick, each as `(start_tick, start_bar_idx, time_signature)`.
+    sections: Vec<(i32, i32, TimeSignature)>,
+}
+
+impl TimeSignatureMap {
+    /// Creates a map with a single time signature starting at tick `0`. Returns [`None`] if the
+    /// time signature is invalid.
+    pub fn new(time_signature: TimeSignature) -> Option<TimeSignatureMap> {
+        time_signature.is_valid().then(|| TimeSignatureMap {
+            sections: vec![(0, 0, time_signature)],
+        })
+    }
+
+    /// Creates a map from `(start_tick, time_signature)` pairs (in any order), skipping invalid
+    /// time signatures. Returns [`None`] if there are no valid time signatures. If multiple time
+    /// signatures start at the same tick, the last one is used.
+    pub fn from_changes(
+        changes: impl IntoIterator<Item = (i32, TimeSignature)>,
+    ) -> Option<TimeSignatureMap> {
+        let mut changes = changes
+            .into_iter()
+            .filter(|(_, time_signature)| time_signature.is_valid())
+            .collect::<Vec<_>>();
+        changes.sort_by_key(|(start, _)| *start);
+
+        let mut changes = changes.into_iter();
+        let (start, first) = changes.next()?;
+        let mut map = TimeSignatureMap {
+            sections: vec![(start, 0, first)],
+        };
+        for (start, time_signature) in changes {
+            map = map.with_change(start, time_signature);
+        }
+
+        Some(map)
+    }
+
+    /// Creates a map from [`TimeSignature`] segments, such as those returned from a
+    /// `find::<TimeSignature>()` context query. Returns [`None`] if there are no valid time
+    /// signatures.
+    #[cfg(feature = "redact-composer")]
+    pub fn from_segments<'a>(
+        segments: impl IntoIterator<Item = SegmentRef<'a, TimeSignature>>,
+    ) -> Option<TimeSignatureMap> {
+        Self::from_changes(
+            segments
+                .into_iter()
+                .map(|segment| (segment.timing.start, *segment.element)),
+        )
+    }
+
+    /// Adds a time signature change taking effect at `start`. Any existing changes at or after
+    /// `start` are replaced. Invalid time signatures are ignored, leaving the map unchanged.
+    pub fn with_change(mut self, start: i32, time_signature: TimeSignature) -> TimeSignatureMap {
+        if !time_signature.is_valid() {
+            return self;
+        }
+        self.sections.retain(|(s, _, _)| *s < start);
+
+        let start_bar = match self.sections.last() {
+            Some((prev_start, prev_bar, prev_ts)) => {
+                let prev_bar_len = prev_ts.bar();
+                // Incomplete bars (from a mid-bar change) still count as a bar
+                prev_bar + (start - prev_start + prev_bar_len - 1) / prev_bar_len
+            }
+            None => 0,
+        };
+        self.sections.push((start, start_bar, time_signature));
+
+        self
+    }
+
+    /// Returns the time signature in effect at the given tick.
+    pub fn time_signature_at(&self, tick: i32) -> TimeSignature {
+        self.section_at_tick(tick).2
+    }
+
+    /// Converts a tick position to its [`MusicalTime`].
+    pub fn musical_time(&self, tick: i32) -> MusicalTime {
+        let (start, start_bar, ts) = self.section_at_tick(tick);
+        let offset = tick - start;
+        let in_bar = offset.rem_euclid(ts.bar());
+
+        MusicalTime {
+            bar: start_bar + offset.div_euclid(ts.bar()) + 1,
+            beat: in_bar / ts.beat() + 1,
+            tick: in_bar % ts.beat(),
+        }
+    }
+
+    /// Converts a [`MusicalTime`] to its tick position. Beats or ticks exceeding the length of
+    /// their bar/beat simply continue on from there.
+    pub fn ticks(&self, time: impl Into<MusicalTime>) -> i32 {
+        let time = time.into();
+        let bar_idx = time.bar - 1;
+        let (start, start_bar, ts) = *self
+            .sections
+            .iter()
+            .rev()
+            .find(|(_, start_bar, _)| *start_bar <= bar_idx)
+            .unwrap_or(&self.sections[0]);
+
+        start + (bar_idx - start_bar) * ts.bar() + (time.beat - 1) * ts.beat() + time.tick
+    }
+
+    /// Returns the tick range of the given (1-based) bar.
+    pub fn bar_range(&self, bar: i32) -> Range<i32> {
+        let start = self.ticks(MusicalTime::bar(bar));
+        let ts = self.time_signature_at(start);
+        let next_change = self
+            .sections
+            .iter()
+            .map(|(s, _, _)| *s)
+            .find(|s| *s > start)
+            .unwrap_or(i32::MAX);
+
+        start..(start + ts.bar()).min(next_change)
+    }
+
+    fn section_at_tick(&self, tick: i32) -> (i32, i32, TimeSignature) {
+        *self
+            .sections
+            .iter()
+            .rev()
+            .find(|(start, _, _)| *start <= tick)
+            .unwrap_or(&self.sections[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MusicalTime, TimeSignature, TimeSignatureMap};
+
+    const FOUR_FOUR: TimeSignature = TimeSignature {
+        beats_per_bar: 4,
+        beat_length: 480,
+    };
+    const SIX_EIGHT: TimeSignature = TimeSignature {
+        beats_per_bar: 6,
+        beat_length: 240,
+    };
+
+    #[test]
+    fn single_time_signature_round_trip() {
+        let map = TimeSignatureMap::new(FOUR_FOUR).unwrap();
+
+        assert_eq!(map.ticks((17, 3)), FOUR_FOUR.bars(16) + FOUR_FOUR.beats(2));
+        for tick in [0, 1, 479, 480, 1919, 1920, 12345] {
+            assert_eq!(map.ticks(map.musical_time(tick)), tick);
+        }
+        assert_eq!(map.musical_time(-1), MusicalTime::new(0, 4, 479));
+    }
+
+    #[test]
+    fn mid_piece_changes() {
+        // 2 bars of 4/4, 1 bar of 6/8, then 4/4 again (starting mid-bar)
+        let change = FOUR_FOUR.bars(2) + SIX_EIGHT.bar();
+        let map = TimeSignatureMap::from_changes([
+            (change + 100, FOUR_FOUR),
+            (0, FOUR_FOUR),
+            (FOUR_FOUR.bars(2), SIX_EIGHT),
+        ])
+        .unwrap();
+
+        assert_eq!(map.musical_time(FOUR_FOUR.bars(2)), MusicalTime::bar(3));
+        assert_eq!(
+            map.musical_time(FOUR_FOUR.bars(2) + SIX_EIGHT.beats(5)),
+            MusicalTime::new(3, 6, 0)
+        );
+        // The incomplete bar 4 is cut short by the change
+        assert_eq!(map.musical_time(change), MusicalTime::bar(4));
+        assert_eq!(map.bar_range(4), change..change + 100);
+        assert_eq!(map.ticks(MusicalTime::bar(5)), change + 100);
+        assert_eq!(
+            map.musical_time(change + 100 + 481),
+            MusicalTime::new(5, 2, 1)
+        );
+        assert_eq!(map.time_signature_at(change - 1).beats_per_bar, 6);
+    }
+
+    #[test]
+    fn invalid_time_signatures() {
+        let zero_length = TimeSignature {
+            beats_per_bar: 4,
+            beat_length: 0,
+        };
+        let negative_beats = TimeSignature {
+            beats_per_bar: -3,
+            beat_length: 480,
+        };
+
+        assert!(TimeSignatureMap::new(zero_length).is_none());
+        assert!(
+            TimeSignatureMap::from_changes([(0, zero_length), (960, negative_beats)]).is_none()
+        );
+
+        let map = TimeSignatureMap::from_changes([(0, negative_beats), (960, FOUR_FOUR)])
+            .unwrap()
+            .with_change(FOUR_FOUR.bars(2), zero_length);
+        assert_eq!(map.time_signature_at(0).beat_length, 480);
+        assert_eq!(
+            map.musical_time(960 + FOUR_FOUR.bars(3)),
+            MusicalTime::bar(4)
+        );
+        assert_eq!(map.ticks(MusicalTime::bar(3)), 960 + FOUR_FOUR.bars(2));
+    }
+}
@@ -14,6 +14,8 @@ pub enum SynthesisError {
     WavError(#[from] hound::Error),
     #[error("Midi error: {:?}", .0)]
     MidiError(#[from] MidiFileError),
+    #[error("Midi parse error: {:?}", .0)]
+    MidiParseError(#[from] midly::Error),
     #[error("Synthesizer error: {:?}", .0)]
     SynthesizerError(#[from] SynthesizerError),
 }
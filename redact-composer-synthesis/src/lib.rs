@@ -19,6 +19,11 @@
 //! .unwrap();
 //! ```
 //!
+//! Per-[`Part`](redact_composer_core::elements::Part) mix settings (volume, pan, reverb and chorus
+//! sends) given by [`Mix`](redact_composer_midi::elements::Mix) elements are applied during
+//! synthesis, and events are timed according to the composition's
+//! [`TempoMap`] (including any tempo ramps).
+//!
 //! ## Options
 //! [`SF2Synthesizer`] defaults to 44.1kHz sample rate with a bit-depth of 16, but can be customized
 //! if desired.
@@ -41,7 +46,8 @@ mod test;
 use crate::error::SynthesisError;
 use hound::{SampleFormat, WavSpec, WavWriter};
 use log::{debug, info};
-use midly::Smf;
+use midly::{MidiMessage, Smf, TrackEventKind};
+use redact_composer_core::timing::TempoMap;
 use redact_composer_core::Composition;
 use redact_composer_midi::convert::MidiConverter;
 pub use rustysynth::SoundFont;
@@ -97,6 +103,15 @@ impl SF2Synthesizer {
         })
     }
 
+    /// Returns the index of the sample (within the synthesized output) at which the given
+    /// composition tick occurs, according to the composition's [`TempoMap`]. Useful for aligning
+    /// synthesized audio with composition events.
+    pub fn sample_at(&self, composition: &Composition, tick: i32) -> usize {
+        let seconds = composition.tempo_map().seconds_at(tick).max(0.0);
+
+        (seconds * self.options.sample_rate as f64).round() as usize
+    }
+
     /// Prepares a synthesis request for the given content. Use further chained calls to initiate
     /// synthesis -- such as [`to_file`](SF2SynthesisRequest::to_file), [`write`](SF2SynthesisRequest::write)
     /// or [`to_raw_stereo_waveforms`](SF2SynthesisRequest::to_raw_stereo_waveforms).
@@ -113,6 +128,10 @@ impl MidiBytesProvider for Composition {
         let smf = MidiConverter::convert(self);
         smf.midi_bytes()
     }
+
+    fn tempo_map(&self) -> Option<TempoMap> {
+        Some(Composition::tempo_map(self))
+    }
 }
 
 impl MidiBytesProvider for Smf<'_> {
@@ -143,6 +162,14 @@ pub trait SF2Synthesizable<M: MidiBytesProvider> {
 pub trait MidiBytesProvider {
     /// Return the midi file bytes for this type.
     fn midi_bytes(&self) -> Vec<u8>;
+
+    /// Returns a [`TempoMap`] used to time the midi events during synthesis, instead of the midi
+    /// file's own tempo events.
+    ///
+    /// **Default:** `None`, timing events according to the midi file.
+    fn tempo_map(&self) -> Option<TempoMap> {
+        None
+    }
 }
 
 /// A synthesis request, which can be processed to multiple output types.
@@ -197,24 +224,37 @@ impl<M: MidiBytesProvider> SF2SynthesisRequest<'_, M> {
         debug!("{:?}", self.synth.options);
         let start_instant = std::time::Instant::now();
         let midi_bytes = self.midi_reader.midi_bytes();
-        let midi_file = Arc::new(MidiFile::new(&mut &midi_bytes[..])?);
 
-        // Create a RustySynth MIDI file sequencer.
         let settings = SynthesizerSettings::new(self.synth.options.sample_rate as i32);
         let synthesizer = Synthesizer::new(&self.synth.sound_font, &settings)?;
-        let mut sequencer = MidiFileSequencer::new(synthesizer);
-
-        // Play our midi file through the sequencer
-        sequencer.play(&midi_file, false);
-
-        // Create two sample buffers for left and right stereo channels
-        // Adds an additional 10 seconds at the end to account for trailoff
-        let sample_count = (settings.sample_rate as f64 * (midi_file.get_length() + 10.0)) as usize;
-        let mut left: Vec<f32> = vec![0_f32; sample_count];
-        let mut right: Vec<f32> = vec![0_f32; sample_count];
-
-        // Render the waveforms into the sample buffers.
-        sequencer.render(&mut left[..], &mut right[..]);
+        let (mut left, mut right) = match self.midi_reader.tempo_map() {
+            Some(tempo_map) => {
+                let smf = Smf::parse(&midi_bytes)?;
+                render_with_tempo_map(synthesizer, &smf, &tempo_map)
+            }
+            None => {
+                let midi_file = Arc::new(MidiFile::new(&mut &midi_bytes[..])?);
+
+                // Create a RustySynth MIDI file sequencer.
+                let mut sequencer = MidiFileSequencer::new(synthesizer);
+
+                // Play our midi file through the sequencer
+                sequencer.play(&midi_file, false);
+
+                // Create two sample buffers for left and right stereo channels
+                // Adds an additional 10 seconds at the end to account for trailoff
+                let sample_count = (settings.sample_rate as f64
+                    * (midi_file.get_length() + TRAILOFF_SECONDS))
+                    as usize;
+                let mut left: Vec<f32> = vec![0_f32; sample_count];
+                let mut right: Vec<f32> = vec![0_f32; sample_count];
+
+                // Render the waveforms into the sample buffers.
+                sequencer.render(&mut left[..], &mut right[..]);
+
+                (left, right)
+            }
+        };
 
         // Trim the final period of silence at the end of the buffers
         let end_trim_range = get_end_trim_range(&left, &right);
@@ -234,6 +274,83 @@ impl<M: MidiBytesProvider> SF2SynthesisRequest<'_, M> {
     }
 }
 
+// Additional time rendered after the last event to account for trailoff.
+const TRAILOFF_SECONDS: f64 = 10.0;
+
+// Renders the midi events of `smf` with the synthesizer, each at the sample given by the tempo
+// map. Returns the left and right channel samples.
+fn render_with_tempo_map(
+    mut synthesizer: Synthesizer,
+    smf: &Smf,
+    tempo_map: &TempoMap,
+) -> (Vec<f32>, Vec<f32>) {
+    let sample_rate = synthesizer.get_sample_rate() as f64;
+    let sample_at = |tick: u32| {
+        (tempo_map
+            .seconds_at(tick.min(i32::MAX as u32) as i32)
+            .max(0.0)
+            * sample_rate)
+            .round() as usize
+    };
+
+    let mut events = smf
+        .tracks
+        .iter()
+        .flat_map(|track| {
+            let mut tick = 0_u32;
+            track.iter().filter_map(move |event| {
+                tick = tick.saturating_add(event.delta.as_int());
+                match event.kind {
+                    TrackEventKind::Midi { channel, message } => {
+                        Some((tick, i32::from(channel.as_int()), message))
+                    }
+                    _ => None,
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    // Stable, keeping the order of simultaneous events
+    events.sort_by_key(|(tick, _, _)| *tick);
+
+    let end = events.last().map_or(0, |(tick, _, _)| sample_at(*tick));
+    let sample_count = end + (sample_rate * TRAILOFF_SECONDS) as usize;
+    let mut left: Vec<f32> = vec![0_f32; sample_count];
+    let mut right: Vec<f32> = vec![0_f32; sample_count];
+
+    let mut rendered = 0;
+    for (tick, channel, message) in events {
+        let sample = sample_at(tick);
+        if sample > rendered {
+            synthesizer.render(&mut left[rendered..sample], &mut right[rendered..sample]);
+            rendered = sample;
+        }
+
+        let (command, data1, data2) = midi_message_data(message);
+        synthesizer.process_midi_message(channel, command, data1, data2);
+    }
+    synthesizer.render(&mut left[rendered..], &mut right[rendered..]);
+
+    (left, right)
+}
+
+// Splits a midi message into its command (status without channel) and data bytes.
+fn midi_message_data(message: MidiMessage) -> (i32, i32, i32) {
+    match message {
+        MidiMessage::NoteOff { key, vel } => (0x80, key.as_int().into(), vel.as_int().into()),
+        MidiMessage::NoteOn { key, vel } => (0x90, key.as_int().into(), vel.as_int().into()),
+        MidiMessage::Aftertouch { key, vel } => (0xA0, key.as_int().into(), vel.as_int().into()),
+        MidiMessage::Controller { controller, value } => {
+            (0xB0, controller.as_int().into(), value.as_int().into())
+        }
+        MidiMessage::ProgramChange { program } => (0xC0, program.as_int().into(), 0),
+        MidiMessage::ChannelAftertouch { vel } => (0xD0, vel.as_int().into(), 0),
+        MidiMessage::PitchBend { bend } => {
+            let bend = i32::from(bend.0.as_int());
+            (0xE0, bend & 0x7F, bend >> 7)
+        }
+    }
+}
+
 // Scales the left/right sample buffers so their samples fit snuggly in the range [-1.0, 1.0].
 fn normalize(left: &mut [f32], right: &mut [f32]) {
     let abs_max = left
@@ -5,6 +5,7 @@ use redact_composer_core::elements::Part;
 use redact_composer_core::render::{AdhocRenderer, RenderEngine};
 use redact_composer_core::timing::Tempo;
 use redact_composer_core::{Composer, IntoSegment};
+use redact_composer_midi::elements::Mix;
 use redact_composer_musical::Note;
 use redact_composer_musical::NoteName::{A, B, C, E, F};
 use serde::{Deserialize, Serialize};
@@ -87,6 +88,37 @@ pub fn test_soundfont_synthesis() {
     }
 }
 
+#[test]
+pub fn test_soundfont_synthesis_with_mix() {
+    let composer = Composer::from(
+        RenderEngine::new()
+            + AdhocRenderer::<SynthComp>::new(|segment, _| {
+                Ok(vec![
+                    Note::from((C, 4)).play(100).over(segment),
+                    Tempo::from_bpm(60).over(segment),
+                    // Panned hard left
+                    Mix::new().volume(100).pan(0).over(segment),
+                ])
+            }),
+    );
+    let composition =
+        composer.compose(Part::instrument(SynthComp).over(0..composer.options.ticks_per_beat));
+
+    let synth = SF2Synthesizer::new(SF2_TEST_FILE).expect("Error creating SF2Synthesizer");
+    let (left, right) = synth
+        .synthesize(&composition)
+        .to_raw_stereo_waveforms()
+        .expect("Error during synthesis");
+
+    let energy = |samples: &[f32]| samples.iter().map(|s| s * s).sum::<f32>();
+    assert!(
+        energy(&left) > energy(&right) * 4.0,
+        "Expected the left channel to be louder (left: {:?}, right: {:?})",
+        energy(&left),
+        energy(&right)
+    );
+}
+
 #[test]
 pub fn test_soundfont_synthesis_to_file() {
     let composer = test_synth_composer();
@@ -152,3 +184,44 @@ pub fn debug_display() {
     let synth = SF2Synthesizer::new(SF2_TEST_FILE).expect("Error creating SF2Synthesizer");
     assert_eq!(format!("{:?}", synth), "SF2Synthesizer { sound_font: \"Tiny Sine\", options: SoundFontSynthesizerOptions { sample_rate: 44100, bit_depth: 16 } }");
 }
+
+#[test]
+pub fn test_soundfont_synthesis_with_tempo_changes() {
+    use redact_composer_core::timing::TempoRamp;
+
+    let composer = Composer::from(
+        RenderEngine::new()
+            + AdhocRenderer::<SynthComp>::new(|_, _| {
+                Ok(vec![
+                    Note::from((C, 4)).play(100).over(0..480),
+                    Note::from((E, 4)).play(100).over(1920..2400),
+                    Tempo::from_bpm(60).over(0..960),
+                    TempoRamp::from_bpm(60, 240).over(960..1920),
+                    Tempo::from_bpm(240).over(1920..2400),
+                ])
+            }),
+    );
+    let composition = composer.compose(Part::instrument(SynthComp).over(0..2400));
+
+    let synth = SF2Synthesizer::new(SF2_TEST_FILE).expect("Error creating SF2Synthesizer");
+    let (left, _) = synth
+        .synthesize(&composition)
+        .to_raw_stereo_waveforms()
+        .expect("Error during synthesis");
+
+    // The second note starts after an accelerando and tempo change, where its sample is given by the
+    // composition's tempo map
+    let onset = synth.sample_at(&composition, 1920);
+    let first_sample = left[(onset - 4410)..]
+        .iter()
+        .position(|s| *s != 0.0)
+        .map(|idx| idx + onset - 4410)
+        .expect("Expected the second note to be synthesized");
+    // Allowing for the synthesizer's block size (64 samples)
+    assert!(
+        (onset..onset + 64).contains(&first_sample),
+        "Expected the second note to start at sample {:?}, but started at {:?}",
+        onset,
+        first_sample
+    );
+}
@@ -6,22 +6,32 @@ pub mod util;
 
 // Re-export core components
 pub use redact_composer_core::{
-    elements, error, render::Renderer, timing, timing::Timing, Composer, ComposerOptions,
-    Composition, CompositionOptions, Element, Segment, SegmentRef,
+    elements, error, observer, render::Renderer, timing, timing::Timing, Composer, ComposerOptions,
+    Composition, CompositionOptions, Element, RescaleReport, Segment, SegmentRef,
 };
 
 /// Types and traits used for and during composition rendering.
 pub mod render {
     pub use redact_composer_core::render::{
-        context, AdhocRenderer, RenderEngine, RenderSegment, Renderer, RendererGroup, Result,
+        context, stats, validation, AdhocRenderer, RenderEngine, RenderSegment, Renderer,
+        RendererGroup, RendererRegistration, Result,
     };
 }
 
+// Not public API. Used by generated code from the derive crate.
+#[doc(hidden)]
+pub use redact_composer_core::__private;
+
 #[cfg(feature = "derive")]
 #[doc(inline)]
 /// `feature = derive (default)`
 pub use redact_composer_derive::Element;
 
+#[cfg(feature = "derive")]
+#[doc(inline)]
+/// `feature = derive (default)`
+pub use redact_composer_derive::renderer;
+
 #[cfg(feature = "midi")]
 #[doc(inline)]
 /// `feature = midi (default)`
@@ -37,8 +47,9 @@ pub use redact_composer_synthesis as synthesis;
 /// `feature = musical (default)`
 pub use redact_composer_musical as musical;
 
-/// Default renderers for [`midi`] elements if `midi` feature is enabled (default).
-/// Otherwise, just an empty [`RenderEngine`](crate::render::RenderEngine).
+/// Default renderers for [`midi`] elements if `midi` feature is enabled (default), along with all
+/// renderers registered via the `#[renderer]` attribute macro (see
+/// [`RenderEngine::registered`](crate::render::RenderEngine::registered)).
 pub fn renderers() -> crate::render::RenderEngine {
     let mut engine = crate::render::RenderEngine::new();
 
@@ -47,5 +58,5 @@ pub fn renderers() -> crate::render::RenderEngine {
         engine = engine + midi::renderers();
     }
 
-    engine
+    engine + crate::render::RenderEngine::registered()
 }